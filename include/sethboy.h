@@ -0,0 +1,130 @@
+#ifndef SETHBOY_H
+#define SETHBOY_H
+
+/* Generated with cbindgen from src/ffi.rs */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef struct Emulator Emulator;
+
+/**
+ * What the frame callback gets. `framebuffer` is 160x144 rgba pixels, only
+ * valid during the call. `audio_samples` is how many sample frames the
+ * frame made, ready for `sethboy_read_audio`.
+ */
+typedef struct SethboyFrameInfo {
+  const uint8_t *framebuffer;
+  uint64_t frame;
+  uint64_t cycles;
+  uintptr_t audio_samples;
+  uint8_t buttons;
+} SethboyFrameInfo;
+
+typedef void (*SethboyFrameCallback)(const SethboyFrameInfo *info, void *user_data);
+
+/**
+ * Free it with `sethboy_free`.
+ */
+Emulator *sethboy_new(void);
+
+/**
+ * # Safety
+ * `emu` must come from `sethboy_new` and not be used afterwards.
+ */
+void sethboy_free(Emulator *emu);
+
+/**
+ * Returns 0 on success, or -1 if the rom is too short or too long. A header
+ * that gets the rom's size wrong isn't an error.
+ *
+ * # Safety
+ * `emu` must come from `sethboy_new`, and `rom` must point to `len` bytes.
+ */
+int sethboy_load_rom(Emulator *emu, const uint8_t *rom, size_t len);
+
+/**
+ * Runs until the next frame. Returns 1 if there's a new frame, which isn't
+ * the case while the lcd is off.
+ *
+ * # Safety
+ * `emu` must come from `sethboy_new` and have a rom loaded.
+ */
+int sethboy_run_frame(Emulator *emu);
+
+/**
+ * The screen as 160x144 rgba pixels. Valid until the next call taking `emu`.
+ *
+ * # Safety
+ * `emu` must come from `sethboy_new`.
+ */
+const uint8_t *sethboy_framebuffer(Emulator *emu);
+
+/**
+ * Sets which buttons are held, a bit each: right, left, up, down, a, b,
+ * select, start from the lowest bit up.
+ *
+ * # Safety
+ * `emu` must come from `sethboy_new`.
+ */
+void sethboy_set_buttons(Emulator *emu, uint8_t buttons);
+
+/**
+ * Copies the emulator's state into `out`, which has room for `*len` bytes,
+ * and sets `*len` to the state's size. Returns 0 on success, or -1 if it
+ * didn't fit and nothing was copied, which is how a null `out` with a `*len`
+ * of 0 gets the size. The size can change by a few bytes as the game runs.
+ *
+ * # Safety
+ * `emu` must come from `sethboy_new`, `len` must be valid to read and write,
+ * and `out` must have room for `*len` bytes unless it's null.
+ */
+int sethboy_save_state(Emulator *emu, uint8_t *out, size_t *len);
+
+/**
+ * Loads a state from `sethboy_save_state`. Returns 0 on success, -1 if it
+ * isn't a state, -2 if it's from another version, -3 if it's from another
+ * rom, or -4 if it's corrupt. Nothing changes unless it loads.
+ *
+ * # Safety
+ * `emu` must come from `sethboy_new`, and `state` must point to `len` bytes.
+ */
+int sethboy_load_state(Emulator *emu, const uint8_t *state, size_t len);
+
+/**
+ * Sample frames a second, 48000 to start with, or 0 to not make any.
+ * Returns 0 on success, or -1 for a rate from 1 to 7999, which changes
+ * nothing.
+ *
+ * # Safety
+ * `emu` must come from `sethboy_new`.
+ */
+int sethboy_set_sample_rate(Emulator *emu, uint32_t rate);
+
+/**
+ * Copies up to `len` samples of sound into `out`, left then right for each
+ * sample frame, and returns how many. Only the last quarter of a second is
+ * kept, so call it at least that often.
+ *
+ * # Safety
+ * `emu` must come from `sethboy_new`, and `out` must have room for `len`
+ * samples.
+ */
+size_t sethboy_read_audio(Emulator *emu, int16_t *out, size_t len);
+
+/**
+ * Calls `callback` with `user_data` at the end of every frame, from inside
+ * `sethboy_run_frame`. Pass a null callback to stop.
+ *
+ * # Safety
+ * `emu` must come from `sethboy_new`, and `user_data` must stay usable for
+ * as long as the callback is set.
+ */
+void sethboy_set_frame_callback(Emulator *emu,
+                                SethboyFrameCallback callback,
+                                void *user_data);
+
+#endif /* SETHBOY_H */
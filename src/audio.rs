@@ -0,0 +1,164 @@
+// sound out through an sdl queue, a frame's worth at a time. the [audio]
+// section of the config sets the device's buffer and how far ahead the
+// queue is kept, since what's acceptable differs a lot between speakers and
+// bluetooth headphones
+use std::{
+    borrow::Cow,
+    iter,
+    time::{Duration, Instant},
+};
+
+use sdl2::{
+    AudioSubsystem,
+    audio::{AudioQueue, AudioSpecDesired},
+};
+
+use gameboy::emulator::SAMPLE_RATE;
+
+use crate::{FRAME_DUR, config::Config};
+
+// sample frames in the device's buffer
+const BUFFER: u16 = 512;
+const LATENCY: Duration = Duration::from_millis(50);
+
+pub struct AudioSettings {
+    buffer: u16,
+    latency: Duration,
+}
+
+impl AudioSettings {
+    // the buffer is in sample frames and the latency in milliseconds
+    pub fn load(config: &Config) -> Result<Self, String> {
+        let path = config.path().display();
+        let buffer = match config.get("audio", "buffer").map(str::parse::<u16>) {
+            // sdl wants a power of two
+            Some(Ok(n)) if n.is_power_of_two() => n,
+            Some(_) => return Err(format!("{path}: audio.buffer must be a power of two")),
+            None => BUFFER,
+        };
+        let latency = match config.get("audio", "latency").map(str::parse) {
+            Some(Ok(ms)) => Duration::from_millis(ms),
+            Some(Err(_)) => return Err(format!("{path}: audio.latency must be milliseconds")),
+            None => LATENCY,
+        };
+        Ok(AudioSettings { buffer, latency })
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct AudioStats {
+    // times the queue ran dry while frames were coming
+    pub underruns: u64,
+    // frames dropped because the queue was already twice as full as it
+    // should be
+    pub overruns: u64,
+    pub queued: Duration,
+    // what the queue's kept at
+    pub latency: Duration,
+}
+
+impl AudioStats {
+    pub fn report(&self) -> String {
+        format!(
+            "audio: {} underruns, {} overruns, {:.1}ms queued",
+            self.underruns,
+            self.overruns,
+            self.queued.as_secs_f64() * 1000.0
+        )
+    }
+}
+
+pub struct AudioOut {
+    queue: AudioQueue<i16>,
+    // sample frames a second, and samples to each of those
+    freq: u32,
+    channels: u32,
+    // in bytes, like the queue's size
+    target: u32,
+    last: Option<Instant>,
+    stats: AudioStats,
+}
+
+impl AudioOut {
+    pub fn open(audio: &AudioSubsystem, settings: &AudioSettings) -> Result<Self, String> {
+        let desired = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE as i32),
+            channels: Some(2),
+            samples: Some(settings.buffer),
+        };
+        let queue = audio.open_queue::<i16, _>(None, &desired)?;
+        let spec = queue.spec();
+        let (freq, channels) = (spec.freq as u32, spec.channels as u32);
+        let target = (settings.latency.as_secs_f64() * freq as f64) as u32 * channels * 2;
+        queue.resume();
+        Ok(AudioOut {
+            queue,
+            freq,
+            channels,
+            target,
+            last: None,
+            stats: AudioStats::default(),
+        })
+    }
+    // what the emulator's sample rate has to be, since sdl can give a
+    // different one
+    pub fn freq(&self) -> u32 {
+        self.freq
+    }
+    // queues the emulator's samples for a frame, left and right for each
+    // sample frame. after a gap, like a pause, the queue is filled back up
+    // to the latency with silence first and nothing's counted
+    pub fn push_frame(&mut self, samples: &[i16]) {
+        let samples = to_channels(samples, self.channels);
+        let now = Instant::now();
+        let running = self.last.is_some_and(|last| now - last < 4 * FRAME_DUR);
+        self.last = Some(now);
+        let queued = self.queue.size();
+        if running && queued == 0 {
+            self.stats.underruns += 1;
+        }
+        if queued > self.target * 2 {
+            self.stats.overruns += 1;
+            return;
+        }
+        if !running {
+            let queued = queued + samples.len() as u32 * 2;
+            let bytes = self.target.saturating_sub(queued);
+            // in whole sample frames, so left stays left
+            let frame = self.channels * 2;
+            let silence = vec![0; (bytes / frame * self.channels) as usize];
+            self.enqueue(&silence);
+        }
+        self.enqueue(&samples);
+    }
+    fn enqueue(&mut self, samples: &[i16]) {
+        if let Err(e) = self.queue.queue_audio(samples) {
+            eprintln!("Unable to queue audio: {e}");
+        }
+    }
+    pub fn stats(&self) -> AudioStats {
+        let bytes_per_sec = self.freq * self.channels * 2;
+        let secs = |bytes: u32| Duration::from_secs_f64(bytes as f64 / bytes_per_sec as f64);
+        AudioStats {
+            queued: secs(self.queue.size()),
+            latency: secs(self.target),
+            ..self.stats
+        }
+    }
+}
+
+// sdl might not open two channels. mono gets the average of left and right,
+// and any past the first two are silent
+fn to_channels(samples: &[i16], channels: u32) -> Cow<'_, [i16]> {
+    match channels {
+        2 => samples.into(),
+        1 => samples
+            .chunks_exact(2)
+            .map(|lr| ((lr[0] as i32 + lr[1] as i32) / 2) as i16)
+            .collect(),
+        n => samples
+            .chunks_exact(2)
+            .flat_map(|lr| lr.iter().copied().chain(iter::repeat_n(0, n as usize - 2)))
+            .collect(),
+    }
+}
@@ -0,0 +1,78 @@
+// bench-render runs the same rom through each renderer without a window,
+// timing every frame and hashing it to check the renderers draw the same
+use std::{
+    process::ExitCode,
+    time::{Duration, Instant},
+};
+
+use gameboy::emulator::{constants::*, *};
+
+const RENDERERS: [(&str, Renderer); 2] =
+    [("fifo", Renderer::Fifo), ("scanline", Renderer::Scanline)];
+
+// how long a frame takes on hardware
+const FRAME_SECS: f64 = FRAME_CYCLES as f64 / CLOCK_HZ as f64;
+
+struct Run {
+    total: Duration,
+    worst: Duration,
+    hashes: Vec<u64>,
+}
+
+fn run_one(mut emu: Emulator, frames: u64) -> Run {
+    let mut run = Run {
+        total: Duration::ZERO,
+        worst: Duration::ZERO,
+        hashes: Vec::new(),
+    };
+    while emu.frame_count() < frames && !emu.quit_requested() {
+        let start = Instant::now();
+        emu.run_frame();
+        let time = start.elapsed();
+        run.total += time;
+        run.worst = run.worst.max(time);
+        run.hashes.push(emu.frame_hash());
+        if let Some(fault) = emu.take_fault() {
+            eprintln!("{fault} on frame {}", emu.frame_count() + 1);
+            break;
+        }
+    }
+    run
+}
+
+// emu has the rom loaded already, and each renderer gets a fresh copy of it
+// from new_emu so none of them start ahead
+pub fn run(emu: Emulator, new_emu: impl Fn() -> Emulator, frames: u64) -> ExitCode {
+    let [fifo, scanline] = RENDERERS.map(|(name, renderer)| {
+        let mut fresh = new_emu();
+        fresh.set_renderer(renderer);
+        let _ = fresh.load_bytes(emu.rom());
+        let run = run_one(fresh, frames);
+        let n = run.hashes.len().max(1) as u32;
+        let average = run.total / n;
+        let speed = FRAME_SECS / average.as_secs_f64() * 100.0;
+        println!(
+            "{name}: {} frames in {:.2?}, {average:.2?} a frame ({speed:.0}%), worst {:.2?}",
+            run.hashes.len(),
+            run.total,
+            run.worst
+        );
+        run
+    });
+    let differ = fifo
+        .hashes
+        .iter()
+        .zip(&scanline.hashes)
+        .position(|(a, b)| a != b);
+    match differ {
+        Some(i) => {
+            println!("The renderers first differ on frame {}", i + 1);
+            ExitCode::FAILURE
+        }
+        None => {
+            let n = fifo.hashes.len().min(scanline.hashes.len());
+            println!("The renderers agree on all {n} frames");
+            ExitCode::SUCCESS
+        }
+    }
+}
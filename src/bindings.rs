@@ -0,0 +1,135 @@
+// which keyboard keys press which buttons, kept in the [keys] section of the
+// config by sdl's key names. other profiles, like one for an arcade stick,
+// go in [keys.<name>] sections, and [input] profile is the one in use
+use gameboy::emulator::*;
+use sdl2::keyboard::Keycode;
+
+use crate::config::Config;
+
+// the order Capture asks for them in
+const CAPTURE_ORDER: [Button; 8] = [
+    Button::Up,
+    Button::Down,
+    Button::Left,
+    Button::Right,
+    Button::A,
+    Button::B,
+    Button::Select,
+    Button::Start,
+];
+
+// the profile kept in plain [keys]
+pub const DEFAULT_PROFILE: &str = "default";
+
+fn section(profile: &str) -> String {
+    if profile == DEFAULT_PROFILE {
+        "keys".into()
+    } else {
+        format!("keys.{profile}")
+    }
+}
+
+// the default, then the rest in the order they're in the config
+pub fn profiles(config: &Config) -> Vec<String> {
+    let mut names = vec![DEFAULT_PROFILE.to_string()];
+    for (section, ..) in config.entries() {
+        if let Some(name) = section.strip_prefix("keys.")
+            && !names.iter().any(|n| n == name)
+        {
+            names.push(name.into());
+        }
+    }
+    names
+}
+
+pub struct Bindings {
+    // indexed by Button
+    keys: [Keycode; 8],
+    profile: String,
+}
+
+impl Bindings {
+    // anything missing or unknown in the config gets its default, so a new
+    // profile starts out as the defaults
+    pub fn load(config: &Config, profile: &str) -> Self {
+        let mut keys = [
+            Keycode::Right,
+            Keycode::Left,
+            Keycode::Up,
+            Keycode::Down,
+            Keycode::X,
+            Keycode::Z,
+            Keycode::Backspace,
+            Keycode::Return,
+        ];
+        for (key, name) in keys.iter_mut().zip(BUTTON_NAMES) {
+            let section = section(profile);
+            if let Some(k) = config.get(&section, name).and_then(Keycode::from_name) {
+                *key = k;
+            }
+        }
+        let profile = profile.into();
+        Bindings { keys, profile }
+    }
+    pub fn save(&self, config: &mut Config) {
+        let section = section(&self.profile);
+        for (key, name) in self.keys.iter().zip(BUTTON_NAMES) {
+            config.set(&section, name, &key.name());
+        }
+    }
+    pub fn profile(&self) -> &str {
+        &self.profile
+    }
+    // the profile after this one, wrapping around
+    pub fn next(&self, config: &Config) -> Self {
+        let names = profiles(config);
+        let i = names.iter().position(|n| *n == self.profile);
+        let next = i.map_or(0, |i| (i + 1) % names.len());
+        Bindings::load(config, &names[next])
+    }
+    pub fn key(&self, button: Button) -> Keycode {
+        self.keys[button as usize]
+    }
+    pub fn button(&self, key: Keycode) -> Option<Button> {
+        let i = self.keys.iter().position(|&k| k == key)?;
+        Some(Button::ALL[i])
+    }
+}
+
+// asks for a key for each button in turn
+pub struct Capture {
+    next: usize,
+    keys: [Keycode; 8],
+    // the profile the keys are for
+    profile: String,
+}
+
+impl Capture {
+    pub fn new(profile: &str) -> Self {
+        Capture {
+            next: 0,
+            keys: [Keycode::Escape; 8],
+            profile: profile.into(),
+        }
+    }
+    pub fn prompt(&self) -> String {
+        let button = CAPTURE_ORDER[self.next];
+        format!("Press the key for {}", BUTTON_NAMES[button as usize])
+    }
+    // takes key for the button being asked for, and returns the bindings
+    // once every button has one
+    pub fn key(&mut self, key: Keycode) -> Option<Bindings> {
+        // one key can't be two buttons. the rest are still escape, which
+        // never gets here since it cancels
+        if self.keys.contains(&key) {
+            return None;
+        }
+        self.keys[CAPTURE_ORDER[self.next] as usize] = key;
+        self.next += 1;
+        let done = self.next == CAPTURE_ORDER.len();
+        done.then(|| Bindings {
+            keys: self.keys,
+            profile: self.profile.clone(),
+        })
+    }
+}
@@ -0,0 +1,113 @@
+// cheat files in libretro's .cht layout, which looks like
+//   cheats = 1
+//   cheat0_desc = "Infinite lives"
+//   cheat0_code = "00A-17B-C49+010238CD"
+//   cheat0_enable = true
+// with several codes in one cheat joined by +. a \ or " inside the quotes
+// is escaped with a \
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use gameboy::emulator::*;
+
+use crate::config::{escape, unescape};
+
+struct Entry {
+    desc: String,
+    code: String,
+    enabled: bool,
+    cheats: Vec<Cheat>,
+}
+
+pub struct CheatList {
+    path: PathBuf,
+    entries: Vec<Entry>,
+}
+
+impl CheatList {
+    // returns None if there's no file at path
+    pub fn load(path: &Path) -> Result<Option<Self>, String> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("Unable to read {}: {e}", path.display())),
+        };
+        let values: HashMap<&str, String> = text
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| (key.trim(), unquote(value.trim())))
+            .collect();
+        let count = values.get("cheats").map_or(Ok(0), |n| n.parse());
+        let count: usize = count.map_err(|_| format!("{}: bad cheat count", path.display()))?;
+        let mut entries = Vec::new();
+        for i in 0..count {
+            let get = |key| {
+                values
+                    .get(format!("cheat{i}_{key}").as_str())
+                    .map(String::as_str)
+            };
+            let code = get("code").unwrap_or_default();
+            let cheats = code
+                .split('+')
+                .map(|c| Cheat::parse(c.trim()))
+                .collect::<Option<Vec<_>>>()
+                .ok_or(format!(
+                    "{}: cheat{i} has a bad code: {code}",
+                    path.display()
+                ))?;
+            entries.push(Entry {
+                desc: get("desc").unwrap_or_default().into(),
+                code: code.into(),
+                enabled: get("enable") == Some("true"),
+                cheats,
+            });
+        }
+        Ok(Some(CheatList {
+            path: path.into(),
+            entries,
+        }))
+    }
+    pub fn save(&self) -> io::Result<()> {
+        let mut text = format!("cheats = {}\n", self.entries.len());
+        for (i, entry) in self.entries.iter().enumerate() {
+            let _ = write!(
+                text,
+                "\ncheat{i}_desc = \"{}\"\ncheat{i}_code = \"{}\"\ncheat{i}_enable = {}\n",
+                escape(&entry.desc),
+                escape(&entry.code),
+                entry.enabled
+            );
+        }
+        fs::write(&self.path, text)
+    }
+    // each cheat's description and whether it's on
+    pub fn entries(&self) -> impl Iterator<Item = (&str, bool)> {
+        self.entries.iter().map(|e| (e.desc.as_str(), e.enabled))
+    }
+    // the codes of every enabled cheat
+    pub fn enabled(&self) -> Vec<Cheat> {
+        let entries = self.entries.iter().filter(|e| e.enabled);
+        entries.flat_map(|e| e.cheats.iter().copied()).collect()
+    }
+    // flips cheat i on or off and saves the file, returning its description
+    // and whether it's on now
+    pub fn toggle(&mut self, i: usize) -> Option<(&str, bool)> {
+        let entry = self.entries.get_mut(i)?;
+        entry.enabled = !entry.enabled;
+        if let Err(e) = self.save() {
+            eprintln!("Unable to write {}: {e}", self.path.display());
+        }
+        let entry = &self.entries[i];
+        Some((&entry.desc, entry.enabled))
+    }
+}
+
+// takes a value out of its quotes, if it has them, and unescapes it
+fn unquote(value: &str) -> String {
+    let value = value.strip_prefix('"').unwrap_or(value);
+    unescape(value.strip_suffix('"').unwrap_or(value))
+}
@@ -0,0 +1,67 @@
+// runs two emulators side by side on the same rom and input, for checking
+// what a setting changes. pixels that differ are drawn red on the right, and
+// the first frame where they differ gets reported
+use std::{iter::zip, time::Instant};
+
+use gameboy::emulator::*;
+use sdl2::{event::Event, keyboard::Keycode};
+
+use crate::{FRAME_DUR, display::Display, input_script::InputScript, wait_until};
+
+const DIFF_COLOR: [u8; 4] = [0xFF, 0x00, 0x00, 0xFF];
+
+pub fn run(left: Emulator, right: Emulator, script: Option<InputScript>) {
+    let mut emus = [left, right];
+    let mut scripts = [script.clone(), script];
+    let mut disp = Display::with_screens(2);
+    disp.show();
+    let mut diverged = false;
+    let mut deadline = Instant::now();
+    'running: loop {
+        for event in disp.events() {
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => break 'running,
+                _ => {}
+            }
+        }
+        deadline += FRAME_DUR;
+        let mut ready = [false; 2];
+        for ((emu, script), ready) in zip(zip(&mut emus, &mut scripts), &mut ready) {
+            if let Some(script) = script {
+                script.apply(emu);
+            }
+            *ready = emu.run_frame();
+        }
+        if emus.iter().any(Emulator::quit_requested) {
+            break;
+        }
+        let [left, right] = &mut emus;
+        let differs = ready[0] != ready[1] || left.framebuffer() != right.framebuffer();
+        if differs && !diverged {
+            diverged = true;
+            eprintln!("The emulators first differ on frame {}", left.frame_count());
+        }
+        if ready.contains(&true) {
+            disp.update_with(0, |pixels, pitch| left.write_rgba(pixels, pitch));
+            let mut rgba = *right.framebuffer_rgba();
+            let pixels = zip(left.framebuffer(), right.framebuffer());
+            for (px, (a, b)) in zip(rgba.chunks_exact_mut(4), pixels) {
+                if a != b {
+                    px.copy_from_slice(&DIFF_COLOR);
+                }
+            }
+            disp.update_screen(1, &rgba);
+            disp.present();
+        }
+        let now = Instant::now();
+        if now < deadline {
+            wait_until(deadline);
+        } else if now - deadline > 4 * FRAME_DUR {
+            deadline = now;
+        }
+    }
+}
@@ -0,0 +1,119 @@
+// settings that stick between runs, in a small subset of toml like
+//   [keys]
+//   a = "X"
+// where every value is a string. anything else in the file is skipped
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+pub struct Config {
+    path: PathBuf,
+    // section, key and value, in the order they were read or set
+    entries: Vec<(String, String, String)>,
+}
+
+// escapes \ and " for a value that goes between quotes. cheat files use
+// these too
+pub fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// undoes escape
+pub fn unescape(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(mut c) = chars.next() {
+        if c == '\\' {
+            c = chars.next().unwrap_or('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+impl Config {
+    // $XDG_CONFIG_HOME/sethboy/config.toml, or under ~/.config without it.
+    // windows has neither, so there it's next to wherever it runs from
+    pub fn default_path() -> PathBuf {
+        let dir = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+            .unwrap_or_default();
+        dir.join("sethboy").join("config.toml")
+    }
+    // a missing file is the same as an empty one
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("Unable to read {}: {e}", path.display())),
+        };
+        let mut section = String::new();
+        let mut entries = Vec::new();
+        for line in text.lines().map(str::trim) {
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = name.trim().into();
+            } else if let Some((key, value)) = line.split_once('=')
+                && let Some(value) = value.trim().strip_prefix('"')
+                && let Some(value) = value.strip_suffix('"')
+            {
+                entries.push((section.clone(), key.trim().into(), unescape(value)));
+            }
+        }
+        Ok(Config {
+            path: path.into(),
+            entries,
+        })
+    }
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        let mut entries = self.entries.iter();
+        let entry = entries.find(|(s, k, _)| s == section && k == key)?;
+        Some(&entry.2)
+    }
+    // section, key and value
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &str, &str)> {
+        let entries = self.entries.iter();
+        entries.map(|(s, k, v)| (s.as_str(), k.as_str(), v.as_str()))
+    }
+    pub fn set(&mut self, section: &str, key: &str, value: &str) {
+        let entry = self
+            .entries
+            .iter_mut()
+            .find(|(s, k, _)| s == section && k == key);
+        match entry {
+            Some(entry) => entry.2 = value.into(),
+            None => self
+                .entries
+                .push((section.into(), key.into(), value.into())),
+        }
+    }
+    // writes every section in the order it first showed up
+    pub fn save(&self) -> io::Result<()> {
+        let mut sections: Vec<&str> = Vec::new();
+        for (section, ..) in &self.entries {
+            if !sections.contains(&section.as_str()) {
+                sections.push(section);
+            }
+        }
+        let mut text = String::new();
+        for section in sections {
+            if !text.is_empty() {
+                text.push('\n');
+            }
+            if !section.is_empty() {
+                text += &format!("[{section}]\n");
+            }
+            for (_, key, value) in self.entries.iter().filter(|(s, ..)| s == section) {
+                text += &format!("{key} = \"{}\"\n", escape(value));
+            }
+        }
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, text)
+    }
+}
@@ -0,0 +1,324 @@
+// a line based protocol for scripting the emulator without a window, over
+// stdin/stdout or a unix socket. each command gets one line back, starting
+// with ok, fail (for asserts that don't hold) or error. blank lines and
+// lines starting with # are ignored, so test scripts can be piped in
+use std::{
+    fs::File,
+    io::{self, BufRead, Write, stdin, stdout},
+    process::ExitCode,
+    time::Duration,
+};
+
+use gameboy::emulator::*;
+
+use crate::{frame_hashes::FrameHashes, input_script::InputScript, serial_out::SerialOut};
+
+const COMMANDS: &str = "load, press, release, frames, run, hold, peek, poke, assert, screenshot, \
+     rtc, hash, reset, profile, coverage, barcode, quit";
+
+enum Reply {
+    Ok(String),
+    // an assert that didn't hold
+    Fail(String),
+}
+
+struct Session<F> {
+    emu: Option<Emulator>,
+    // the path emu's rom came from, for finding its symbols
+    rom: String,
+    // makes a fresh emulator for each rom, with the command line's settings
+    new_emu: F,
+    script: Option<InputScript>,
+    hashes: Option<FrameHashes>,
+    serial: Option<SerialOut>,
+    // plugged in by --barcode or the first barcode command
+    barcode: Option<BarcodeBoy>,
+    failed: bool,
+    quit: bool,
+}
+
+fn buttons<'a>(names: impl Iterator<Item = &'a str>) -> Result<Vec<Button>, String> {
+    let buttons = names
+        .map(|name| Button::from_name(name).ok_or(format!("unknown button: {name}")))
+        .collect::<Result<Vec<_>, _>>()?;
+    if buttons.is_empty() {
+        return Err(format!("buttons are: {}", BUTTON_NAMES.join(", ")));
+    }
+    Ok(buttons)
+}
+
+fn number<T: TryFrom<u32>>(s: Option<&str>) -> Result<T, String> {
+    let s = s.ok_or("missing a number")?;
+    let n = match s.strip_prefix('$') {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    n.ok()
+        .and_then(|n| T::try_from(n).ok())
+        .ok_or(format!("not a valid number: {s}"))
+}
+
+impl<F: Fn() -> Emulator> Session<F> {
+    fn run_frames(
+        emu: &mut Emulator,
+        script: &mut Option<InputScript>,
+        hashes: &mut Option<FrameHashes>,
+        n: u32,
+    ) -> Result<(), String> {
+        for _ in 0..n {
+            if emu.quit_requested() {
+                break;
+            }
+            if let Some(script) = script {
+                script.apply(emu);
+            }
+            if emu.run_frame()
+                && let Some(hashes) = hashes
+            {
+                hashes.record(emu);
+            }
+            if let Some(fault) = emu.take_fault() {
+                return Err(fault.to_string());
+            }
+        }
+        Ok(())
+    }
+    fn command(&mut self, line: &str) -> Result<Reply, String> {
+        let mut words = line.split_whitespace();
+        let cmd = words.next().unwrap_or_default();
+        if cmd == "load" {
+            let path = words.next().ok_or("load needs a rom")?;
+            let mut file = File::open(path).map_err(|e| format!("unable to open {path}: {e}"))?;
+            let mut emu = (self.new_emu)();
+            if let Some(reader) = &self.barcode {
+                emu.set_serial_device(Some(Box::new(reader.clone())));
+            }
+            let warnings = emu
+                .load(&mut file)
+                .map_err(|e| format!("unable to load {path}: {e}"))?;
+            self.emu = Some(emu);
+            self.rom = path.into();
+            if let Some(script) = &mut self.script {
+                script.restart();
+            }
+            // warnings about the header go after the ok
+            let warnings: Vec<_> = warnings.iter().map(ToString::to_string).collect();
+            return Ok(Reply::Ok(warnings.join("; ")));
+        }
+        if cmd == "quit" {
+            self.quit = true;
+            return Ok(Reply::Ok(String::new()));
+        }
+        let Some(emu) = &mut self.emu else {
+            return Err("no rom loaded".into());
+        };
+        match cmd {
+            "press" | "release" => {
+                for button in buttons(words)? {
+                    emu.set_button(button, cmd == "press");
+                }
+            }
+            "frames" => {
+                let n = number(words.next())?;
+                Self::run_frames(emu, &mut self.script, &mut self.hashes, n)?;
+            }
+            // run <duration>, as fast as possible and without the input
+            // script or frame hashes. replies with the frames it took
+            "run" => {
+                let duration = words.next().ok_or("run needs a duration like 2h")?;
+                let secs = parse_duration(duration).ok_or(format!("not a duration: {duration}"))?;
+                let frames = emu.frame_count();
+                emu.run_for(Duration::from_secs(secs));
+                if let Some(fault) = emu.take_fault() {
+                    return Err(fault.to_string());
+                }
+                return Ok(Reply::Ok((emu.frame_count() - frames).to_string()));
+            }
+            // hold <frames> <buttons>
+            "hold" => {
+                let n = number(words.next())?;
+                let buttons = buttons(words)?;
+                buttons.iter().for_each(|&b| emu.set_button(b, true));
+                let result = Self::run_frames(emu, &mut self.script, &mut self.hashes, n);
+                buttons.iter().for_each(|&b| emu.set_button(b, false));
+                result?;
+            }
+            "peek" => {
+                let addr: u16 = number(words.next())?;
+                let len: u16 = words.next().map_or(Ok(1), |s| number(Some(s)))?;
+                let bytes: Vec<String> = (0..len)
+                    .map(|i| format!("${:02x}", emu.ram.read(addr.wrapping_add(i))))
+                    .collect();
+                return Ok(Reply::Ok(bytes.join(" ")));
+            }
+            "poke" => {
+                let addr: u16 = number(words.next())?;
+                let bytes = words
+                    .map(|s| number(Some(s)))
+                    .collect::<Result<Vec<u8>, _>>()?;
+                for (i, byte) in bytes.into_iter().enumerate() {
+                    emu.ram.write(addr.wrapping_add(i as u16), byte);
+                }
+            }
+            "assert" => {
+                let addr: u16 = number(words.next())?;
+                let expected: u8 = number(words.next())?;
+                let got = emu.ram.read(addr);
+                if got != expected {
+                    self.failed = true;
+                    let msg = format!("${addr:04x} is ${got:02x}, expected ${expected:02x}");
+                    return Ok(Reply::Fail(msg));
+                }
+            }
+            "screenshot" => {
+                let path = words.next().ok_or("screenshot needs a path")?;
+                File::create(path)
+                    .and_then(|f| emu.screenshot(f))
+                    .map_err(|e| format!("unable to write {path}: {e}"))?;
+            }
+            // rtc, or rtc add <duration> to move it on first
+            "rtc" => {
+                if let Some(duration) = words.next() {
+                    let secs = parse_duration(duration);
+                    emu.advance_rtc(secs.ok_or(format!("not a duration: {duration}"))?);
+                }
+                let time = emu.rtc().ok_or("this cart has no rtc")?;
+                return Ok(Reply::Ok(time.to_string()));
+            }
+            // for checking two runs are still in step
+            "hash" => return Ok(Reply::Ok(format!("{:016x}", emu.state_hash()))),
+            // with --cycle-profile on, writes what's been profiled so far
+            "profile" => {
+                let path = words.next().ok_or("profile needs a path")?;
+                let symbols = crate::read_symbols(&self.rom, None)?;
+                crate::profile::write(emu, path, &symbols, &self.rom)?;
+            }
+            // with --coverage on, writes the code/data log so far
+            "coverage" => {
+                let path = words.next().ok_or("coverage needs a path")?;
+                crate::write_coverage(emu, path)?;
+            }
+            // reset, or reset soft to keep wram
+            "reset" => {
+                match words.next() {
+                    None | Some("hard") => emu.hard_reset(),
+                    Some("soft") => emu.soft_reset(),
+                    Some(kind) => return Err(format!("not a kind of reset: {kind}")),
+                }
+                if let Some(script) = &mut self.script {
+                    script.restart();
+                }
+            }
+            // barcode <13 digits>, swiped once the game's listening. says so
+            // if the game hasn't found the reader yet
+            "barcode" => {
+                let code = words.next().ok_or("barcode needs a card's 13 digit code")?;
+                let reader = self.barcode.get_or_insert_with(|| {
+                    let reader = BarcodeBoy::new();
+                    emu.set_serial_device(Some(Box::new(reader.clone())));
+                    reader
+                });
+                reader.scan(code)?;
+                if !reader.found() {
+                    return Ok(Reply::Ok("queued until the game looks for it".into()));
+                }
+            }
+            _ => return Err(format!("commands are: {COMMANDS}")),
+        }
+        Ok(Reply::Ok(String::new()))
+    }
+    fn serve<R: BufRead, W: Write>(&mut self, input: R, mut output: W) -> io::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let reply = self.command(line);
+            if let Some(serial) = &mut self.serial
+                && let Some(emu) = &mut self.emu
+            {
+                serial.drain(emu);
+            }
+            match reply {
+                Ok(Reply::Ok(rest)) if rest.is_empty() => writeln!(output, "ok")?,
+                Ok(Reply::Ok(rest)) => writeln!(output, "ok {rest}")?,
+                Ok(Reply::Fail(e)) => writeln!(output, "fail {e}")?,
+                Err(e) => writeln!(output, "error {e}")?,
+            }
+            output.flush()?;
+            if self.quit {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+// serves commands from target, which is - for stdin/stdout or a unix socket
+// path, to emu and the path its rom was loaded from. fails if any assert did
+pub fn run(
+    target: &str,
+    emu: Option<(Emulator, String)>,
+    new_emu: impl Fn() -> Emulator,
+    script: Option<InputScript>,
+    hashes: Option<FrameHashes>,
+    serial: Option<SerialOut>,
+    barcode: Option<BarcodeBoy>,
+) -> ExitCode {
+    let (emu, rom) = emu.unzip();
+    let mut session = Session {
+        emu,
+        rom: rom.unwrap_or_default(),
+        new_emu,
+        script,
+        hashes,
+        serial,
+        barcode,
+        failed: false,
+        quit: false,
+    };
+    let result = if target == "-" {
+        session.serve(stdin().lock(), stdout().lock())
+    } else {
+        serve_socket(&mut session, target)
+    };
+    if let Err(e) = result {
+        eprintln!("Control connection failed: {e}");
+        return ExitCode::FAILURE;
+    }
+    if session.failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+// takes one connection at a time until one of them quits
+#[cfg(unix)]
+fn serve_socket<F: Fn() -> Emulator>(session: &mut Session<F>, path: &str) -> io::Result<()> {
+    use std::{io::BufReader, os::unix::net::UnixListener};
+    let listener = UnixListener::bind(path)?;
+    let mut accept = || {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            // a client going away isn't a reason to stop
+            let _ = session.serve(BufReader::new(&stream), &stream);
+            if session.quit {
+                break;
+            }
+        }
+        Ok(())
+    };
+    let result = accept();
+    let _ = std::fs::remove_file(path);
+    result
+}
+
+#[cfg(not(unix))]
+fn serve_socket<F: Fn() -> Emulator>(_: &mut Session<F>, _: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "unix sockets aren't available here, use - for stdin/stdout",
+    ))
+}
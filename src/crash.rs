@@ -0,0 +1,51 @@
+// a text file with everything needed to look into a crash after the fact
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+use gameboy::emulator::*;
+
+pub fn write_report(emu: &Emulator, fault: Fault, path: &str) -> io::Result<()> {
+    let mut w = BufWriter::new(File::create(path)?);
+    let read = |addr: u16| emu.ram.read(addr);
+    writeln!(w, "{fault}")?;
+
+    let r = emu.registers();
+    writeln!(w, "\nRegisters")?;
+    writeln!(
+        w,
+        "AF: ${:02x}{:02x}  BC: ${:02x}{:02x}",
+        r.a, r.f, r.b, r.c
+    )?;
+    writeln!(
+        w,
+        "DE: ${:02x}{:02x}  HL: ${:02x}{:02x}",
+        r.d, r.e, r.h, r.l
+    )?;
+    writeln!(w, "SP: ${:04x}  PC: ${:04x}", r.sp, r.pc)?;
+
+    writeln!(w, "\nROM header")?;
+    writeln!(w, "Title: {}", emu.rom_title())?;
+    writeln!(w, "Cartridge type: ${:02x}", read(0x147))?;
+    writeln!(w, "ROM size: ${:02x}", read(0x148))?;
+    writeln!(w, "RAM size: ${:02x}", read(0x149))?;
+    let ok = emu.header().is_some_and(|h| h.checksum_ok());
+    let ok = if ok { "ok" } else { "bad" };
+    writeln!(w, "Header checksum: ${:02x} ({ok})", read(0x14D))?;
+
+    writeln!(w, "\nLast instructions, oldest first")?;
+    for addr in emu.trace() {
+        writeln!(w, "${addr:04x}: {}", emu.disassemble(addr).0)?;
+    }
+
+    writeln!(w, "\nMemory")?;
+    for row in (0..=0xFFFF).step_by(16) {
+        write!(w, "{row:04x}:")?;
+        for addr in row..=row + 15 {
+            write!(w, " {:02x}", read(addr))?;
+        }
+        writeln!(w)?;
+    }
+    w.flush()
+}
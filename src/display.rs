@@ -1,18 +1,22 @@
 extern crate sdl2;
 
-use std::iter::zip;
-
-use crate::emulator::constants::*;
+use gameboy::emulator::constants::*;
+#[cfg(feature = "gui")]
+use sdl2::VideoSubsystem;
 use sdl2::{
-    EventPump,
+    AudioSubsystem, EventPump, Sdl,
     event::EventPollIterator,
-    render::{Texture, TextureCreator, WindowCanvas},
+    keyboard::{Keycode, Scancode},
+    pixels::PixelFormatEnum,
+    rect::Rect,
+    render::{ScaleMode, Texture, TextureCreator, WindowCanvas},
     video::WindowContext,
 };
 
 // sdl2 more like sdlPOO
 // my textures are unsafe now (yay!)
 pub struct Display {
+    sdl: Sdl,
     canvas: WindowCanvas,
     event_pump: EventPump,
     #[allow(dead_code)]
@@ -22,56 +26,93 @@ pub struct Display {
 
 impl Display {
     pub fn new() -> Self {
+        Self::with_screens(1)
+    }
+    // a window with room for several screens next to each other
+    pub fn with_screens(screens: u32) -> Self {
+        // windows only scales by the display's dpi when told it can
+        sdl2::hint::set("SDL_WINDOWS_DPI_AWARENESS", "permonitorv2");
         let sdl_context = sdl2::init().unwrap();
         let video_subsystem = sdl_context.video().unwrap();
         const SCALE: u32 = 4;
+        let width = SCRN_X as u32 * screens;
         let window = video_subsystem
-            .window("Gameboy", SCRN_X as u32 * SCALE, SCRN_Y as u32 * SCALE)
+            .window("Gameboy", width * SCALE, SCRN_Y as u32 * SCALE)
             .position_centered()
+            // on retina and scaled wayland the drawable gets more pixels
+            // than the window has points, rather than being stretched up
+            .allow_highdpi()
             .build()
             .unwrap();
         let mut canvas = window.into_canvas().build().unwrap();
-        canvas
-            .set_logical_size(SCRN_X as u32, SCRN_Y as u32)
-            .unwrap();
+        // the logical size is scaled to whatever the drawable really is, and
+        // only by whole pixels so none come out wider than the others
+        canvas.set_logical_size(width, SCRN_Y as u32).unwrap();
+        canvas.set_integer_scale(true).unwrap();
         let texture_creator = canvas.texture_creator();
-        let texture = texture_creator
-            .create_texture_streaming(None, SCRN_X as u32, SCRN_Y as u32)
+        let mut texture = texture_creator
+            .create_texture_streaming(PixelFormatEnum::RGBA32, width, SCRN_Y as u32)
             .unwrap();
+        texture.set_scale_mode(ScaleMode::Nearest);
         Display {
-            canvas,
             event_pump: sdl_context.event_pump().unwrap(),
+            sdl: sdl_context,
+            canvas,
             texture_creator,
             texture,
         }
     }
+    #[cfg(feature = "gui")]
+    pub fn video(&self) -> VideoSubsystem {
+        self.canvas.window().subsystem().clone()
+    }
+    pub fn audio(&self) -> Result<AudioSubsystem, String> {
+        self.sdl.audio()
+    }
     pub fn events(&mut self) -> EventPollIterator<'_> {
         self.event_pump.poll_iter()
     }
-    pub fn update(&mut self, fb: &[u8; SCRN_X * SCRN_Y]) {
-        self.texture
-            .with_lock(None, |pixels, pitch| {
-                for row in 0..SCRN_Y {
-                    for (i, color) in zip(
-                        ((row * pitch)..(row * pitch + SCRN_X * 4)).step_by(4),
-                        fb.iter().skip(row * SCRN_X),
-                    ) {
-                        // this is in the lovely format BGRA
-                        pixels[i..(i + 4)].copy_from_slice(match color {
-                            0 => &[0x8C, 0xDE, 0xC6, 255],
-                            1 => &[0x63, 0xA5, 0x84, 255],
-                            2 => &[0x39, 0x61, 0x39, 255],
-                            3 => &[0x10, 0x18, 0x08, 255],
-                            _ => unreachable!(),
-                        });
-                    }
-                }
-            })
-            .unwrap();
+    // catches up on what's being held without taking any events off the
+    // queue, for key_down partway through a frame
+    pub fn pump(&mut self) {
+        self.event_pump.pump_events();
+    }
+    pub fn key_down(&self, key: Keycode) -> bool {
+        let Some(code) = Scancode::from_keycode(key) else {
+            return false;
+        };
+        self.event_pump.keyboard_state().is_scancode_pressed(code)
+    }
+    // fb is rgba straight from the emulator
+    pub fn update(&mut self, fb: &[u8; SCRN_X * SCRN_Y * 4]) {
+        self.update_screen(0, fb);
+    }
+    // draws fb as the nth screen from the left
+    pub fn update_screen(&mut self, n: u32, fb: &[u8; SCRN_X * SCRN_Y * 4]) {
+        self.update_with(n, |pixels, pitch| {
+            if pitch == SCRN_X * 4 {
+                pixels[..fb.len()].copy_from_slice(fb);
+                return;
+            }
+            for (row, line) in fb.chunks_exact(SCRN_X * 4).enumerate() {
+                pixels[row * pitch..][..SCRN_X * 4].copy_from_slice(line);
+            }
+        });
+    }
+    // draws the nth screen by letting write fill in the locked texture,
+    // given the bytes from one row to the next
+    pub fn update_with(&mut self, n: u32, write: impl FnOnce(&mut [u8], usize)) {
+        let rect = Rect::new(n as i32 * SCRN_X as i32, 0, SCRN_X as u32, SCRN_Y as u32);
+        self.texture.with_lock(rect, write).unwrap();
+    }
+    pub fn present(&mut self) {
         self.canvas.clear();
         let _ = self.canvas.copy(&self.texture, None, None);
         self.canvas.present();
     }
+    pub fn set_title(&mut self, title: &str) {
+        let _ = self.canvas.window_mut().set_title(title);
+    }
     pub fn show(&mut self) {
         self.canvas.present();
     }
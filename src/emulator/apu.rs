@@ -0,0 +1,600 @@
+// sound: two pulse channels, a wave channel and a noise channel, the frame
+// sequencer that clocks their lengths, envelopes and sweep, and the mixer.
+// the registers live in mem like the rest of io, and cpu writes come through
+// here so triggers and the power switch take effect. samples come out
+// stereo and interleaved at whatever rate the frontend asks for, into a ring
+// that drops the oldest when nothing's taking them
+use alloc::{collections::VecDeque, vec::Vec};
+
+use super::{
+    Emulator,
+    constants::*,
+    state::{StateReader, StateWriter},
+};
+
+// what the frontend gets unless it asks for something else
+pub const SAMPLE_RATE: u32 = 48000;
+// the lowest rate other than 0. any lower and the ring has no room
+pub const MIN_SAMPLE_RATE: u32 = 8000;
+
+// the ring holds a quarter of a second
+const RING_PARTS: u32 = 4;
+// the waveform of each duty cycle, 12.5%, 25%, 50% and 75%
+const DUTIES: [u8; 4] = [0b0000_0001, 0b1000_0001, 0b1000_0111, 0b0111_1110];
+const NOISE_DIVISORS: [u32; 8] = [8, 16, 32, 48, 64, 80, 96, 112];
+// how much of its charge the output capacitor keeps each t-cycle, which
+// takes the dc offset out like the real thing
+const CHARGE_KEPT: f32 = 0.999958;
+// four channels at full volume on both master volumes
+const MAX_MIX: f32 = 4.0 * 15.0 * 8.0;
+
+type Mem = [u8; 0x10000];
+
+fn reg(mem: &Mem, i: u16) -> u8 {
+    mem[i as usize]
+}
+
+// the frequency in the low 3 bits of nrx4 and all of nrx3
+fn freq(mem: &Mem, nrx3: u16) -> u32 {
+    (reg(mem, nrx3) as u32) | (reg(mem, nrx3 + 1) as u32 & 7) << 8
+}
+
+// counts t-cycles down to the next step of a channel's waveform, and
+// returns how many steps there were
+fn run_timer(timer: &mut u32, mut t_cyc: u32, period: u32) -> u32 {
+    let mut steps = 0;
+    while t_cyc >= *timer {
+        t_cyc -= *timer;
+        *timer = period;
+        steps += 1;
+    }
+    *timer -= t_cyc;
+    steps
+}
+
+#[derive(Default)]
+struct Envelope {
+    volume: u8,
+    timer: u8,
+}
+
+impl Envelope {
+    // nrx2 has the starting volume, whether it goes up and the period
+    fn trigger(&mut self, nrx2: u8) {
+        self.volume = nrx2 >> 4;
+        self.timer = nrx2 & 7;
+    }
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(&[self.volume, self.timer]);
+    }
+    fn load_state(&mut self, r: &mut StateReader) -> Option<()> {
+        [self.volume, self.timer] = r.bytes(2)?.try_into().ok()?;
+        Some(())
+    }
+    fn clock(&mut self, nrx2: u8) {
+        let period = nrx2 & 7;
+        if period == 0 {
+            return;
+        }
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer > 0 {
+            return;
+        }
+        self.timer = period;
+        if nrx2 & 0x08 > 0 {
+            self.volume = (self.volume + 1).min(15);
+        } else {
+            self.volume = self.volume.saturating_sub(1);
+        }
+    }
+}
+
+// counts down while nrx4's length bit is set, and turns the channel off at 0
+fn clock_length(length: &mut u16, on: &mut bool, nrx4: u8) {
+    if nrx4 & 0x40 > 0 && *length > 0 {
+        *length -= 1;
+        if *length == 0 {
+            *on = false;
+        }
+    }
+}
+
+#[derive(Default)]
+struct Pulse {
+    on: bool,
+    length: u16,
+    envelope: Envelope,
+    // t-cycles until the next step of the duty
+    timer: u32,
+    step: u8,
+    // channel 1's sweep, which works on a copy of the frequency
+    sweeping: bool,
+    sweep_timer: u8,
+    shadow: u32,
+}
+
+impl Pulse {
+    // nrx1 is the duty and length, nrx2 the envelope, and nrx3 and nrx4
+    // the frequency. channel 1 has nr10 for the sweep before those
+    fn trigger(&mut self, mem: &Mem, nrx1: u16) {
+        let nrx2 = reg(mem, nrx1 + 1);
+        self.on = nrx2 & 0xF8 > 0;
+        if self.length == 0 {
+            self.length = 64;
+        }
+        self.timer = (2048 - freq(mem, nrx1 + 2)) * 4;
+        self.envelope.trigger(nrx2);
+        if nrx1 == NR11 {
+            let nr10 = reg(mem, NR10);
+            let (period, shift) = (nr10 >> 4 & 7, nr10 & 7);
+            self.shadow = freq(mem, NR13);
+            self.sweep_timer = if period == 0 { 8 } else { period };
+            self.sweeping = period > 0 || shift > 0;
+            // an overflow turns it straight back off
+            if shift > 0 {
+                self.sweep_next(nr10);
+            }
+        }
+    }
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bool(self.on);
+        w.u16(self.length);
+        self.envelope.save_state(w);
+        w.u32(self.timer);
+        w.u8(self.step);
+        w.bool(self.sweeping);
+        w.u8(self.sweep_timer);
+        w.u32(self.shadow);
+    }
+    fn load_state(&mut self, r: &mut StateReader) -> Option<()> {
+        self.on = r.bool()?;
+        self.length = r.u16()?;
+        self.envelope.load_state(r)?;
+        self.timer = r.u32()?;
+        self.step = r.u8()? % 8;
+        self.sweeping = r.bool()?;
+        self.sweep_timer = r.u8()?;
+        self.shadow = r.u32()?;
+        Some(())
+    }
+    fn sweep_next(&mut self, nr10: u8) -> Option<u32> {
+        let delta = self.shadow >> (nr10 & 7);
+        let next = if nr10 & 0x08 > 0 {
+            self.shadow - delta
+        } else {
+            self.shadow + delta
+        };
+        if next > 2047 {
+            self.on = false;
+            return None;
+        }
+        Some(next)
+    }
+    // writes the new frequency back to nr13 and nr14, like the real one
+    fn clock_sweep(&mut self, mem: &mut Mem) {
+        let nr10 = reg(mem, NR10);
+        let period = nr10 >> 4 & 7;
+        self.sweep_timer = self.sweep_timer.saturating_sub(1);
+        if self.sweep_timer > 0 {
+            return;
+        }
+        self.sweep_timer = if period == 0 { 8 } else { period };
+        if !self.sweeping || period == 0 {
+            return;
+        }
+        if let Some(next) = self.sweep_next(nr10)
+            && nr10 & 7 > 0
+        {
+            self.shadow = next;
+            mem[NR13 as usize] = next as u8;
+            mem[NR14 as usize] = (reg(mem, NR14) & !7) | (next >> 8) as u8;
+            // and checked again with the new one
+            self.sweep_next(nr10);
+        }
+    }
+    fn tick(&mut self, mem: &Mem, nrx1: u16, t_cyc: u32) {
+        let period = (2048 - freq(mem, nrx1 + 2)) * 4;
+        let steps = run_timer(&mut self.timer, t_cyc, period);
+        self.step = (self.step + steps as u8) % 8;
+    }
+    fn output(&self, mem: &Mem, nrx1: u16) -> u8 {
+        let duty = DUTIES[reg(mem, nrx1) as usize >> 6];
+        if self.on && duty >> self.step & 1 > 0 {
+            self.envelope.volume
+        } else {
+            0
+        }
+    }
+}
+
+#[derive(Default)]
+struct Wave {
+    on: bool,
+    length: u16,
+    timer: u32,
+    // which of the 32 samples in wave ram is playing
+    position: u8,
+    sample: u8,
+}
+
+impl Wave {
+    fn trigger(&mut self, mem: &Mem) {
+        self.on = reg(mem, NR30) & 0x80 > 0;
+        if self.length == 0 {
+            self.length = 256;
+        }
+        self.timer = (2048 - freq(mem, NR33)) * 2;
+        self.position = 0;
+    }
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bool(self.on);
+        w.u16(self.length);
+        w.u32(self.timer);
+        w.bytes(&[self.position, self.sample]);
+    }
+    fn load_state(&mut self, r: &mut StateReader) -> Option<()> {
+        self.on = r.bool()?;
+        self.length = r.u16()?;
+        self.timer = r.u32()?;
+        [self.position, self.sample] = r.bytes(2)?.try_into().ok()?;
+        self.position %= 32;
+        Some(())
+    }
+    fn tick(&mut self, mem: &Mem, t_cyc: u32) {
+        let period = (2048 - freq(mem, NR33)) * 2;
+        let steps = run_timer(&mut self.timer, t_cyc, period);
+        if steps > 0 {
+            self.position = (self.position + steps as u8) % 32;
+            // high nibble first
+            let byte = reg(mem, WAVE_RAM + self.position as u16 / 2);
+            self.sample = if self.position.is_multiple_of(2) {
+                byte >> 4
+            } else {
+                byte & 0xF
+            };
+        }
+    }
+    fn output(&self, mem: &Mem) -> u8 {
+        // muted, full, half and a quarter
+        let shift = [4, 0, 1, 2][reg(mem, NR32) as usize >> 5 & 3];
+        if self.on { self.sample >> shift } else { 0 }
+    }
+}
+
+#[derive(Default)]
+struct Noise {
+    on: bool,
+    length: u16,
+    envelope: Envelope,
+    timer: u32,
+    lfsr: u16,
+}
+
+impl Noise {
+    fn period(mem: &Mem) -> u32 {
+        let nr43 = reg(mem, NR43);
+        NOISE_DIVISORS[nr43 as usize & 7] << (nr43 >> 4)
+    }
+    fn trigger(&mut self, mem: &Mem) {
+        let nr42 = reg(mem, NR42);
+        self.on = nr42 & 0xF8 > 0;
+        if self.length == 0 {
+            self.length = 64;
+        }
+        self.timer = Self::period(mem);
+        self.envelope.trigger(nr42);
+        self.lfsr = 0x7FFF;
+    }
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bool(self.on);
+        w.u16(self.length);
+        self.envelope.save_state(w);
+        w.u32(self.timer);
+        w.u16(self.lfsr);
+    }
+    fn load_state(&mut self, r: &mut StateReader) -> Option<()> {
+        self.on = r.bool()?;
+        self.length = r.u16()?;
+        self.envelope.load_state(r)?;
+        self.timer = r.u32()?;
+        self.lfsr = r.u16()?;
+        Some(())
+    }
+    fn tick(&mut self, mem: &Mem, t_cyc: u32) {
+        let short = reg(mem, NR43) & 0x08 > 0;
+        for _ in 0..run_timer(&mut self.timer, t_cyc, Self::period(mem)) {
+            let bit = (self.lfsr ^ self.lfsr >> 1) & 1;
+            self.lfsr = self.lfsr >> 1 | bit << 14;
+            // the short mode repeats every 127 steps, for a buzzier sound
+            if short {
+                self.lfsr = (self.lfsr & !0x40) | bit << 6;
+            }
+        }
+    }
+    fn output(&self) -> u8 {
+        if self.on && self.lfsr & 1 == 0 {
+            self.envelope.volume
+        } else {
+            0
+        }
+    }
+}
+
+pub(super) struct Apu {
+    pulse: [Pulse; 2],
+    wave: Wave,
+    noise: Noise,
+    // which of its 8 steps the frame sequencer is on
+    step: u8,
+    // sample frames a second, or 0 for none at all
+    rate: u32,
+    // t-cycles times the rate since the last sample, due at CLOCK_HZ
+    clock: u64,
+    // the mix summed over each t-cycle since the last sample
+    sum: [i32; 2],
+    cycles: u32,
+    // the output capacitors, and how much they keep between samples
+    charge: [f32; 2],
+    kept: f32,
+    samples: VecDeque<i16>,
+    // sample frames since the last end_frame
+    frame_samples: usize,
+}
+
+impl Apu {
+    pub(super) fn new() -> Self {
+        let mut apu = Apu {
+            pulse: Default::default(),
+            wave: Wave::default(),
+            noise: Noise::default(),
+            step: 0,
+            rate: 0,
+            clock: 0,
+            sum: [0; 2],
+            cycles: 0,
+            charge: [0.0; 2],
+            kept: 0.0,
+            samples: VecDeque::new(),
+            frame_samples: 0,
+        };
+        apu.set_rate(SAMPLE_RATE);
+        apu
+    }
+    // starts over like at power on, but keeps the rate and any samples
+    // that haven't been taken
+    pub(super) fn reset(&mut self) {
+        let samples = core::mem::take(&mut self.samples);
+        *self = Apu {
+            samples,
+            ..Apu::new()
+        };
+    }
+    // the channels and frame sequencer. the samples and what's making them
+    // belong to the frontend's audio, so they carry on
+    pub(super) fn save_state(&self, w: &mut StateWriter) {
+        for pulse in &self.pulse {
+            pulse.save_state(w);
+        }
+        self.wave.save_state(w);
+        self.noise.save_state(w);
+        w.u8(self.step);
+    }
+    pub(super) fn load_state(&mut self, r: &mut StateReader) -> Option<()> {
+        for pulse in &mut self.pulse {
+            pulse.load_state(r)?;
+        }
+        self.wave.load_state(r)?;
+        self.noise.load_state(r)?;
+        self.step = r.u8()? % 8;
+        Some(())
+    }
+    fn set_rate(&mut self, rate: u32) {
+        self.rate = rate;
+        self.kept = 1.0;
+        for _ in 0..CLOCK_HZ / rate.max(1) as u64 {
+            self.kept *= CHARGE_KEPT;
+        }
+        self.samples.clear();
+    }
+    fn powered(mem: &Mem) -> bool {
+        reg(mem, NR52) & 0x80 > 0
+    }
+    // a cpu write to one of the sound registers or wave ram
+    pub(super) fn write(&mut self, mem: &mut Mem, i: u16, val: u8) {
+        let powered = Self::powered(mem);
+        if i == NR52 {
+            let on = val & 0x80 > 0;
+            if powered && !on {
+                self.power_off(mem);
+            } else if !powered && on {
+                self.step = 0;
+            }
+            mem[NR52 as usize] = (val & 0x80) | (reg(mem, NR52) & 0xF);
+            self.update_status(mem);
+            return;
+        }
+        if i >= WAVE_RAM {
+            mem[i as usize] = val;
+            return;
+        }
+        // while it's off the registers stay clear, but the dmg still takes
+        // lengths
+        if !powered {
+            match i {
+                NR11 => self.pulse[0].length = 64 - (val & 0x3F) as u16,
+                NR21 => self.pulse[1].length = 64 - (val & 0x3F) as u16,
+                NR31 => self.wave.length = 256 - val as u16,
+                NR41 => self.noise.length = 64 - (val & 0x3F) as u16,
+                _ => {}
+            }
+            return;
+        }
+        mem[i as usize] = val;
+        let trigger = val & 0x80 > 0;
+        match i {
+            NR11 => self.pulse[0].length = 64 - (val & 0x3F) as u16,
+            NR21 => self.pulse[1].length = 64 - (val & 0x3F) as u16,
+            NR31 => self.wave.length = 256 - val as u16,
+            NR41 => self.noise.length = 64 - (val & 0x3F) as u16,
+            // turning a dac off turns its channel off too
+            NR12 if val & 0xF8 == 0 => self.pulse[0].on = false,
+            NR22 if val & 0xF8 == 0 => self.pulse[1].on = false,
+            NR30 if val & 0x80 == 0 => self.wave.on = false,
+            NR42 if val & 0xF8 == 0 => self.noise.on = false,
+            NR14 if trigger => self.pulse[0].trigger(mem, NR11),
+            NR24 if trigger => self.pulse[1].trigger(mem, NR21),
+            NR34 if trigger => self.wave.trigger(mem),
+            NR44 if trigger => self.noise.trigger(mem),
+            _ => {}
+        }
+        self.update_status(mem);
+    }
+    // clears every register but wave ram, and everything about the
+    // channels but their lengths
+    fn power_off(&mut self, mem: &mut Mem) {
+        mem[NR10 as usize..NR52 as usize].fill(0);
+        let pulse = |p: &Pulse| Pulse {
+            length: p.length,
+            ..Pulse::default()
+        };
+        self.pulse = [pulse(&self.pulse[0]), pulse(&self.pulse[1])];
+        self.wave = Wave {
+            length: self.wave.length,
+            ..Wave::default()
+        };
+        self.noise = Noise {
+            length: self.noise.length,
+            ..Noise::default()
+        };
+    }
+    // the low bits of nr52 say which channels are on
+    fn update_status(&self, mem: &mut Mem) {
+        let on = [
+            self.pulse[0].on,
+            self.pulse[1].on,
+            self.wave.on,
+            self.noise.on,
+        ];
+        let bits = on.iter().rev().fold(0, |bits, &on| bits << 1 | on as u8);
+        mem[NR52 as usize] = (reg(mem, NR52) & 0xF0) | bits;
+    }
+    // lengths on every other step, the sweep on 2 and 6, and envelopes on 7
+    fn clock_sequencer(&mut self, mem: &mut Mem) {
+        if self.step.is_multiple_of(2) {
+            let [p1, p2] = &mut self.pulse;
+            clock_length(&mut p1.length, &mut p1.on, reg(mem, NR14));
+            clock_length(&mut p2.length, &mut p2.on, reg(mem, NR24));
+            clock_length(&mut self.wave.length, &mut self.wave.on, reg(mem, NR34));
+            clock_length(&mut self.noise.length, &mut self.noise.on, reg(mem, NR44));
+        }
+        if self.step == 2 || self.step == 6 {
+            self.pulse[0].clock_sweep(mem);
+        }
+        if self.step == 7 {
+            self.pulse[0].envelope.clock(reg(mem, NR12));
+            self.pulse[1].envelope.clock(reg(mem, NR22));
+            self.noise.envelope.clock(reg(mem, NR42));
+        }
+        self.step = (self.step + 1) % 8;
+    }
+    // div_fell is whether bit 4 of DIV went from 1 to 0, which is what
+    // clocks the frame sequencer
+    pub(super) fn tick(&mut self, mem: &mut Mem, t_cyc: u8, div_fell: bool) {
+        if Self::powered(mem) {
+            if div_fell {
+                self.clock_sequencer(mem);
+            }
+            let t = t_cyc as u32;
+            self.pulse[0].tick(mem, NR11, t);
+            self.pulse[1].tick(mem, NR21, t);
+            self.wave.tick(mem, t);
+            self.noise.tick(mem, t);
+            self.update_status(mem);
+        }
+        if self.rate > 0 {
+            self.mix(mem, t_cyc);
+        }
+    }
+    fn mix(&mut self, mem: &Mem, t_cyc: u8) {
+        let outputs = [
+            self.pulse[0].output(mem, NR11),
+            self.pulse[1].output(mem, NR21),
+            self.wave.output(mem),
+            self.noise.output(),
+        ];
+        let dacs = [
+            reg(mem, NR12) & 0xF8 > 0,
+            reg(mem, NR22) & 0xF8 > 0,
+            reg(mem, NR30) & 0x80 > 0,
+            reg(mem, NR42) & 0xF8 > 0,
+        ];
+        let (nr50, nr51) = (reg(mem, NR50), reg(mem, NR51));
+        // right is the low nibble of nr51 and the low bits of nr50
+        for (side, shift) in [(0, 4), (1, 0)] {
+            let mut mix = 0;
+            for (ch, (&out, &dac)) in outputs.iter().zip(&dacs).enumerate() {
+                // a dac turns 0 to 15 into a swing either side of 0
+                if dac && nr51 >> (ch + shift) & 1 > 0 {
+                    mix += out as i32 * 2 - 15;
+                }
+            }
+            let volume = (nr50 >> shift & 7) as i32 + 1;
+            self.sum[side] += mix * volume * t_cyc as i32;
+        }
+        self.cycles += t_cyc as u32;
+        self.clock += t_cyc as u64 * self.rate as u64;
+        if self.clock >= CLOCK_HZ {
+            self.clock -= CLOCK_HZ;
+            self.push_sample();
+        }
+    }
+    fn push_sample(&mut self) {
+        let max = (self.rate / RING_PARTS * 2) as usize;
+        while self.samples.len() >= max {
+            self.samples.pop_front();
+        }
+        for side in 0..2 {
+            let level = self.sum[side] as f32 / self.cycles as f32 / MAX_MIX;
+            let out = level - self.charge[side];
+            self.charge[side] = level - out * self.kept;
+            self.samples.push_back((out * i16::MAX as f32) as i16);
+        }
+        self.sum = [0; 2];
+        self.cycles = 0;
+        self.frame_samples += 1;
+    }
+    pub(super) fn take_frame_samples(&mut self) -> usize {
+        core::mem::take(&mut self.frame_samples)
+    }
+}
+
+impl Emulator {
+    // sample frames a second, SAMPLE_RATE to start with, or 0 to not make
+    // any. whatever hasn't been taken yet is dropped. returns false, leaving
+    // the rate be, if it's below MIN_SAMPLE_RATE
+    pub fn set_sample_rate(&mut self, rate: u32) -> bool {
+        if rate != 0 && rate < MIN_SAMPLE_RATE {
+            return false;
+        }
+        self.ram.apu.set_rate(rate);
+        true
+    }
+    pub fn sample_rate(&self) -> u32 {
+        self.ram.apu.rate
+    }
+    // everything made since the last take, left then right for each sample
+    // frame
+    pub fn take_audio(&mut self) -> Vec<i16> {
+        self.ram.apu.samples.drain(..).collect()
+    }
+    // as many whole sample frames as fit in out, oldest first, returning
+    // how many samples that was
+    pub fn read_audio(&mut self, out: &mut [i16]) -> usize {
+        let samples = &mut self.ram.apu.samples;
+        let n = out.len().min(samples.len()) & !1;
+        for (out, sample) in out.iter_mut().zip(samples.drain(..n)) {
+            *out = sample;
+        }
+        n
+    }
+}
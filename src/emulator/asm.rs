@@ -0,0 +1,203 @@
+// turns rgbds style assembly back into instructions, the other way from
+// disasm. only takes what disasm writes, plus a few common spellings
+use alloc::{format, string::String, vec, vec::Vec};
+
+use super::disasm::{ALU, CC, MISC, R, ROT, RP, RP2};
+
+// parses $hex, 0xhex, %binary or decimal, with an optional minus
+pub(super) fn number(s: &str) -> Option<i32> {
+    let (neg, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let n = if let Some(hex) = s.strip_prefix('$').or_else(|| s.strip_prefix("0x")) {
+        i32::from_str_radix(hex, 16).ok()?
+    } else if let Some(bin) = s.strip_prefix('%') {
+        i32::from_str_radix(bin, 2).ok()?
+    } else {
+        s.parse().ok()?
+    };
+    Some(if neg { -n } else { n })
+}
+
+fn n8(s: &str) -> Result<u8, String> {
+    match number(s) {
+        Some(n @ -128..=255) => Ok(n as u8),
+        _ => Err(format!("Expected a byte, not '{s}'")),
+    }
+}
+
+fn n16(s: &str) -> Result<[u8; 2], String> {
+    match number(s) {
+        Some(n @ 0..=0xFFFF) => Ok((n as u16).to_le_bytes()),
+        _ => Err(format!("Expected an address or 16 bit number, not '{s}'")),
+    }
+}
+
+fn signed(s: &str) -> Result<u8, String> {
+    match number(s) {
+        Some(n @ -128..=127) => Ok(n as i8 as u8),
+        _ => Err(format!("Expected a number from -128 to 127, not '{s}'")),
+    }
+}
+
+// the address inside brackets, like [$c000]
+fn mem(s: &str) -> Option<&str> {
+    s.strip_prefix('[')?.strip_suffix(']').map(str::trim)
+}
+
+fn find(table: &[&str], s: &str) -> Option<u8> {
+    table.iter().position(|&t| t == s).map(|i| i as u8)
+}
+
+// what ldh takes, either $ff44 or just $44
+fn high_page(s: &str) -> Result<u8, String> {
+    match number(s) {
+        Some(n @ 0..=0xFF) => Ok(n as u8),
+        Some(n @ 0xFF00..=0xFFFF) => Ok(n as u8),
+        _ => Err(format!(
+            "ldh needs an address from $ff00 to $ffff, not '{s}'"
+        )),
+    }
+}
+
+// assembles one instruction for addr, which only matters for jr
+pub(super) fn assemble(text: &str, addr: u16) -> Result<Vec<u8>, String> {
+    let text = text.trim().to_ascii_lowercase();
+    let (op, rest) = text.split_once(' ').unwrap_or((&text, ""));
+    let args: Vec<&str> = match rest.trim() {
+        "" => Vec::new(),
+        rest => rest.split(',').map(str::trim).collect(),
+    };
+    // the few spellings that differ from what disasm writes
+    let args: Vec<&str> = args
+        .into_iter()
+        .map(|a| match a {
+            "[hli]" => "[hl+]",
+            "[hld]" => "[hl-]",
+            "[$ff00+c]" => "[c]",
+            a => a,
+        })
+        .collect();
+    let r = |s: &str| find(&R, s);
+    let cc = |s: &str| find(&CC, s);
+    let bad = || Err(format!("Can't assemble '{text}'"));
+    let bytes = match (op, args.as_slice()) {
+        ("db", args) if !args.is_empty() => {
+            return args.iter().map(|a| n8(a)).collect();
+        }
+        ("nop", []) => vec![0x00],
+        ("stop", []) => vec![0x10, 0x00],
+        ("halt", []) => vec![0x76],
+        ("di", []) => vec![0xF3],
+        ("ei", []) => vec![0xFB],
+        ("ret", []) => vec![0xC9],
+        ("reti", []) => vec![0xD9],
+        (op, []) if find(&MISC, op).is_some() => vec![0x07 | find(&MISC, op).unwrap() << 3],
+        ("ret", [c]) if cc(c).is_some() => vec![0xC0 | cc(c).unwrap() << 3],
+        ("jp", ["hl" | "[hl]"]) => vec![0xE9],
+        ("jp", [target]) => [&[0xC3][..], &n16(target)?].concat(),
+        ("jp", [c, target]) if cc(c).is_some() => {
+            [&[0xC2 | cc(c).unwrap() << 3][..], &n16(target)?].concat()
+        }
+        ("call", [target]) => [&[0xCD][..], &n16(target)?].concat(),
+        ("call", [c, target]) if cc(c).is_some() => {
+            [&[0xC4 | cc(c).unwrap() << 3][..], &n16(target)?].concat()
+        }
+        ("jr", [target]) | ("jr", [_, target]) => {
+            let op = match args.as_slice() {
+                [_] => 0x18,
+                [c, _] if cc(c).is_some() => 0x20 | cc(c).unwrap() << 3,
+                _ => return bad(),
+            };
+            let target = u16::from_le_bytes(n16(target)?);
+            let offset = target.wrapping_sub(addr.wrapping_add(2)) as i16;
+            let Ok(offset) = i8::try_from(offset) else {
+                return Err(format!("${target:04x} is too far for jr from ${addr:04x}"));
+            };
+            vec![op, offset as u8]
+        }
+        ("rst", [n]) => match number(n) {
+            Some(n @ 0..=0x38) if n % 8 == 0 => vec![0xC7 | n as u8],
+            _ => return Err(format!("rst needs one of $00, $08 ... $38, not '{n}'")),
+        },
+        ("push", [rp]) if find(&RP2, rp).is_some() => vec![0xC5 | find(&RP2, rp).unwrap() << 4],
+        ("pop", [rp]) if find(&RP2, rp).is_some() => vec![0xC1 | find(&RP2, rp).unwrap() << 4],
+        ("inc", [x]) | ("dec", [x]) => {
+            let dec = (op == "dec") as u8;
+            if let Some(y) = r(x) {
+                vec![0x04 | dec | y << 3]
+            } else if let Some(p) = find(&RP, x) {
+                vec![0x03 | dec << 3 | p << 4]
+            } else {
+                return bad();
+            }
+        }
+        ("add", ["hl", rp]) if find(&RP, rp).is_some() => vec![0x09 | find(&RP, rp).unwrap() << 4],
+        ("add", ["sp", e]) => vec![0xE8, signed(e)?],
+        // alu ops can leave off the a
+        (op, ["a", x]) | (op, [x]) if find(&ALU, op).is_some() => {
+            let y = find(&ALU, op).unwrap();
+            match r(x) {
+                Some(z) => vec![0x80 | y << 3 | z],
+                None => vec![0xC6 | y << 3, n8(x)?],
+            }
+        }
+        (op, [x]) if find(&ROT, op).is_some() && r(x).is_some() => {
+            vec![0xCB, find(&ROT, op).unwrap() << 3 | r(x).unwrap()]
+        }
+        ("bit" | "res" | "set", [b, x]) if r(x).is_some() => {
+            let x = r(x).unwrap();
+            let b = match number(b) {
+                Some(b @ 0..=7) => b as u8,
+                _ => return Err(format!("Expected a bit from 0 to 7, not '{b}'")),
+            };
+            let kind = match op {
+                "bit" => 0x40,
+                "res" => 0x80,
+                _ => 0xC0,
+            };
+            vec![0xCB, kind | b << 3 | x]
+        }
+        ("ldh", ["[c]", "a"]) => vec![0xE2],
+        ("ldh", ["a", "[c]"]) => vec![0xF2],
+        ("ldh", [to, "a"]) if mem(to).is_some() => vec![0xE0, high_page(mem(to).unwrap())?],
+        ("ldh", ["a", from]) if mem(from).is_some() => vec![0xF0, high_page(mem(from).unwrap())?],
+        ("ld", args) => ld(args)?,
+        _ => return bad(),
+    };
+    Ok(bytes)
+}
+
+fn ld(args: &[&str]) -> Result<Vec<u8>, String> {
+    let r = |s: &str| find(&R, s);
+    let indirect = ["[bc]", "[de]", "[hl+]", "[hl-]"];
+    let bytes = match *args {
+        [to, from] if r(to).is_some() && r(from).is_some() => {
+            // ld [hl], [hl] would be halt
+            if to == "[hl]" && from == "[hl]" {
+                return Err("There's no ld [hl], [hl]".into());
+            }
+            vec![0x40 | r(to).unwrap() << 3 | r(from).unwrap()]
+        }
+        ["[c]", "a"] => vec![0xE2],
+        ["a", "[c]"] => vec![0xF2],
+        ["sp", "hl"] => vec![0xF9],
+        [to, "a"] if find(&indirect, to).is_some() => {
+            vec![0x02 | find(&indirect, to).unwrap() << 4]
+        }
+        ["a", from] if find(&indirect, from).is_some() => {
+            vec![0x0A | find(&indirect, from).unwrap() << 4]
+        }
+        ["hl", from] if from.starts_with("sp") => vec![0xF8, signed(from[2..].trim())?],
+        [to, "sp"] if mem(to).is_some() => [&[0x08][..], &n16(mem(to).unwrap())?].concat(),
+        [to, "a"] if mem(to).is_some() => [&[0xEA][..], &n16(mem(to).unwrap())?].concat(),
+        ["a", from] if mem(from).is_some() => [&[0xFA][..], &n16(mem(from).unwrap())?].concat(),
+        [to, from] if r(to).is_some() => vec![0x06 | r(to).unwrap() << 3, n8(from)?],
+        [to, from] if find(&RP, to).is_some() => {
+            [&[0x01 | find(&RP, to).unwrap() << 4][..], &n16(from)?].concat()
+        }
+        _ => return Err(format!("Can't assemble 'ld {}'", args.join(", "))),
+    };
+    Ok(bytes)
+}
@@ -0,0 +1,78 @@
+// the barcode boy, a card swiper on the link port that a few bandai games
+// like monster maker barcode saga came with. the game looks for it by
+// sending 10 07 10 07, to which it answers ff ff 10 07. after that the game
+// waits on the external clock, and each swipe sends the card's jan-13 code
+// as ascii digits between 02 and 03, twice over
+use alloc::{collections::VecDeque, format, rc::Rc, string::String};
+use core::cell::RefCell;
+
+use super::serial::SerialDevice;
+
+const HANDSHAKE: [u8; 4] = [0x10, 0x07, 0x10, 0x07];
+const REPLIES: [u8; 4] = [0xFF, 0xFF, 0x10, 0x07];
+const STX: u8 = 0x02;
+const ETX: u8 = 0x03;
+
+#[derive(Default)]
+struct Reader {
+    // how much of the handshake the game's sent
+    handshake: usize,
+    // swipes waiting for the game to clock them in
+    sending: VecDeque<u8>,
+}
+
+// a handle on the reader, one of which is plugged in while the frontend
+// keeps another to swipe cards with
+#[derive(Clone, Default)]
+pub struct BarcodeBoy {
+    reader: Rc<RefCell<Reader>>,
+}
+
+impl BarcodeBoy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    // swipes a card with a 13 digit code, which goes once the game's found
+    // the reader and is listening
+    pub fn scan(&self, code: &str) -> Result<(), String> {
+        if code.len() != 13 || !code.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(format!("a barcode is 13 digits, not '{code}'"));
+        }
+        let mut reader = self.reader.borrow_mut();
+        for _ in 0..2 {
+            reader.sending.push_back(STX);
+            reader.sending.extend(code.bytes());
+            reader.sending.push_back(ETX);
+        }
+        Ok(())
+    }
+    // whether the game has gone through the handshake
+    pub fn found(&self) -> bool {
+        self.reader.borrow().handshake == HANDSHAKE.len()
+    }
+}
+
+impl SerialDevice for BarcodeBoy {
+    fn send(&mut self, byte: u8) -> Option<u8> {
+        let mut reader = self.reader.borrow_mut();
+        let i = reader.handshake % HANDSHAKE.len();
+        if byte == HANDSHAKE[i] {
+            reader.handshake = i + 1;
+            Some(REPLIES[i])
+        } else if byte == HANDSHAKE[0] {
+            // anything out of order starts the handshake over
+            reader.handshake = 1;
+            Some(REPLIES[0])
+        } else {
+            reader.handshake = 0;
+            Some(0xFF)
+        }
+    }
+    fn receive(&mut self, _sb: u8) -> Option<u8> {
+        let mut reader = self.reader.borrow_mut();
+        if reader.handshake < HANDSHAKE.len() {
+            return None;
+        }
+        reader.sending.pop_front()
+    }
+}
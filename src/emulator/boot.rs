@@ -0,0 +1,137 @@
+// a boot rom of our own, for the logo scroll without needing nintendo's.
+// it leaves the cpu and io registers the way the original does, but skips
+// the logo and header checks
+pub const BUILTIN_BOOT_ROM: [u8; 0x100] = [
+    0x31, 0xFE, 0xFF, // ld sp, $fffe
+    // clear vram
+    0xAF, // xor a
+    0x21, 0xFF, 0x9F, // ld hl, $9fff
+    // clear:
+    0x32, // ld [hl-], a
+    0xCB, 0x7C, // bit 7, h
+    0x20, 0xFB, // jr nz, clear
+    // sound on, with the same settings the original leaves
+    0x21, 0x26, 0xFF, // ld hl, $ff26
+    0x0E, 0x11, // ld c, $11
+    0x3E, 0x80, // ld a, $80
+    0x32, // ld [hl-], a
+    0xE2, // ldh [c], a
+    0x0C, // inc c
+    0x3E, 0xF3, // ld a, $f3
+    0xE2, // ldh [c], a
+    0x32, // ld [hl-], a
+    0x3E, 0x77, // ld a, $77
+    0x77, // ld [hl], a
+    0x3E, 0xFC, // ld a, $fc
+    0xE0, 0x47, // ldh [$47], a
+    // the logo from the header, where each nibble is a row of 4 pixels.
+    // every pixel gets doubled both ways into tiles 1-24
+    0x11, 0x04, 0x01, // ld de, $0104
+    0x21, 0x10, 0x80, // ld hl, $8010
+    // logo:
+    0x1A, // ld a, [de]
+    0xD5, // push de, freeing d for the doubled row
+    0x4F, // ld c, a
+    0x06, 0x08, // ld b, 8
+    // bit:
+    0xCB, 0x21, // sla c
+    0x9F, // sbc a, a
+    0xE6, 0x03, // and 3
+    0xCB, 0x22, // sla d
+    0xCB, 0x22, // sla d
+    0xB2, // or d
+    0x57, // ld d, a
+    0x05, // dec b
+    0x78, // ld a, b
+    0xE6, 0x03, // and 3
+    0x20, 0xEF, // jr nz, bit
+    0x7A, // ld a, d
+    0x22, // ld [hl+], a
+    0x23, // inc hl
+    0x22, // ld [hl+], a
+    0x23, // inc hl
+    0x78, // ld a, b
+    0xA7, // and a
+    0x20, 0xE6, // jr nz, bit
+    0xD1, // pop de
+    0x13, // inc de
+    0x7B, // ld a, e
+    0xFE, 0x34, // cp $34
+    0x20, 0xDA, // jr nz, logo
+    // the (r) into tile 25
+    0x11, 0xB0, 0x00, // ld de, mark
+    0x06, 0x08, // ld b, 8
+    // markcopy:
+    0x1A, // ld a, [de]
+    0x13, // inc de
+    0x22, // ld [hl+], a
+    0x23, // inc hl
+    0x05, // dec b
+    0x20, 0xF9, // jr nz, markcopy
+    // the tile map, in two rows under the middle of the screen
+    0x21, 0x04, 0x99, // ld hl, $9904
+    0x3E, 0x01, // ld a, 1
+    0x0E, 0x02, // ld c, 2
+    // row:
+    0x06, 0x0C, // ld b, 12
+    // col:
+    0x22, // ld [hl+], a
+    0x3C, // inc a
+    0x05, // dec b
+    0x20, 0xFB, // jr nz, col
+    0x2E, 0x24, // ld l, $24
+    0x0D, // dec c
+    0x20, 0xF4, // jr nz, row
+    0x3E, 0x19, // ld a, $19
+    0xEA, 0x10, 0x99, // ld [$9910], a
+    // turn the lcd on and scroll the logo down from off screen
+    0x3E, 0x64, // ld a, $64
+    0xE0, 0x42, // ldh [$42], a
+    0x3E, 0x91, // ld a, $91
+    0xE0, 0x40, // ldh [$40], a
+    // scroll:
+    0xCD, 0xA3, 0x00, // call vblank
+    0xF0, 0x42, // ldh a, [$42]
+    0x3D, // dec a
+    0xE0, 0x42, // ldh [$42], a
+    0x20, 0xF6, // jr nz, scroll
+    // hold it for a second
+    0x06, 0x3C, // ld b, 60
+    // hold:
+    0xCD, 0xA3, 0x00, // call vblank
+    0x05, // dec b
+    0x20, 0xFA, // jr nz, hold
+    // registers as the original leaves them. h and c are only set when
+    // the header checksum isn't 0
+    0xFA, 0x4D, 0x01, // ld a, [$014d]
+    0xA7, // and a
+    0x21, 0xB0, 0x01, // ld hl, $01b0
+    0x20, 0x02, // jr nz, flags
+    0x2E, 0x80, // ld l, $80
+    // flags:
+    0xE5, // push hl
+    0xF1, // pop af
+    0x01, 0x13, 0x00, // ld bc, $0013
+    0x11, 0xD8, 0x00, // ld de, $00d8
+    0x21, 0x4D, 0x01, // ld hl, $014d
+    0x18, 0x5B, // jr finish
+    // waits for the start of the next vblank
+    // vblank:
+    0xF0, 0x44, // ldh a, [$44]
+    0xFE, 0x90, // cp $90
+    0x20, 0xFA, // jr nz, vblank
+    // vblank_end:
+    0xF0, 0x44, // ldh a, [$44]
+    0xFE, 0x90, // cp $90
+    0x28, 0xFA, // jr z, vblank_end
+    0xC9, // ret
+    // mark:
+    0x3C, 0x42, 0xB9, 0xA5, 0xB9, 0xA5, 0x42, 0x3C, // (r)
+    // padding, so the last instruction ends at 0x100
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xE0,
+    0x50, // finish: ldh [$50], a, which unmaps this rom
+];
@@ -0,0 +1,178 @@
+// what the header says is on the cart, and the mbc to go with it
+use alloc::{boxed::Box, string::String};
+
+use super::{mbc::*, rtc::Rtc};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MbcKind {
+    None,
+    Mbc1,
+    Mbc3,
+    // mbc3 with a full 8 bit rom bank and 8 ram banks, for 4mb carts
+    Mbc30,
+    Mbc5,
+    // unlicensed, switching the whole 32kb at once
+    WisdomTree,
+    M161,
+    // bandai's, used by tamagotchi 3
+    Tama5,
+}
+
+// the names frontends use for mbcs, in the same order as MbcKind
+pub const MBC_NAMES: [&str; 8] = [
+    "none",
+    "mbc1",
+    "mbc3",
+    "mbc30",
+    "mbc5",
+    "wisdom-tree",
+    "m161",
+    "tama5",
+];
+
+impl MbcKind {
+    pub const ALL: [MbcKind; 8] = [
+        MbcKind::None,
+        MbcKind::Mbc1,
+        MbcKind::Mbc3,
+        MbcKind::Mbc30,
+        MbcKind::Mbc5,
+        MbcKind::WisdomTree,
+        MbcKind::M161,
+        MbcKind::Tama5,
+    ];
+    pub fn from_name(name: &str) -> Option<Self> {
+        let i = MBC_NAMES
+            .iter()
+            .position(|&n| n.eq_ignore_ascii_case(name))?;
+        Some(Self::ALL[i])
+    }
+    // None for the cart types there's nothing for yet. huc1 is close
+    // enough to mbc1 to run as one
+    pub(super) fn from_header(rom: &[u8]) -> Option<Self> {
+        // wisdom tree carts claim to be rom only, but are too big for that
+        let wisdom_tree = |rom: &[u8]| {
+            rom.windows(11)
+                .any(|w| w == b"WISDOM TREE" || w == b"WISDOM\0TREE")
+        };
+        let kind = match rom[0x147] {
+            0x00 if rom.len() > 0x8000 && wisdom_tree(rom) => MbcKind::WisdomTree,
+            // rom only, or with ram but no mbc
+            0x00 | 0x08 | 0x09 => MbcKind::None,
+            0x01..=0x03 | 0xFF => MbcKind::Mbc1,
+            // there's no cart type for mbc30, only the sizes give it away
+            0x0F..=0x13 if rom[0x148] == 7 || rom[0x149] == 5 => MbcKind::Mbc30,
+            0x0F..=0x13 => MbcKind::Mbc3,
+            0x19..=0x1E => MbcKind::Mbc5,
+            0xFD => MbcKind::Tama5,
+            _ => return None,
+        };
+        Some(kind)
+    }
+}
+
+// the name of a cart type byte, for saying which one isn't supported
+pub(super) fn cart_type_name(cart_type: u8) -> &'static str {
+    match cart_type {
+        0x05 | 0x06 => "MBC2",
+        0x0B..=0x0D => "MMM01",
+        0x20 => "MBC6",
+        0x22 => "MBC7",
+        0xFC => "Pocket Camera",
+        0xFE => "HuC3",
+        _ => "unknown",
+    }
+}
+
+// the header at 0x100-0x14f, as the cart describes itself
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CartridgeHeader {
+    // up to 16 characters, since newer carts use the end of it for other
+    // things. anything unprintable shows as a space
+    pub title: String,
+    pub cart_type: u8,
+    // in bytes, or None if the size byte isn't one
+    pub rom_size: Option<usize>,
+    pub ram_size: usize,
+    pub header_checksum: u8,
+    // what the checksum works out to, which the boot rom hangs on if it
+    // doesn't match
+    pub actual_checksum: u8,
+    pub global_checksum: u16,
+    // None for cart types there's no mbc for
+    pub mbc: Option<MbcKind>,
+}
+
+impl CartridgeHeader {
+    // None if the rom isn't long enough to have one
+    pub fn parse(rom: &[u8]) -> Option<Self> {
+        let header = rom.get(0x100..0x150)?;
+        let title: String = rom[0x134..0x144]
+            .iter()
+            .take_while(|&&b| b != 0)
+            .map(|&b| if b.is_ascii_graphic() { b as char } else { ' ' })
+            .collect();
+        let actual_checksum = rom[0x134..0x14D]
+            .iter()
+            .fold(0u8, |sum, &b| sum.wrapping_sub(b).wrapping_sub(1));
+        let mbc = MbcKind::from_header(rom);
+        Some(CartridgeHeader {
+            title: title.trim_end().into(),
+            cart_type: header[0x47],
+            rom_size: match header[0x48] {
+                n @ 0..=8 => Some(0x8000 << n),
+                _ => None,
+            },
+            ram_size: ram_size(rom, mbc.unwrap_or(MbcKind::None)),
+            header_checksum: header[0x4D],
+            actual_checksum,
+            global_checksum: u16::from_be_bytes([header[0x4E], header[0x4F]]),
+            mbc,
+        })
+    }
+    pub fn checksum_ok(&self) -> bool {
+        self.header_checksum == self.actual_checksum
+    }
+}
+
+// the bytes of cart ram, from the header
+pub(super) fn ram_size(rom: &[u8], kind: MbcKind) -> usize {
+    match rom[0x149] {
+        1 => 0x800,
+        2 => 0x2000,
+        3 => 0x8000,
+        4 => 0x20000,
+        5 => 0x10000,
+        // tama5 carts say they have none, but have 32 bytes behind the mcu
+        _ if kind == MbcKind::Tama5 => 32,
+        _ => 0,
+    }
+}
+
+// from the cart type in the header
+pub(super) fn has_battery(rom: &[u8]) -> bool {
+    let kind = rom.get(0x147).copied().unwrap_or_default();
+    matches!(
+        kind,
+        0x03 | 0x06 | 0x09 | 0x0D | 0x0F | 0x10 | 0x13 | 0x1B | 0x1E | 0x22 | 0xFD..=0xFF
+    )
+}
+
+// rtc_start is how many seconds the rtc has already counted, on carts that
+// have one
+pub(super) fn new_mbc(rom: &[u8], kind: MbcKind, rtc_start: u64) -> Box<dyn Mbc> {
+    match kind {
+        MbcKind::None => Box::new(NoMbc),
+        MbcKind::Mbc1 => Box::new(Mbc1::new()),
+        MbcKind::Mbc3 | MbcKind::Mbc30 => {
+            // mbc3+timer+battery, with or without ram
+            let timer = matches!(rom[0x147], 0x0F | 0x10);
+            let rtc = timer.then(|| Rtc::new(rtc_start));
+            Box::new(Mbc3::new(kind == MbcKind::Mbc30, rtc))
+        }
+        MbcKind::Mbc5 => Box::new(Mbc5::new()),
+        MbcKind::WisdomTree => Box::new(WholeRom::new(false)),
+        MbcKind::M161 => Box::new(WholeRom::new(true)),
+        MbcKind::Tama5 => Box::new(Tama5::new()),
+    }
+}
@@ -0,0 +1,63 @@
+// game genie and gameshark codes
+use alloc::vec::Vec;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Cheat {
+    // replaces a byte of rom. with compare it only does so while the rom
+    // holds that byte, so it doesn't hit the same address in every bank
+    GameGenie {
+        addr: u16,
+        value: u8,
+        compare: Option<u8>,
+    },
+    // writes a byte of ram every frame
+    GameShark {
+        addr: u16,
+        value: u8,
+    },
+}
+
+impl Cheat {
+    // parses game genie codes like 00A-17B-C49 or 00A-17B, and gameshark
+    // codes like 010238CD
+    pub fn parse(code: &str) -> Option<Self> {
+        let digits = code
+            .chars()
+            .filter(|&c| c != '-')
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<Vec<_>>>()?;
+        let byte = |i: usize| (digits[i] << 4) | digits[i + 1];
+        match digits.len() {
+            // ttvvllhh, where type 01 is the only one that writes plain ram
+            8 if !code.contains('-') => {
+                if !matches!(byte(0), 0x00 | 0x01) {
+                    return None;
+                }
+                let addr = u16::from_le_bytes([byte(4), byte(6)]);
+                Some(Cheat::GameShark {
+                    addr,
+                    value: byte(2),
+                })
+            }
+            // vv, then the address with its top digit moved to the end and
+            // inverted, then the compare value scrambled around a check digit
+            6 | 9 => {
+                let addr = ((digits[5] as u16 ^ 0xF) << 12)
+                    | (digits[2] as u16) << 8
+                    | (digits[3] as u16) << 4
+                    | digits[4] as u16;
+                if addr >= 0x8000 {
+                    return None;
+                }
+                let compare = (digits.len() == 9)
+                    .then(|| ((digits[6] << 4) | digits[8]).rotate_right(2) ^ 0xBA);
+                Some(Cheat::GameGenie {
+                    addr,
+                    value: byte(0),
+                    compare,
+                })
+            }
+            _ => None,
+        }
+    }
+}
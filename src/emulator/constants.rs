@@ -1,4 +1,5 @@
 #![allow(unused)]
+pub(super) const P1: u16 = 0xFF00;
 pub(super) const SB: u16 = 0xFF01;
 pub(super) const SC: u16 = 0xFF02;
 pub(super) const DIV: u16 = 0xFF04;
@@ -6,6 +7,28 @@ pub(super) const TIMA: u16 = 0xFF05;
 pub(super) const TMA: u16 = 0xFF06;
 pub(super) const TAC: u16 = 0xFF07;
 pub(super) const IF: u16 = 0xFF0F;
+pub(super) const NR10: u16 = 0xFF10;
+pub(super) const NR11: u16 = 0xFF11;
+pub(super) const NR12: u16 = 0xFF12;
+pub(super) const NR13: u16 = 0xFF13;
+pub(super) const NR14: u16 = 0xFF14;
+pub(super) const NR21: u16 = 0xFF16;
+pub(super) const NR22: u16 = 0xFF17;
+pub(super) const NR23: u16 = 0xFF18;
+pub(super) const NR24: u16 = 0xFF19;
+pub(super) const NR30: u16 = 0xFF1A;
+pub(super) const NR31: u16 = 0xFF1B;
+pub(super) const NR32: u16 = 0xFF1C;
+pub(super) const NR33: u16 = 0xFF1D;
+pub(super) const NR34: u16 = 0xFF1E;
+pub(super) const NR41: u16 = 0xFF20;
+pub(super) const NR42: u16 = 0xFF21;
+pub(super) const NR43: u16 = 0xFF22;
+pub(super) const NR44: u16 = 0xFF23;
+pub(super) const NR50: u16 = 0xFF24;
+pub(super) const NR51: u16 = 0xFF25;
+pub(super) const NR52: u16 = 0xFF26;
+pub(super) const WAVE_RAM: u16 = 0xFF30;
 pub(super) const LCDC: u16 = 0xFF40;
 pub(super) const STAT: u16 = 0xFF41;
 pub(super) const SCY: u16 = 0xFF42;
@@ -16,6 +39,22 @@ pub(super) const DMA: u16 = 0xFF46;
 pub(super) const BGP: u16 = 0xFF47;
 pub(super) const OBP0: u16 = 0xFF48;
 pub(super) const OBP1: u16 = 0xFF49;
+pub(super) const WY: u16 = 0xFF4A;
+pub(super) const WX: u16 = 0xFF4B;
+pub(super) const BOOT: u16 = 0xFF50;
 pub(super) const IE: u16 = 0xFFFF;
+// t-cycles in a frame
+pub const FRAME_CYCLES: u32 = 70224;
+// t-cycles in a scanline
+pub const LINE_CYCLES: u32 = 456;
+// t-cycles in a second
+pub const CLOCK_HZ: u64 = 4_194_304;
 pub const SCRN_X: usize = 160;
 pub const SCRN_Y: usize = 144;
+// the colors of the 4 shades as rgba, lightest first
+pub const DEFAULT_PALETTE: [[u8; 4]; 4] = [
+    [0xC6, 0xDE, 0x8C, 0xFF],
+    [0x84, 0xA5, 0x63, 0xFF],
+    [0x39, 0x61, 0x39, 0xFF],
+    [0x08, 0x18, 0x10, 0xFF],
+];
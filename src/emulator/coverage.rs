@@ -0,0 +1,57 @@
+// which rom bytes have run as code, for rom hacking and for checking a test
+// rom went where it should. while running only the start of each
+// instruction is marked, and its operands are filled in from the rom when
+// it's written out as a code/data log: a byte per rom byte, with bit 0 set
+// for code like the .cdl files fceux and mesen write
+use alloc::{boxed::Box, vec, vec::Vec};
+
+use super::{Emulator, disasm::disassemble};
+
+pub const CDL_CODE: u8 = 1;
+
+#[derive(Default)]
+pub struct Coverage {
+    // by rom offset
+    starts: Vec<bool>,
+}
+
+impl Coverage {
+    fn add(&mut self, offset: usize) {
+        if self.starts.len() <= offset {
+            self.starts.resize(offset + 1, false);
+        }
+        self.starts[offset] = true;
+    }
+    // how many different instructions have run
+    pub fn instructions(&self) -> usize {
+        self.starts.iter().filter(|&&run| run).count()
+    }
+    pub fn cdl(&self, rom: &[u8]) -> Vec<u8> {
+        let mut cdl = vec![0; rom.len()];
+        for (offset, _) in self.starts.iter().enumerate().filter(|&(_, &run)| run) {
+            // only the length's wanted, which doesn't depend on the address
+            let read = |i: u16| rom.get(offset + i as usize).copied().unwrap_or(0);
+            let len = disassemble(read, 0).1 as usize;
+            let end = (offset + len).min(rom.len());
+            cdl[offset.min(end)..end].fill(CDL_CODE);
+        }
+        cdl
+    }
+}
+
+impl Emulator {
+    // starts marking what runs afresh, or stops
+    pub fn set_coverage(&mut self, on: bool) {
+        self.coverage = on.then(Box::default);
+    }
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_deref()
+    }
+    pub(super) fn coverage_step(&mut self, pc: u16) {
+        if let Some(coverage) = &mut self.coverage
+            && let Some(bank) = self.ram.code_bank(pc)
+        {
+            coverage.add(bank * 0x4000 + pc as usize % 0x4000);
+        }
+    }
+}
@@ -1,6 +1,9 @@
-use super::{constants::*, ram::CpuBus};
+use super::{
+    ram::CpuBus,
+    state::{StateReader, StateWriter},
+};
 
-#[derive(PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 enum Ime {
     Disabled,
     Pending,
@@ -35,6 +38,21 @@ impl From<Flag> for u8 {
     }
 }
 
+// a copy of the registers, for debuggers and for comparing against another core
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Registers {
+    pub a: u8,
+    pub f: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub sp: u16,
+    pub pc: u16,
+}
+
 pub(super) struct Cpu {
     pub(super) pc: u16,
     pub(super) sp: u16,
@@ -50,6 +68,11 @@ pub(super) struct Cpu {
     ime: Ime,
     halted: bool,
     stopped: bool,
+    // illegal opcodes hang the cpu for good
+    pub(super) locked: bool,
+    pub(super) instructions: u64,
+    // interrupts serviced, by IF bit
+    pub(super) interrupts: [u64; 5],
 }
 
 impl Cpu {
@@ -74,35 +97,42 @@ impl Cpu {
             ime: Ime::Disabled,
             halted: false,
             stopped: false,
+            locked: false,
+            instructions: 0,
+            interrupts: [0; 5],
         }
     }
     #[allow(clippy::needless_return)]
     pub(super) fn tick<T: CpuBus>(&mut self, ram: &mut T) -> u8 {
-        if self.stopped {
+        if self.stopped || self.locked {
             return 1;
         }
-        let if_ = ram.read(IF);
-        let ie = ram.read(IE);
+        let pending = ram.pending_interrupt();
         if self.halted {
-            if if_ & ie & 0b11111 > 0 {
+            // waking up takes a cycle of its own, and only then does the
+            // interrupt get serviced (ime set) or the next instruction run (ime clear)
+            if pending.is_some() {
                 self.halted = false;
-            } else {
-                return 1;
             }
+            return 1;
         }
         // self.log(ram);
-        if self.ime == Ime::Enabled && if_ & ie & 0b11111 > 0 {
-            let bit = (if_ & ie).trailing_zeros() as u16;
+        if self.ime == Ime::Enabled
+            && let Some(interrupt) = pending
+        {
             self.push16(ram, self.pc);
-            self.pc = 0x40 + bit * 8;
-            ram.write(IF, if_ & !(1 << bit));
+            self.pc = interrupt.vector();
+            // the push can land on IF itself, so it's only cleared after
+            ram.acknowledge_interrupt(interrupt);
             self.ime = Ime::Disabled;
+            self.interrupts[interrupt as usize] += 1;
             return 5;
         }
         if self.ime == Ime::Pending {
             self.ime = Ime::Enabled;
         }
         let op = self.fetch(ram);
+        self.instructions += 1;
         match (op >> 6) & 0b11 {
             // block 0
             0 => match op & 0b111_111 {
@@ -112,7 +142,7 @@ impl Cpu {
                 0b001_000 => {
                     let n16 = self.fetch16(ram);
                     ram.write(n16, self.sp as u8);
-                    ram.write(n16 + 1, (self.sp >> 8) as u8);
+                    ram.write(n16.wrapping_add(1), (self.sp >> 8) as u8);
                     return 5;
                 }
                 // rlca
@@ -221,7 +251,7 @@ impl Cpu {
                             return 3;
                         } else {
                             // still need to increment if cond failed
-                            self.pc += 1;
+                            self.pc = self.pc.wrapping_add(1);
                             return 2;
                         }
                     }
@@ -340,9 +370,9 @@ impl Cpu {
                 // halt
                 if op == 0b0111_0110 {
                     self.halted = true;
-                    if self.ime == Ime::Disabled && if_ & ie & 0b11111 > 0 {
+                    if self.ime == Ime::Disabled && pending.is_some() {
                         // TODO: do halt bug
-                        println!("WARNING: HALT BUG NOT IMPLEMENTED!");
+                        log::warn!(target: "cpu", "The halt bug isn't implemented");
                     }
                     return 1;
                 // ld r8, r8
@@ -900,7 +930,13 @@ impl Cpu {
                 }
                 // ei
                 0b111_011 => {
-                    self.ime = Ime::Pending;
+                    // a second ei doesn't push back the first one, so interrupts
+                    // still get serviced after the instruction following the first.
+                    // a di right after ei still wins, since it runs before any
+                    // interrupt can be serviced
+                    if self.ime == Ime::Disabled {
+                        self.ime = Ime::Pending;
+                    }
                     return 1;
                 }
                 _ => match op & 0b1111 {
@@ -920,7 +956,7 @@ impl Cpu {
                             return 4;
                         } else {
                             // still need to increment if cond failed
-                            self.pc += 2;
+                            self.pc = self.pc.wrapping_add(2);
                             return 3;
                         }
                     }
@@ -933,7 +969,7 @@ impl Cpu {
                             return 6;
                         } else {
                             // still need to increment if cond failed
-                            self.pc += 2;
+                            self.pc = self.pc.wrapping_add(2);
                             return 3;
                         }
                     }
@@ -967,11 +1003,11 @@ impl Cpu {
                         }
                         return 4;
                     }
+                    // left on the opcode so it shows up in the debugger
                     _ => {
-                        if op == 0xED {
-                            self.print_regs();
-                        }
-                        panic!("{:#04x} is not an instruction", op);
+                        self.pc = self.pc.wrapping_sub(1);
+                        self.locked = true;
+                        return 1;
                     }
                 },
             },
@@ -981,7 +1017,7 @@ impl Cpu {
     }
     fn fetch<T: CpuBus>(&mut self, ram: &T) -> u8 {
         let val = ram.read(self.pc);
-        self.pc += 1;
+        self.pc = self.pc.wrapping_add(1);
         val
     }
     fn fetch16<T: CpuBus>(&mut self, ram: &T) -> u16 {
@@ -989,14 +1025,14 @@ impl Cpu {
     }
     fn pop<T: CpuBus>(&mut self, ram: &T) -> u8 {
         let val = ram.read(self.sp);
-        self.sp += 1;
+        self.sp = self.sp.wrapping_add(1);
         val
     }
     fn pop16<T: CpuBus>(&mut self, ram: &T) -> u16 {
         self.pop(ram) as u16 | ((self.pop(ram) as u16) << 8)
     }
     fn push<T: CpuBus>(&mut self, ram: &mut T, val: u8) {
-        self.sp -= 1;
+        self.sp = self.sp.wrapping_sub(1);
         ram.write(self.sp, val);
     }
     fn push16<T: CpuBus>(&mut self, ram: &mut T, val: u16) {
@@ -1053,7 +1089,96 @@ impl Cpu {
             _ => unreachable!(),
         }
     }
-    pub fn print_regs(&self) {
+    pub(super) fn registers(&self) -> Registers {
+        Registers {
+            a: self.a,
+            f: self.f.into(),
+            b: self.b,
+            c: self.c,
+            d: self.d,
+            e: self.e,
+            h: self.h,
+            l: self.l,
+            sp: self.sp,
+            pc: self.pc,
+        }
+    }
+    // what registers leaves out, for state_hash
+    pub(super) fn status(&self) -> [u8; 4] {
+        [
+            self.ime as u8,
+            self.halted as u8,
+            self.stopped as u8,
+            self.locked as u8,
+        ]
+    }
+    pub(super) fn set_registers(&mut self, r: &Registers) {
+        (self.a, self.b, self.c, self.d) = (r.a, r.b, r.c, r.d);
+        (self.e, self.h, self.l) = (r.e, r.h, r.l);
+        self.f = r.f.into();
+        (self.sp, self.pc) = (r.sp, r.pc);
+    }
+    pub(super) fn save_state(&self, w: &mut StateWriter) {
+        let f = u8::from(self.f);
+        w.bytes(&[self.a, f, self.b, self.c, self.d, self.e, self.h, self.l]);
+        w.u16(self.sp);
+        w.u16(self.pc);
+        w.bytes(&[self.ime as u8, self.halted as u8, self.stopped as u8]);
+        w.bool(self.locked);
+    }
+    pub(super) fn load_state(&mut self, r: &mut StateReader) -> Option<()> {
+        let [a, f, b, c, d, e, h, l] = r.bytes(8)?.try_into().ok()?;
+        (self.a, self.b, self.c, self.d) = (a, b, c, d);
+        (self.e, self.h, self.l) = (e, h, l);
+        self.f = f.into();
+        (self.sp, self.pc) = (r.u16()?, r.u16()?);
+        self.ime = match r.u8()? {
+            0 => Ime::Disabled,
+            1 => Ime::Pending,
+            2 => Ime::Enabled,
+            _ => return None,
+        };
+        self.halted = r.bool()?;
+        self.stopped = r.bool()?;
+        self.locked = r.bool()?;
+        Some(())
+    }
+    // everything, or with since, only what's changed from it on one line
+    #[cfg(feature = "std")]
+    pub fn print_regs(&self, since: Option<&Registers>) {
+        if let Some(before) = since {
+            let now = self.registers();
+            let mut changes = Vec::new();
+            let bytes = [
+                ("A", before.a, now.a),
+                ("B", before.b, now.b),
+                ("C", before.c, now.c),
+                ("D", before.d, now.d),
+                ("E", before.e, now.e),
+                ("H", before.h, now.h),
+                ("L", before.l, now.l),
+            ];
+            for (name, before, now) in bytes {
+                if before != now {
+                    changes.push(format!("{name}: ${before:02x}->${now:02x}"));
+                }
+            }
+            if before.sp != now.sp {
+                changes.push(format!("SP: ${:04x}->${:04x}", before.sp, now.sp));
+            }
+            for (name, bit) in [("z", 7), ("n", 6), ("h", 5), ("c", 4)] {
+                let (before, now) = (before.f >> bit & 1, now.f >> bit & 1);
+                if before != now {
+                    changes.push(format!("{name}: {before}->{now}"));
+                }
+            }
+            if changes.is_empty() {
+                println!("No registers changed");
+            } else {
+                println!("{}", changes.join("  "));
+            }
+            return;
+        }
         println!(
             "AF: ${:04x}",
             ((self.a as u16) << 8) | u8::from(self.f) as u16
@@ -1063,6 +1188,7 @@ impl Cpu {
         println!("HL: ${:04x}", self.get_r16(2));
         println!("SP: ${:04x}", self.get_r16(3));
     }
+    #[cfg(feature = "std")]
     #[allow(dead_code)]
     pub fn log<T: CpuBus>(&self, ram: &T) {
         // A:00 F:11 B:22 C:33 D:44 E:55 H:66 L:77 SP:8888 PC:9999 PCMEM:AA,BB,CC,DD
@@ -1079,9 +1205,9 @@ impl Cpu {
             self.sp,
             self.pc,
             ram.read(self.pc),
-            ram.read(self.pc + 1),
-            ram.read(self.pc + 2),
-            ram.read(self.pc + 3),
+            ram.read(self.pc.wrapping_add(1)),
+            ram.read(self.pc.wrapping_add(2)),
+            ram.read(self.pc.wrapping_add(3)),
         )
     }
 }
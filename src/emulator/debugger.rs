@@ -0,0 +1,279 @@
+// the terminal debugger that -d and breakpoints drop into
+use std::{fs, io::stdin, num::ParseIntError};
+
+use super::{
+    Breakpoint, Emulator, MEMORY_REGIONS, VRAM_IMAGES,
+    constants::{SCRN_X, SCRN_Y},
+    expr, parse_duration,
+    vram::write_png,
+};
+
+fn parse_addr(s: &str) -> Result<u16, ParseIntError> {
+    if let Some(s) = s.strip_prefix("$") {
+        u16::from_str_radix(s, 16)
+    } else {
+        s.parse()
+    }
+}
+
+impl Emulator {
+    pub fn debug(&mut self) {
+        self.debug_mode = true;
+        let (pc, op) = (self.cpu.pc, self.ram.read(self.cpu.pc));
+        match self.code_bank(pc) {
+            Some(bank) => println!("OP at {bank:02x}:{pc:04x}: ${op:02x}"),
+            None => println!("OP at {pc:04x}: ${op:02x}"),
+        }
+        if let Some(before) = &self.last_step {
+            self.cpu.print_regs(Some(before));
+        }
+        for i in 0..self.displays.len() {
+            self.print_display(i);
+        }
+        loop {
+            let mut input = String::new();
+            stdin().read_line(&mut input).unwrap();
+            let mut input = input.split_whitespace();
+            if let Some(cmd) = input.next() {
+                match cmd {
+                    // b <addr> stops whatever bank's mapped, b <bank>:<addr>
+                    // only in that one
+                    "b" => {
+                        let Some(s) = input.next() else {
+                            continue;
+                        };
+                        let bp = if s.contains(':') {
+                            Breakpoint::parse_banked(s)
+                        } else {
+                            parse_addr(s).ok().map(Breakpoint::any_bank)
+                        };
+                        match bp {
+                            Some(bp) => {
+                                self.add_breakpoint(bp);
+                                println!("Breakpoint inserted at {bp}");
+                            }
+                            None => println!("Usage: b <addr> or b <bank>:<addr>, in rom"),
+                        }
+                    }
+                    "c" => {
+                        self.debug_mode = false;
+                        break;
+                    }
+                    "d" => {
+                        self.breakpoints.clear();
+                    }
+                    // r for all of them, r c for what the last step changed
+                    "r" => match input.next() {
+                        None => self.cpu.print_regs(None),
+                        Some("c") => match &self.last_step {
+                            Some(before) => self.cpu.print_regs(Some(before)),
+                            None => println!("Nothing's been stepped yet"),
+                        },
+                        Some(_) => println!("Usage: r [c]"),
+                    },
+                    // patching things up by hand
+                    "pc" => {
+                        if let Some(addr) = input.next().and_then(|s| parse_addr(s).ok()) {
+                            self.cpu.pc = addr;
+                        }
+                    }
+                    "w" => {
+                        if let Some(addr) = input.next().and_then(|s| parse_addr(s).ok())
+                            && let Some(val) = input.next().and_then(|s| parse_addr(s).ok())
+                            && let Ok(val) = u8::try_from(val)
+                        {
+                            self.ram.write(addr, val);
+                        }
+                    }
+                    // assembles an instruction over whatever's at addr
+                    "asm" => {
+                        let Some(addr) = input.next().and_then(|s| parse_addr(s).ok()) else {
+                            println!("Usage: asm <addr> <instruction>");
+                            continue;
+                        };
+                        let text = input.collect::<Vec<_>>().join(" ");
+                        match self.assemble(addr, &text) {
+                            Ok(len) => {
+                                let bytes: Vec<String> = (0..len as u16)
+                                    .map(|i| format!("{:02x}", self.ram.read(addr.wrapping_add(i))))
+                                    .collect();
+                                println!("${addr:04x}: {}", bytes.join(" "));
+                            }
+                            Err(e) => println!("{e}"),
+                        }
+                    }
+                    "undo" => match self.undo_patch() {
+                        Some(addr) => println!("Restored ${addr:04x}"),
+                        None => println!("Nothing to undo"),
+                    },
+                    // rtc, rtc set <days> <hh:mm:ss>, rtc add <duration>,
+                    // rtc speed <n> or rtc freeze
+                    "rtc" => {
+                        let Some(mut time) = self.rtc() else {
+                            println!("This cart has no rtc");
+                            continue;
+                        };
+                        match (input.next(), input.next(), input.next()) {
+                            (None, ..) => {}
+                            (Some("set"), Some(days), Some(clock)) => {
+                                let mut parts = clock.split(':').map(|p| p.parse().ok());
+                                if let Ok(days) = days.parse::<u16>()
+                                    && days < 512
+                                    && let (Some(Some(h)), Some(Some(m)), Some(Some(s)), None) =
+                                        (parts.next(), parts.next(), parts.next(), parts.next())
+                                    && h < 24
+                                    && m < 60
+                                    && s < 60
+                                {
+                                    (time.days, time.hours, time.minutes, time.seconds) =
+                                        (days, h, m, s);
+                                    self.set_rtc(time);
+                                } else {
+                                    println!("Usage: rtc set <days> <hh:mm:ss>");
+                                    continue;
+                                }
+                            }
+                            (Some("add"), Some(duration), None) => match parse_duration(duration) {
+                                Some(secs) => _ = self.advance_rtc(secs),
+                                None => {
+                                    println!("Durations look like 90, 45s, 2h or 1d12h30m");
+                                    continue;
+                                }
+                            },
+                            (Some("speed"), Some(n), None) => match n.parse() {
+                                Ok(n) => self.set_rtc_speed(n),
+                                Err(_) => {
+                                    println!("Usage: rtc speed <n>, with 0 freezing it");
+                                    continue;
+                                }
+                            },
+                            (Some("freeze"), None, None) => self.set_rtc_speed(0),
+                            _ => {
+                                println!("Usage: rtc [set|add|speed|freeze]");
+                                continue;
+                            }
+                        }
+                        let time = self.rtc().unwrap();
+                        match self.rtc_speed() {
+                            0 => println!("{time}, frozen"),
+                            speed => println!("{time}, at {speed}x"),
+                        }
+                    }
+                    // display <expr> prints it every time the debugger
+                    // stops, and display alone prints them all again
+                    "display" => {
+                        let text = input.collect::<Vec<_>>().join(" ");
+                        if text.is_empty() {
+                            for i in 0..self.displays.len() {
+                                self.print_display(i);
+                            }
+                            continue;
+                        }
+                        match expr::eval(&text, self) {
+                            Ok(_) => {
+                                self.displays.push(text);
+                                self.print_display(self.displays.len() - 1);
+                            }
+                            Err(e) => println!("{e}"),
+                        }
+                    }
+                    "undisplay" => {
+                        let n = input.next().and_then(|n| n.parse::<usize>().ok());
+                        match n.filter(|n| (1..=self.displays.len()).contains(n)) {
+                            Some(n) => _ = self.displays.remove(n - 1),
+                            None => println!("Usage: undisplay <n>, with n from the list"),
+                        }
+                    }
+                    "q" => {
+                        self.quit = true;
+                        self.debug_mode = false;
+                        break;
+                    }
+                    "dump" => {
+                        let Some(region) = input.next() else {
+                            println!("Regions: {}", MEMORY_REGIONS.join(", "));
+                            continue;
+                        };
+                        let Some(mem) = self.memory_region(region) else {
+                            println!("Unknown region: {region}");
+                            continue;
+                        };
+                        let path = input
+                            .next()
+                            .map_or_else(|| format!("{region}.bin"), String::from);
+                        match fs::write(&path, mem) {
+                            Ok(()) => println!("Dumped {region} to {path}"),
+                            Err(e) => println!("Unable to write {path}: {e}"),
+                        }
+                    }
+                    "png" => {
+                        let Some(image) = input.next() else {
+                            println!("Images: {}", VRAM_IMAGES.join(", "));
+                            continue;
+                        };
+                        let path = input
+                            .next()
+                            .map_or_else(|| format!("{image}.png"), String::from);
+                        match fs::File::create(&path).and_then(|f| self.export_vram(image, f)) {
+                            Ok(()) => println!("Exported {image} to {path}"),
+                            Err(e) => println!("Unable to export {image} to {path}: {e}"),
+                        }
+                    }
+                    // the screen as drawn so far this frame, with the rest
+                    // left blank
+                    "screen" => {
+                        let mut capture = self.capture_screen();
+                        capture.framebuffer[capture.drawn..].fill(0);
+                        let path = input.next().unwrap_or("screen.png");
+                        let png = fs::File::create(path).and_then(|f| {
+                            write_png(f, SCRN_X, SCRN_Y, &capture.framebuffer, &self.palette)
+                        });
+                        match png {
+                            Ok(()) => println!(
+                                "Line {}, dot {}: wrote {} drawn pixels to {path}",
+                                capture.line, capture.dot, capture.drawn
+                            ),
+                            Err(e) => println!("Unable to write {path}: {e}"),
+                        }
+                    }
+                    "x" => {
+                        let Some(s) = input.next() else {
+                            continue;
+                        };
+                        let addr = match s {
+                            // "a" => ...
+                            "sp" => self.cpu.sp,
+                            _ => match parse_addr(s).ok() {
+                                Some(addr) => addr,
+                                None => continue,
+                            },
+                        };
+                        print!("{:04x}:", addr);
+                        for i in 0..16 {
+                            print!(" {:02x}", self.ram.read(addr.wrapping_add(i)));
+                        }
+                        println!();
+                        let addr = addr.wrapping_add(16);
+                        print!("{:04x}:", addr);
+                        for i in 0..16 {
+                            print!(" {:02x}", self.ram.read(addr.wrapping_add(i)));
+                        }
+                        println!();
+                    }
+                    _ => continue,
+                }
+            } else {
+                break;
+            }
+        }
+        // stepping, unless it's carrying on
+        self.last_step = self.debug_mode.then(|| self.cpu.registers());
+    }
+    fn print_display(&self, i: usize) {
+        let text = &self.displays[i];
+        match expr::eval(text, self) {
+            Ok(val) => println!("{}: {text} = ${val:02x} ({val})", i + 1),
+            Err(e) => println!("{}: {text}: {e}", i + 1),
+        }
+    }
+}
@@ -0,0 +1,88 @@
+// turns instructions back into rgbds style assembly
+use alloc::{format, string::String};
+
+pub(super) const R: [&str; 8] = ["b", "c", "d", "e", "h", "l", "[hl]", "a"];
+pub(super) const RP: [&str; 4] = ["bc", "de", "hl", "sp"];
+pub(super) const RP2: [&str; 4] = ["bc", "de", "hl", "af"];
+pub(super) const CC: [&str; 4] = ["nz", "z", "nc", "c"];
+pub(super) const ALU: [&str; 8] = ["add", "adc", "sub", "sbc", "and", "xor", "or", "cp"];
+pub(super) const ROT: [&str; 8] = ["rlc", "rrc", "rl", "rr", "sla", "sra", "swap", "srl"];
+pub(super) const MISC: [&str; 8] = ["rlca", "rrca", "rla", "rra", "daa", "cpl", "scf", "ccf"];
+
+// disassembles the instruction at addr. returns it and its length in bytes
+pub(super) fn disassemble(read: impl Fn(u16) -> u8, addr: u16) -> (String, u8) {
+    let op = read(addr);
+    let n8 = read(addr.wrapping_add(1));
+    let n16 = n8 as u16 | ((read(addr.wrapping_add(2)) as u16) << 8);
+    // jr targets are relative to the next instruction
+    let target = addr.wrapping_add(2).wrapping_add(n8 as i8 as u16);
+    let (x, y, z) = (op >> 6, ((op >> 3) & 7) as usize, (op & 7) as usize);
+    let (p, q) = (y >> 1, y & 1);
+    let (text, len) = match (x, z) {
+        (0, 0) => match y {
+            0 => ("nop".into(), 1),
+            1 => (format!("ld [${n16:04x}], sp"), 3),
+            2 => ("stop".into(), 2),
+            3 => (format!("jr ${target:04x}"), 2),
+            _ => (format!("jr {}, ${target:04x}", CC[y - 4]), 2),
+        },
+        (0, 1) if q == 0 => (format!("ld {}, ${n16:04x}", RP[p]), 3),
+        (0, 1) => (format!("add hl, {}", RP[p]), 1),
+        (0, 2) => {
+            let mem = ["[bc]", "[de]", "[hl+]", "[hl-]"][p];
+            if q == 0 {
+                (format!("ld {mem}, a"), 1)
+            } else {
+                (format!("ld a, {mem}"), 1)
+            }
+        }
+        (0, 3) => (format!("{} {}", ["inc", "dec"][q], RP[p]), 1),
+        (0, 4) => (format!("inc {}", R[y]), 1),
+        (0, 5) => (format!("dec {}", R[y]), 1),
+        (0, 6) => (format!("ld {}, ${n8:02x}", R[y]), 2),
+        (0, _) => (MISC[y].into(), 1),
+        (1, _) if op == 0x76 => ("halt".into(), 1),
+        (1, _) => (format!("ld {}, {}", R[y], R[z]), 1),
+        (2, _) => (format!("{} a, {}", ALU[y], R[z]), 1),
+        (_, 0) => match y {
+            0..=3 => (format!("ret {}", CC[y]), 1),
+            4 => (format!("ldh [$ff{n8:02x}], a"), 2),
+            5 => (format!("add sp, {}", n8 as i8), 2),
+            6 => (format!("ldh a, [$ff{n8:02x}]"), 2),
+            _ => (format!("ld hl, sp{:+}", n8 as i8), 2),
+        },
+        (_, 1) if q == 0 => (format!("pop {}", RP2[p]), 1),
+        (_, 1) => (["ret", "reti", "jp hl", "ld sp, hl"][p].into(), 1),
+        (_, 2) => match y {
+            0..=3 => (format!("jp {}, ${n16:04x}", CC[y]), 3),
+            4 => ("ldh [c], a".into(), 1),
+            5 => (format!("ld [${n16:04x}], a"), 3),
+            6 => ("ldh a, [c]".into(), 1),
+            _ => (format!("ld a, [${n16:04x}]"), 3),
+        },
+        (_, 3) => match y {
+            0 => (format!("jp ${n16:04x}"), 3),
+            1 => {
+                let (x, y, z) = (n8 >> 6, (n8 >> 3) & 7, (n8 & 7) as usize);
+                let text = match x {
+                    0 => format!("{} {}", ROT[y as usize], R[z]),
+                    1 => format!("bit {y}, {}", R[z]),
+                    2 => format!("res {y}, {}", R[z]),
+                    _ => format!("set {y}, {}", R[z]),
+                };
+                (text, 2)
+            }
+            6 => ("di".into(), 1),
+            7 => ("ei".into(), 1),
+            _ => (format!("db ${op:02x}"), 1),
+        },
+        (_, 4) if y < 4 => (format!("call {}, ${n16:04x}", CC[y]), 3),
+        (_, 5) if q == 0 => (format!("push {}", RP2[p]), 1),
+        (_, 5) if p == 0 => (format!("call ${n16:04x}"), 3),
+        (_, 6) => (format!("{} a, ${n8:02x}", ALU[y]), 2),
+        (_, 7) => (format!("rst ${:02x}", y * 8), 1),
+        // the holes in the opcode table
+        _ => (format!("db ${op:02x}"), 1),
+    };
+    (text, len)
+}
@@ -0,0 +1,135 @@
+// expressions for the debugger, like [hl], a + 1 or [$c0a1] * 256 + [$c0a0].
+// registers and numbers are 16 bit, [x] is the byte at x, and the operators
+// are c's | ^ & << >> + - * / and unary - ~, with c's precedence
+use alloc::{format, string::String, vec::Vec};
+
+use super::{Emulator, asm::number};
+
+// binary operators, loosest first
+const LEVELS: [&[&str]; 6] = [
+    &["|"],
+    &["^"],
+    &["&"],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/"],
+];
+
+fn tokens(text: &str) -> Result<Vec<&str>, String> {
+    let word = |c: char| c.is_ascii_alphanumeric() || matches!(c, '$' | '%' | '_');
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = if word(c) {
+            rest.find(|c| !word(c)).unwrap_or(rest.len())
+        } else if rest.starts_with("<<") || rest.starts_with(">>") {
+            2
+        } else if "|^&+-*/~()[]".contains(c) {
+            1
+        } else {
+            return Err(format!("Unexpected '{c}'"));
+        };
+        tokens.push(&rest[..len]);
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<&'a str>,
+    pos: usize,
+    emu: &'a Emulator,
+}
+
+impl<'a> Parser<'a> {
+    fn next(&mut self) -> Option<&'a str> {
+        let token = self.tokens.get(self.pos).copied();
+        self.pos += 1;
+        token
+    }
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        match self.next() {
+            Some(t) if t == token => Ok(()),
+            Some(t) => Err(format!("Expected '{token}', not '{t}'")),
+            None => Err(format!("Expected '{token}' at the end")),
+        }
+    }
+    fn binary(&mut self, level: usize) -> Result<u16, String> {
+        let Some(ops) = LEVELS.get(level) else {
+            return self.unary();
+        };
+        let mut lhs = self.binary(level + 1)?;
+        while let Some(&op) = self.tokens.get(self.pos).filter(|t| ops.contains(t)) {
+            self.pos += 1;
+            let rhs = self.binary(level + 1)?;
+            lhs = match op {
+                "|" => lhs | rhs,
+                "^" => lhs ^ rhs,
+                "&" => lhs & rhs,
+                "<<" => lhs.checked_shl(rhs as u32).unwrap_or(0),
+                ">>" => lhs.checked_shr(rhs as u32).unwrap_or(0),
+                "+" => lhs.wrapping_add(rhs),
+                "-" => lhs.wrapping_sub(rhs),
+                "*" => lhs.wrapping_mul(rhs),
+                _ => lhs.checked_div(rhs).ok_or("Division by zero")?,
+            };
+        }
+        Ok(lhs)
+    }
+    fn unary(&mut self) -> Result<u16, String> {
+        match self.next() {
+            Some("-") => Ok(self.unary()?.wrapping_neg()),
+            Some("~") => Ok(!self.unary()?),
+            Some("(") => {
+                let val = self.binary(0)?;
+                self.expect(")")?;
+                Ok(val)
+            }
+            Some("[") => {
+                let addr = self.binary(0)?;
+                self.expect("]")?;
+                Ok(self.emu.ram.read(addr) as u16)
+            }
+            Some(word) => self.value(word),
+            None => Err("Expected a value at the end".into()),
+        }
+    }
+    fn value(&self, word: &str) -> Result<u16, String> {
+        let r = self.emu.registers();
+        let pair = |hi: u8, lo: u8| u16::from_le_bytes([lo, hi]);
+        Ok(match word.to_ascii_lowercase().as_str() {
+            "a" => r.a as u16,
+            "f" => r.f as u16,
+            "b" => r.b as u16,
+            "c" => r.c as u16,
+            "d" => r.d as u16,
+            "e" => r.e as u16,
+            "h" => r.h as u16,
+            "l" => r.l as u16,
+            "af" => pair(r.a, r.f),
+            "bc" => pair(r.b, r.c),
+            "de" => pair(r.d, r.e),
+            "hl" => pair(r.h, r.l),
+            "sp" => r.sp,
+            "pc" => r.pc,
+            _ => match number(word) {
+                Some(n @ -0x8000..=0xFFFF) => n as u16,
+                Some(_) => return Err(format!("{word} doesn't fit in 16 bits")),
+                None => return Err(format!("Expected a register or number, not '{word}'")),
+            },
+        })
+    }
+}
+
+pub(super) fn eval(text: &str, emu: &Emulator) -> Result<u16, String> {
+    let mut parser = Parser {
+        tokens: tokens(text)?,
+        pos: 0,
+        emu,
+    };
+    let val = parser.binary(0)?;
+    match parser.next() {
+        Some(t) => Err(format!("Unexpected '{t}'")),
+        None => Ok(val),
+    }
+}
@@ -0,0 +1,617 @@
+// differential fuzzing of Cpu against a separate, much dumber sm83 core.
+// random register and memory states get run through both and the first
+// difference gets reported
+use std::panic::{AssertUnwindSafe, catch_unwind};
+
+use super::{
+    constants::*,
+    cpu::{Cpu, Registers},
+    ram::CpuBus,
+};
+
+// instructions per random state
+const STEPS: usize = 16;
+
+struct FlatBus {
+    mem: Vec<u8>,
+}
+
+impl CpuBus for FlatBus {
+    fn read(&self, i: u16) -> u8 {
+        self.mem[i as usize]
+    }
+    fn write(&mut self, i: u16, byte: u8) {
+        self.mem[i as usize] = byte;
+    }
+}
+
+// xorshift64, good enough for making up instruction streams
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+    fn byte(&mut self) -> u8 {
+        self.next() as u8
+    }
+}
+
+// the reference core. it decodes with the usual x/y/z split of the opcode
+// and does everything as plainly as possible
+struct RefCpu {
+    regs: Registers,
+    ime: bool,
+    // ei takes effect after the next instruction
+    ei_pending: bool,
+}
+
+const ZF: u8 = 1 << 7;
+const NF: u8 = 1 << 6;
+const HF: u8 = 1 << 5;
+const CF: u8 = 1 << 4;
+
+impl RefCpu {
+    fn flag(&self, f: u8) -> bool {
+        self.regs.f & f > 0
+    }
+    fn set_flags(&mut self, z: bool, n: bool, h: bool, c: bool) {
+        self.regs.f = (z as u8 * ZF) | (n as u8 * NF) | (h as u8 * HF) | (c as u8 * CF);
+    }
+    fn imm(&mut self, bus: &FlatBus) -> u8 {
+        let val = bus.read(self.regs.pc);
+        self.regs.pc = self.regs.pc.wrapping_add(1);
+        val
+    }
+    fn imm16(&mut self, bus: &FlatBus) -> u16 {
+        let low = self.imm(bus) as u16;
+        low | ((self.imm(bus) as u16) << 8)
+    }
+    fn push(&mut self, bus: &mut FlatBus, val: u16) {
+        self.regs.sp = self.regs.sp.wrapping_sub(1);
+        bus.write(self.regs.sp, (val >> 8) as u8);
+        self.regs.sp = self.regs.sp.wrapping_sub(1);
+        bus.write(self.regs.sp, val as u8);
+    }
+    fn pop(&mut self, bus: &FlatBus) -> u16 {
+        let low = bus.read(self.regs.sp) as u16;
+        self.regs.sp = self.regs.sp.wrapping_add(1);
+        let high = bus.read(self.regs.sp) as u16;
+        self.regs.sp = self.regs.sp.wrapping_add(1);
+        low | (high << 8)
+    }
+    fn hl(&self) -> u16 {
+        ((self.regs.h as u16) << 8) | self.regs.l as u16
+    }
+    fn set_hl(&mut self, val: u16) {
+        self.regs.h = (val >> 8) as u8;
+        self.regs.l = val as u8;
+    }
+    // B C D E H L (HL) A
+    fn r(&self, bus: &FlatBus, i: u8) -> u8 {
+        match i {
+            0 => self.regs.b,
+            1 => self.regs.c,
+            2 => self.regs.d,
+            3 => self.regs.e,
+            4 => self.regs.h,
+            5 => self.regs.l,
+            6 => bus.read(self.hl()),
+            _ => self.regs.a,
+        }
+    }
+    fn set_r(&mut self, bus: &mut FlatBus, i: u8, val: u8) {
+        match i {
+            0 => self.regs.b = val,
+            1 => self.regs.c = val,
+            2 => self.regs.d = val,
+            3 => self.regs.e = val,
+            4 => self.regs.h = val,
+            5 => self.regs.l = val,
+            6 => bus.write(self.hl(), val),
+            _ => self.regs.a = val,
+        }
+    }
+    // BC DE HL SP
+    fn rp(&self, i: u8) -> u16 {
+        let r = &self.regs;
+        match i {
+            0 => ((r.b as u16) << 8) | r.c as u16,
+            1 => ((r.d as u16) << 8) | r.e as u16,
+            2 => self.hl(),
+            _ => r.sp,
+        }
+    }
+    fn set_rp(&mut self, i: u8, val: u16) {
+        let (high, low) = ((val >> 8) as u8, val as u8);
+        match i {
+            0 => (self.regs.b, self.regs.c) = (high, low),
+            1 => (self.regs.d, self.regs.e) = (high, low),
+            2 => (self.regs.h, self.regs.l) = (high, low),
+            _ => self.regs.sp = val,
+        }
+    }
+    // NZ Z NC C
+    fn cc(&self, i: u8) -> bool {
+        match i {
+            0 => !self.flag(ZF),
+            1 => self.flag(ZF),
+            2 => !self.flag(CF),
+            _ => self.flag(CF),
+        }
+    }
+    // ADD ADC SUB SBC AND XOR OR CP
+    fn alu(&mut self, i: u8, val: u8) {
+        let a = self.regs.a;
+        let carry = self.flag(CF) as u8;
+        match i {
+            0 | 1 => {
+                let c = if i == 1 { carry } else { 0 };
+                let sum = a as u16 + val as u16 + c as u16;
+                let h = (a & 0xF) + (val & 0xF) + c > 0xF;
+                self.regs.a = sum as u8;
+                self.set_flags(sum as u8 == 0, false, h, sum > 0xFF);
+            }
+            2 | 3 | 7 => {
+                let c = if i == 3 { carry } else { 0 };
+                let diff = a as i16 - val as i16 - c as i16;
+                let h = ((a & 0xF) as i16 - (val & 0xF) as i16 - c as i16) < 0;
+                if i != 7 {
+                    self.regs.a = diff as u8;
+                }
+                self.set_flags(diff as u8 == 0, true, h, diff < 0);
+            }
+            4 => {
+                self.regs.a = a & val;
+                self.set_flags(self.regs.a == 0, false, true, false);
+            }
+            5 => {
+                self.regs.a = a ^ val;
+                self.set_flags(self.regs.a == 0, false, false, false);
+            }
+            _ => {
+                self.regs.a = a | val;
+                self.set_flags(self.regs.a == 0, false, false, false);
+            }
+        }
+    }
+    // RLC RRC RL RR SLA SRA SWAP SRL
+    fn rot(&mut self, i: u8, val: u8) -> u8 {
+        let carry = self.flag(CF) as u8;
+        let (res, c) = match i {
+            0 => (val.rotate_left(1), val >> 7),
+            1 => (val.rotate_right(1), val & 1),
+            2 => ((val << 1) | carry, val >> 7),
+            3 => ((val >> 1) | (carry << 7), val & 1),
+            4 => (val << 1, val >> 7),
+            5 => ((val >> 1) | (val & 0x80), val & 1),
+            6 => (val.rotate_left(4), 0),
+            _ => (val >> 1, val & 1),
+        };
+        self.set_flags(res == 0, false, false, c == 1);
+        res
+    }
+    // sp plus a signed byte, with flags from the low byte
+    fn sp_offset(&mut self, bus: &FlatBus) -> u16 {
+        let d = self.imm(bus);
+        let sp = self.regs.sp;
+        let h = (sp & 0xF) + (d as u16 & 0xF) > 0xF;
+        let c = (sp & 0xFF) + d as u16 > 0xFF;
+        self.set_flags(false, false, h, c);
+        sp.wrapping_add(d as i8 as u16)
+    }
+    // returns m-cycles
+    fn step(&mut self, bus: &mut FlatBus) -> u8 {
+        let pending = bus.read(IF) & bus.read(IE) & 0x1F;
+        if self.ime && pending > 0 {
+            let bit = pending.trailing_zeros() as u16;
+            self.push(bus, self.regs.pc);
+            self.regs.pc = 0x40 + 8 * bit;
+            bus.write(IF, bus.read(IF) & !(1 << bit));
+            self.ime = false;
+            return 5;
+        }
+        if self.ei_pending {
+            self.ei_pending = false;
+            self.ime = true;
+        }
+        let op = self.imm(bus);
+        let (x, y, z) = (op >> 6, (op >> 3) & 7, op & 7);
+        let (p, q) = (y >> 1, y & 1);
+        let hl_cost = |r: u8, cost: u8| if r == 6 { cost } else { 0 };
+        match x {
+            0 => match z {
+                0 => match y {
+                    0 => 1,
+                    1 => {
+                        let addr = self.imm16(bus);
+                        bus.write(addr, self.regs.sp as u8);
+                        bus.write(addr.wrapping_add(1), (self.regs.sp >> 8) as u8);
+                        5
+                    }
+                    2 => unreachable!("stop isn't fuzzed"),
+                    _ => {
+                        let d = self.imm(bus) as i8;
+                        if y == 3 || self.cc(y - 4) {
+                            self.regs.pc = self.regs.pc.wrapping_add(d as u16);
+                            3
+                        } else {
+                            2
+                        }
+                    }
+                },
+                1 => {
+                    if q == 0 {
+                        let nn = self.imm16(bus);
+                        self.set_rp(p, nn);
+                        3
+                    } else {
+                        let (hl, rp) = (self.hl(), self.rp(p));
+                        let h = (hl & 0xFFF) + (rp & 0xFFF) > 0xFFF;
+                        let (sum, c) = hl.overflowing_add(rp);
+                        self.set_hl(sum);
+                        let z = self.flag(ZF);
+                        self.set_flags(z, false, h, c);
+                        2
+                    }
+                }
+                2 => {
+                    let addr = match p {
+                        0 | 1 => self.rp(p),
+                        _ => self.hl(),
+                    };
+                    if q == 0 {
+                        bus.write(addr, self.regs.a);
+                    } else {
+                        self.regs.a = bus.read(addr);
+                    }
+                    match p {
+                        2 => self.set_hl(addr.wrapping_add(1)),
+                        3 => self.set_hl(addr.wrapping_sub(1)),
+                        _ => {}
+                    }
+                    2
+                }
+                3 => {
+                    let rp = self.rp(p);
+                    self.set_rp(
+                        p,
+                        if q == 0 {
+                            rp.wrapping_add(1)
+                        } else {
+                            rp.wrapping_sub(1)
+                        },
+                    );
+                    2
+                }
+                4 | 5 => {
+                    let val = self.r(bus, y);
+                    let c = self.flag(CF);
+                    let res = if z == 4 {
+                        self.set_flags(val == 0xFF, false, val & 0xF == 0xF, c);
+                        val.wrapping_add(1)
+                    } else {
+                        self.set_flags(val == 1, true, val & 0xF == 0, c);
+                        val.wrapping_sub(1)
+                    };
+                    self.set_r(bus, y, res);
+                    1 + hl_cost(y, 2)
+                }
+                6 => {
+                    let n = self.imm(bus);
+                    self.set_r(bus, y, n);
+                    2 + hl_cost(y, 1)
+                }
+                _ => {
+                    let (a, f) = (self.regs.a, self.regs.f);
+                    match y {
+                        0..=3 => {
+                            self.regs.a = self.rot(y, a);
+                            self.regs.f &= !ZF;
+                        }
+                        4 => {
+                            let (n, h, mut c) = (f & NF > 0, f & HF > 0, f & CF > 0);
+                            let mut adj = 0;
+                            if h || (!n && a & 0xF > 9) {
+                                adj |= 0x06;
+                            }
+                            if c || (!n && a > 0x99) {
+                                adj |= 0x60;
+                                c = true;
+                            }
+                            self.regs.a = if n {
+                                a.wrapping_sub(adj)
+                            } else {
+                                a.wrapping_add(adj)
+                            };
+                            self.set_flags(self.regs.a == 0, n, false, c);
+                        }
+                        5 => {
+                            self.regs.a = !a;
+                            self.regs.f = f | NF | HF;
+                        }
+                        6 => self.regs.f = (f & ZF) | CF,
+                        _ => self.regs.f = (f & ZF) | ((f & CF) ^ CF),
+                    }
+                    1
+                }
+            },
+            1 => {
+                assert!(op != 0x76, "halt isn't fuzzed");
+                let val = self.r(bus, z);
+                self.set_r(bus, y, val);
+                1 + hl_cost(y, 1) + hl_cost(z, 1)
+            }
+            2 => {
+                let val = self.r(bus, z);
+                self.alu(y, val);
+                1 + hl_cost(z, 1)
+            }
+            _ => match z {
+                0 => match y {
+                    0..=3 => {
+                        if self.cc(y) {
+                            self.regs.pc = self.pop(bus);
+                            5
+                        } else {
+                            2
+                        }
+                    }
+                    4 => {
+                        let n = self.imm(bus);
+                        bus.write(0xFF00 | n as u16, self.regs.a);
+                        3
+                    }
+                    5 => {
+                        self.regs.sp = self.sp_offset(bus);
+                        4
+                    }
+                    6 => {
+                        let n = self.imm(bus);
+                        self.regs.a = bus.read(0xFF00 | n as u16);
+                        3
+                    }
+                    _ => {
+                        let hl = self.sp_offset(bus);
+                        self.set_hl(hl);
+                        3
+                    }
+                },
+                1 => {
+                    if q == 0 {
+                        let val = self.pop(bus);
+                        if p == 3 {
+                            self.regs.a = (val >> 8) as u8;
+                            self.regs.f = val as u8 & 0xF0;
+                        } else {
+                            self.set_rp(p, val);
+                        }
+                        3
+                    } else {
+                        match p {
+                            0 | 1 => {
+                                self.regs.pc = self.pop(bus);
+                                if p == 1 {
+                                    self.ime = true;
+                                }
+                                4
+                            }
+                            2 => {
+                                self.regs.pc = self.hl();
+                                1
+                            }
+                            _ => {
+                                self.regs.sp = self.hl();
+                                2
+                            }
+                        }
+                    }
+                }
+                2 => match y {
+                    0..=3 => {
+                        let nn = self.imm16(bus);
+                        if self.cc(y) {
+                            self.regs.pc = nn;
+                            4
+                        } else {
+                            3
+                        }
+                    }
+                    4 => {
+                        bus.write(0xFF00 | self.regs.c as u16, self.regs.a);
+                        2
+                    }
+                    5 => {
+                        let nn = self.imm16(bus);
+                        bus.write(nn, self.regs.a);
+                        4
+                    }
+                    6 => {
+                        self.regs.a = bus.read(0xFF00 | self.regs.c as u16);
+                        2
+                    }
+                    _ => {
+                        let nn = self.imm16(bus);
+                        self.regs.a = bus.read(nn);
+                        4
+                    }
+                },
+                3 => match y {
+                    0 => {
+                        self.regs.pc = self.imm16(bus);
+                        4
+                    }
+                    1 => {
+                        let cb = self.imm(bus);
+                        let (x, y, z) = (cb >> 6, (cb >> 3) & 7, cb & 7);
+                        let val = self.r(bus, z);
+                        match x {
+                            0 => {
+                                let res = self.rot(y, val);
+                                self.set_r(bus, z, res);
+                            }
+                            1 => {
+                                let c = self.flag(CF);
+                                self.set_flags(val & (1 << y) == 0, false, true, c);
+                                return 2 + hl_cost(z, 1);
+                            }
+                            2 => self.set_r(bus, z, val & !(1 << y)),
+                            _ => self.set_r(bus, z, val | (1 << y)),
+                        }
+                        2 + hl_cost(z, 2)
+                    }
+                    6 => {
+                        self.ime = false;
+                        self.ei_pending = false;
+                        1
+                    }
+                    7 => {
+                        if !self.ime {
+                            self.ei_pending = true;
+                        }
+                        1
+                    }
+                    _ => unreachable!("illegal opcodes aren't fuzzed"),
+                },
+                4 => {
+                    let nn = self.imm16(bus);
+                    if self.cc(y) {
+                        self.push(bus, self.regs.pc);
+                        self.regs.pc = nn;
+                        6
+                    } else {
+                        3
+                    }
+                }
+                5 => {
+                    if q == 0 {
+                        let val = if p == 3 {
+                            ((self.regs.a as u16) << 8) | self.regs.f as u16
+                        } else {
+                            self.rp(p)
+                        };
+                        self.push(bus, val);
+                        4
+                    } else {
+                        let nn = self.imm16(bus);
+                        self.push(bus, self.regs.pc);
+                        self.regs.pc = nn;
+                        6
+                    }
+                }
+                6 => {
+                    let n = self.imm(bus);
+                    self.alu(y, n);
+                    2
+                }
+                _ => {
+                    self.push(bus, self.regs.pc);
+                    self.regs.pc = y as u16 * 8;
+                    4
+                }
+            },
+        }
+    }
+}
+
+// opcodes left out of the streams: stop, halt, and the ones that don't exist
+fn skipped(op: u8) -> bool {
+    matches!(
+        op,
+        0x10 | 0x76 | 0xD3 | 0xDB | 0xDD | 0xE3 | 0xE4 | 0xEB | 0xEC | 0xED | 0xF4 | 0xFC | 0xFD
+    )
+}
+
+fn print_regs(label: &str, r: &Registers) {
+    println!(
+        "{label:>9}: A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X}",
+        r.a, r.f, r.b, r.c, r.d, r.e, r.h, r.l, r.sp, r.pc
+    );
+}
+
+// runs `iterations` random states through both cores. returns false and
+// prints what happened at the first difference
+pub fn fuzz_cpu(iterations: u64, seed: u64) -> bool {
+    // xorshift can't start at 0
+    let mut rng = Rng(seed | 1);
+    for iteration in 0..iterations {
+        let mut mem = vec![0; 0x10000];
+        mem.iter_mut().for_each(|b| *b = rng.byte());
+        let mut regs = Registers {
+            a: rng.byte(),
+            f: rng.byte() & 0xF0,
+            b: rng.byte(),
+            c: rng.byte(),
+            d: rng.byte(),
+            e: rng.byte(),
+            h: rng.byte(),
+            l: rng.byte(),
+            sp: rng.next() as u16,
+            pc: rng.next() as u16,
+        };
+        // sometimes start right at the edges, where pc and sp wrap around
+        if rng.byte() < 32 {
+            regs.sp = regs.sp.rotate_left(rng.byte() as u32) as u8 as i8 as u16;
+            regs.pc = regs.pc as u8 as i8 as u16;
+        }
+        let mut bus = FlatBus { mem };
+        let mut cpu = Cpu::new();
+        cpu.set_registers(&regs);
+        let mut reference = RefCpu {
+            regs,
+            ime: false,
+            ei_pending: false,
+        };
+        let mut ref_bus = FlatBus {
+            mem: bus.mem.clone(),
+        };
+        for step in 0..STEPS {
+            let pc = reference.regs.pc;
+            let op = ref_bus.read(pc);
+            if skipped(op) {
+                break;
+            }
+            let before = reference.regs;
+            let bytes: Vec<u8> = (0..3).map(|i| ref_bus.read(pc.wrapping_add(i))).collect();
+            let expected_cycles = reference.step(&mut ref_bus);
+            let result = catch_unwind(AssertUnwindSafe(|| cpu.tick(&mut bus)));
+            let diverged = match &result {
+                Ok(cycles) => {
+                    *cycles != expected_cycles
+                        || cpu.registers() != reference.regs
+                        || bus.mem != ref_bus.mem
+                }
+                Err(_) => true,
+            };
+            if !diverged {
+                continue;
+            }
+            println!("Divergence on iteration {iteration}, step {step} (seed {seed})");
+            println!(
+                "Instruction at ${pc:04x}: {:02x} {:02x} {:02x}",
+                bytes[0], bytes[1], bytes[2]
+            );
+            print_regs("before", &before);
+            print_regs("expected", &reference.regs);
+            match result {
+                Ok(cycles) => {
+                    print_regs("got", &cpu.registers());
+                    println!("Cycles: expected {expected_cycles}, got {cycles}");
+                    if let Some(addr) = (0..0x10000).find(|&i| bus.mem[i] != ref_bus.mem[i]) {
+                        println!(
+                            "Memory at ${addr:04x}: expected ${:02x}, got ${:02x}",
+                            ref_bus.mem[addr], bus.mem[addr]
+                        );
+                    }
+                }
+                Err(_) => println!("Cpu panicked"),
+            }
+            return false;
+        }
+    }
+    println!("No divergences in {iterations} iterations");
+    true
+}
@@ -0,0 +1,87 @@
+// IF and IE. the ppu, timer, serial port and joypad request interrupts here,
+// and the cpu acknowledges them when it jumps to their handlers
+use super::{
+    constants::*,
+    state::{StateReader, StateWriter},
+};
+
+// in IF bit order, which is also their priority
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Interrupt {
+    VBlank,
+    Stat,
+    Timer,
+    Serial,
+    Joypad,
+}
+
+impl Interrupt {
+    const ALL: [Interrupt; 5] = [
+        Interrupt::VBlank,
+        Interrupt::Stat,
+        Interrupt::Timer,
+        Interrupt::Serial,
+        Interrupt::Joypad,
+    ];
+    pub(super) fn from_bit(bit: u8) -> Option<Self> {
+        Self::ALL.get(bit as usize).copied()
+    }
+    fn mask(self) -> u8 {
+        1 << self as u8
+    }
+    // where the cpu jumps to handle it
+    pub(super) fn vector(self) -> u16 {
+        0x40 + self as u16 * 8
+    }
+}
+
+pub(super) struct InterruptController {
+    // IF's 5 bits
+    requested: u8,
+    // all 8 bits of IE can be written and read back, even if only 5 do
+    // anything
+    enabled: u8,
+}
+
+impl InterruptController {
+    pub(super) fn new() -> Self {
+        InterruptController {
+            requested: 0,
+            enabled: 0,
+        }
+    }
+    pub(super) fn request(&mut self, interrupt: Interrupt) {
+        self.requested |= interrupt.mask();
+    }
+    pub(super) fn acknowledge(&mut self, interrupt: Interrupt) {
+        self.requested &= !interrupt.mask();
+    }
+    // the highest priority interrupt that's both requested and enabled,
+    // whether or not the cpu has interrupts turned on
+    pub(super) fn pending(&self) -> Option<Interrupt> {
+        let pending = self.requested & self.enabled & 0b1_1111;
+        Interrupt::from_bit(pending.trailing_zeros() as u8)
+    }
+    pub(super) fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(&[self.requested, self.enabled]);
+    }
+    pub(super) fn load_state(&mut self, r: &mut StateReader) -> Option<()> {
+        self.requested = r.u8()? & 0b1_1111;
+        self.enabled = r.u8()?;
+        Some(())
+    }
+    // i is IF or IE
+    pub(super) fn read(&self, i: u16) -> u8 {
+        match i {
+            // the unused bits read as 1
+            IF => self.requested | 0b1110_0000,
+            _ => self.enabled,
+        }
+    }
+    pub(super) fn write(&mut self, i: u16, val: u8) {
+        match i {
+            IF => self.requested = val & 0b1_1111,
+            _ => self.enabled = val,
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use super::interrupts::{Interrupt, InterruptController};
+
+// in the same order as the bits of P1, d-pad first
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Button {
+    Right,
+    Left,
+    Up,
+    Down,
+    A,
+    B,
+    Select,
+    Start,
+}
+
+// the names frontends use for buttons, in the same order as Button
+pub const BUTTON_NAMES: [&str; 8] = ["right", "left", "up", "down", "a", "b", "select", "start"];
+
+impl Button {
+    pub const ALL: [Button; 8] = [
+        Button::Right,
+        Button::Left,
+        Button::Up,
+        Button::Down,
+        Button::A,
+        Button::B,
+        Button::Select,
+        Button::Start,
+    ];
+    pub fn from_name(name: &str) -> Option<Self> {
+        let i = BUTTON_NAMES
+            .iter()
+            .position(|&n| n.eq_ignore_ascii_case(name))?;
+        Some(Self::ALL[i])
+    }
+}
+
+pub struct Joypad {
+    // a bit per button, set while it's held
+    pressed: u8,
+}
+
+impl Joypad {
+    pub fn new() -> Self {
+        Joypad { pressed: 0 }
+    }
+    pub fn set(&mut self, button: Button, down: bool) {
+        if down {
+            self.pressed |= 1 << button as u8;
+        } else {
+            self.pressed &= !(1 << button as u8);
+        }
+    }
+    pub fn pressed(&self) -> u8 {
+        self.pressed
+    }
+    // updates the low nibble of P1 from the select bits. lines are 0 while
+    // pressed, and any line going low requests the joypad interrupt
+    pub(super) fn tick(&self, p1: &mut u8, interrupts: &mut InterruptController) {
+        let mut lines = 0;
+        // bit 4 low selects the d-pad, bit 5 low selects the buttons
+        if *p1 & (1 << 4) == 0 {
+            lines |= self.pressed & 0xF;
+        }
+        if *p1 & (1 << 5) == 0 {
+            lines |= self.pressed >> 4;
+        }
+        let lines = !lines & 0xF;
+        if *p1 & !lines & 0xF > 0 {
+            interrupts.request(Interrupt::Joypad);
+        }
+        *p1 = (*p1 & 0xF0) | lines;
+    }
+}
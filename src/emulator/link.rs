@@ -0,0 +1,125 @@
+// a link cable between two emulators, as a pair of serial devices sharing
+// the wire. they run a slice at a time, so a byte one end sends reaches the
+// other within LINK_SLICE cycles, which is far quicker than the 4096 a real
+// transfer takes. the end with the internal clock set drives each transfer,
+// and the other has to be waiting on the external clock to take part
+use alloc::{rc::Rc, vec::Vec};
+use core::{cell::RefCell, iter::zip};
+
+use super::{Emulator, serial::SerialDevice};
+
+const LINK_SLICE: u64 = 64;
+// 8 bits at 8192hz, after which a sender nobody took the byte from gets 0xFF
+const BYTE_CYCLES: u32 = 4096;
+
+#[derive(Default)]
+struct Wire {
+    // the byte on its way to each end
+    to: [Option<u8>; 2],
+    // and what the other end sent back
+    back: [Option<u8>; 2],
+    // cycles each end has waited since it last sent
+    waited: [u32; 2],
+}
+
+pub struct CableEnd {
+    wire: Rc<RefCell<Wire>>,
+    end: usize,
+}
+
+// the wire itself, which is part of the state of two linked game boys.
+// netplay sends it along with theirs
+#[derive(Clone)]
+pub struct Cable(Rc<RefCell<Wire>>);
+
+impl Cable {
+    pub fn state(&self) -> Vec<u8> {
+        let wire = self.0.borrow();
+        let mut state = Vec::new();
+        for end in 0..2 {
+            for byte in [wire.to[end], wire.back[end]] {
+                state.extend([byte.is_some() as u8, byte.unwrap_or(0)]);
+            }
+            state.extend(wire.waited[end].to_le_bytes());
+        }
+        state
+    }
+    // returns None if state isn't from Cable::state, leaving the wire be
+    pub fn restore(&self, state: &[u8]) -> Option<()> {
+        let byte = |pair: &[u8]| match *pair {
+            [0, _] => Some(None),
+            [1, byte] => Some(Some(byte)),
+            _ => None,
+        };
+        if state.len() != 16 {
+            return None;
+        }
+        let mut wire = Wire::default();
+        for (end, state) in state.chunks(8).enumerate() {
+            wire.to[end] = byte(&state[..2])?;
+            wire.back[end] = byte(&state[2..4])?;
+            wire.waited[end] = u32::from_le_bytes(state[4..].try_into().unwrap());
+        }
+        *self.0.borrow_mut() = wire;
+        Some(())
+    }
+}
+
+// the two ends of a new cable, one for each emulator
+pub fn link_cable() -> [CableEnd; 2] {
+    let wire = Rc::new(RefCell::new(Wire::default()));
+    [0, 1].map(|end| CableEnd {
+        wire: wire.clone(),
+        end,
+    })
+}
+
+impl CableEnd {
+    // the cable this end is on
+    pub fn cable(&self) -> Cable {
+        Cable(self.wire.clone())
+    }
+}
+
+impl SerialDevice for CableEnd {
+    fn send(&mut self, byte: u8) -> Option<u8> {
+        let mut wire = self.wire.borrow_mut();
+        wire.to[1 - self.end] = Some(byte);
+        wire.waited[self.end] = 0;
+        None
+    }
+    fn poll(&mut self) -> Option<u8> {
+        let mut wire = self.wire.borrow_mut();
+        if let Some(byte) = wire.back[self.end].take() {
+            return Some(byte);
+        }
+        // the other end never started listening, so the byte's taken back
+        if wire.waited[self.end] >= BYTE_CYCLES && wire.to[1 - self.end].take().is_some() {
+            return Some(0xFF);
+        }
+        None
+    }
+    fn receive(&mut self, sb: u8) -> Option<u8> {
+        let mut wire = self.wire.borrow_mut();
+        let byte = wire.to[self.end].take()?;
+        wire.back[1 - self.end] = Some(sb);
+        Some(byte)
+    }
+    fn tick(&mut self, t_cyc: u8) {
+        let waited = &mut self.wire.borrow_mut().waited[self.end];
+        *waited = waited.saturating_add(t_cyc as u32);
+    }
+}
+
+// runs both to the end of the current slice of cycles, stopping early on
+// the end of a frame so inputs can go in at the same point every time. each
+// gets what run_slice returned. the slices line up with the cycle count,
+// so the same inputs always send bytes across at the same points
+pub fn run_linked_slice(emus: &mut [Emulator; 2]) -> [Option<bool>; 2] {
+    let mut done = [None; 2];
+    for (emu, done) in zip(emus.iter_mut(), &mut done) {
+        let end = (emu.cycles / LINK_SLICE + 1) * LINK_SLICE;
+        *done = emu.run_slice((end - emu.cycles) as u32);
+    }
+    done
+}
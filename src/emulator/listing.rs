@@ -0,0 +1,228 @@
+// a whole rom as an rgbds .asm listing. code is found by following jumps and
+// calls from the entry points, and whatever isn't reached is left as data
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    format,
+    string::String,
+    vec,
+    vec::Vec,
+};
+use core::fmt::Write;
+
+use super::{CDL_CODE, disasm::disassemble};
+
+const BANK_SIZE: usize = 0x4000;
+// bytes per db line
+const DATA_LINE: usize = 8;
+// the shortest run of one byte that gets a ds instead
+const MIN_RUN: usize = 16;
+
+// the rst and interrupt vectors, then where the header jumps from
+const ENTRY_POINTS: [usize; 14] = [
+    0x00, 0x08, 0x10, 0x18, 0x20, 0x28, 0x30, 0x38, 0x40, 0x48, 0x50, 0x58, 0x60, 0x100,
+];
+
+// labels by bank and address, like a .sym file from rgblink or no$gmb
+#[derive(Default)]
+pub struct Symbols(BTreeMap<(usize, u16), String>);
+
+impl Symbols {
+    // lines like "01:4000 Name". comments and anything else are skipped
+    pub fn parse(text: &str) -> Self {
+        let mut symbols = BTreeMap::new();
+        for line in text.lines() {
+            let line = line.split(';').next().unwrap_or_default();
+            let mut parts = line.split_whitespace();
+            if let Some((bank, addr)) = parts.next().and_then(|p| p.split_once(':'))
+                && let Some(name) = parts.next()
+                && let Ok(bank) = usize::from_str_radix(bank, 16)
+                && let Ok(addr) = u16::from_str_radix(addr, 16)
+            {
+                symbols.insert((bank, addr), name.into());
+            }
+        }
+        Symbols(symbols)
+    }
+    // the nearest label at or before addr in the same 16kb, which for code
+    // is usually the function it's in
+    pub(super) fn containing(&self, bank: usize, addr: u16) -> Option<&str> {
+        let start = addr & 0xC000;
+        let mut labels = self.0.range((bank, start)..=(bank, addr));
+        labels.next_back().map(|(_, name)| name.as_str())
+    }
+}
+
+// where addr is in the rom, as seen from code in bank. the switchable bank
+// is only known from code that's in it
+fn rom_offset(rom: &[u8], bank: usize, addr: u16) -> Option<usize> {
+    let offset = match addr {
+        0..0x4000 => addr as usize,
+        0x4000..0x8000 if bank > 0 => bank * BANK_SIZE + addr as usize - BANK_SIZE,
+        _ => return None,
+    };
+    (offset < rom.len()).then_some(offset)
+}
+
+fn cpu_addr(offset: usize) -> u16 {
+    if offset < BANK_SIZE {
+        offset as u16
+    } else {
+        (BANK_SIZE + offset % BANK_SIZE) as u16
+    }
+}
+
+// where the instruction at addr can jump to, and whether it can carry on to
+// the next one
+fn branch(rom: &[u8], offset: usize, addr: u16) -> (Option<u16>, bool) {
+    let byte = |i: usize| rom.get(offset + i).copied().unwrap_or(0);
+    let (op, n8) = (byte(0), byte(1));
+    let n16 = u16::from_le_bytes([n8, byte(2)]);
+    let jr = addr.wrapping_add(2).wrapping_add(n8 as i8 as u16);
+    match op {
+        0xC3 => (Some(n16), false),
+        0xC2 | 0xCA | 0xD2 | 0xDA | 0xCD | 0xC4 | 0xCC | 0xD4 | 0xDC => (Some(n16), true),
+        0x18 => (Some(jr), false),
+        0x20 | 0x28 | 0x30 | 0x38 => (Some(jr), true),
+        // ret, reti, jp hl
+        0xC9 | 0xD9 | 0xE9 => (None, false),
+        _ if op & 0xC7 == 0xC7 => (Some((op & 0x38) as u16), true),
+        _ => (None, true),
+    }
+}
+
+// the disassembly of a single instruction, unless rgbasm wouldn't assemble
+// it back to the same bytes
+fn instruction(rom: &[u8], offset: usize, addr: u16) -> Option<(String, usize)> {
+    let bank = offset / BANK_SIZE;
+    let read = |a: u16| rom_offset(rom, bank, a).map_or(0, |i| rom[i]);
+    let (text, len) = disassemble(read, addr);
+    let len = len as usize;
+    let bytes = rom.get(offset..offset + len)?;
+    let crosses_bank = offset % BANK_SIZE + len > BANK_SIZE;
+    // stop is always assembled with a 0 after it, and ld to or from
+    // $ff00-$ffff would become ldh
+    let ambiguous = match bytes {
+        [0x10, n] => *n != 0,
+        [0xEA | 0xFA, _, hi] => *hi == 0xFF,
+        _ => false,
+    };
+    (!crosses_bank && !ambiguous && !text.starts_with("db")).then_some((text, len))
+}
+
+// the offsets of every instruction reachable from the entry points, and the
+// ones something jumps or calls to. code the log says ran is followed too,
+// which finds what jp hl and jump tables lead to
+fn find_code(rom: &[u8], cdl: &[u8]) -> (BTreeMap<usize, (String, usize)>, BTreeSet<usize>) {
+    let mut code = BTreeMap::new();
+    let mut targets = BTreeSet::new();
+    let mut used = vec![false; rom.len()];
+    let mut queue: Vec<usize> = ENTRY_POINTS
+        .into_iter()
+        .filter(|&i| i < rom.len())
+        .collect();
+    // each run of logged code starts with an instruction
+    let logged = |i: usize| cdl.get(i).is_some_and(|&b| b & CDL_CODE > 0);
+    queue.extend((0..rom.len()).filter(|&i| logged(i) && (i == 0 || !logged(i - 1))));
+    while let Some(mut offset) = queue.pop() {
+        let bank = offset / BANK_SIZE;
+        // until something stops it, or it runs into code already seen
+        while offset < rom.len() && !used[offset] {
+            let addr = cpu_addr(offset);
+            let Some((text, len)) = instruction(rom, offset, addr) else {
+                break;
+            };
+            if used[offset..offset + len].contains(&true) {
+                break;
+            }
+            used[offset..offset + len].fill(true);
+            code.insert(offset, (text, len));
+            let (target, carries_on) = branch(rom, offset, addr);
+            if let Some(target) = target.and_then(|t| rom_offset(rom, bank, t)) {
+                targets.insert(target);
+                queue.push(target);
+            }
+            if !carries_on && !logged(offset + len) {
+                break;
+            }
+            offset += len;
+        }
+    }
+    (code, targets)
+}
+
+// cdl is a code/data log of what's run, like --coverage writes, or empty
+pub fn rom_listing(rom: &[u8], symbols: &Symbols, cdl: &[u8]) -> String {
+    let (code, targets) = find_code(rom, cdl);
+    let label = |offset: usize| {
+        let (bank, addr) = (offset / BANK_SIZE, cpu_addr(offset));
+        match symbols.0.get(&(bank, addr)) {
+            Some(name) => Some(name.clone()),
+            None => targets
+                .contains(&offset)
+                .then(|| format!("code_{bank:03x}_{addr:04x}")),
+        }
+    };
+    let mut out = String::new();
+    let mut offset = 0;
+    while offset < rom.len() {
+        let (bank, addr) = (offset / BANK_SIZE, cpu_addr(offset));
+        if offset.is_multiple_of(BANK_SIZE) {
+            let _ = match bank {
+                0 => writeln!(out, "SECTION \"ROM Bank $000\", ROM0[$0000]"),
+                _ => writeln!(
+                    out,
+                    "\nSECTION \"ROM Bank ${bank:03x}\", ROMX[$4000], BANK[${bank:x}]"
+                ),
+            };
+        }
+        if let Some(label) = label(offset) {
+            let _ = writeln!(out, "\n{label}:");
+        }
+        if let Some((text, len)) = code.get(&offset) {
+            // jumps and calls go to the label rather than the address
+            let target = branch(rom, offset, addr).0;
+            let text = match target.and_then(|t| Some((t, label(rom_offset(rom, bank, t)?)?))) {
+                Some((t, name)) if text.contains(&format!("${t:04x}")) => {
+                    text.replace(&format!("${t:04x}"), &name)
+                }
+                _ => text.clone(),
+            };
+            let _ = writeln!(out, "    {text:<24}; ${addr:04x}");
+            offset += len;
+            continue;
+        }
+        // data up to the next bank, label or instruction
+        let data_ends = |i: usize| {
+            i >= rom.len()
+                || i.is_multiple_of(BANK_SIZE)
+                || code.contains_key(&i)
+                || label(i).is_some()
+        };
+        // long runs of the same byte, usually padding, go on one line
+        let mut end = offset + 1;
+        while !data_ends(end) && rom[end] == rom[offset] {
+            end += 1;
+        }
+        if end - offset >= MIN_RUN {
+            let (n, byte) = (end - offset, rom[offset]);
+            let _ = writeln!(
+                out,
+                "    {:<24}; ${addr:04x}",
+                format!("ds {n}, ${byte:02x}")
+            );
+            offset = end;
+            continue;
+        }
+        let mut end = offset + 1;
+        while !data_ends(end) && end - offset < DATA_LINE {
+            end += 1;
+        }
+        let bytes: Vec<String> = rom[offset..end]
+            .iter()
+            .map(|b| format!("${b:02x}"))
+            .collect();
+        let _ = writeln!(out, "    db {}  ; ${addr:04x}", bytes.join(", "));
+        offset = end;
+    }
+    out
+}
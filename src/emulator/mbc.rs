@@ -0,0 +1,514 @@
+// the mappers on the cart, which bank rom and ram into the address space
+// through registers written in the rom area. Ram keeps the rom and cart ram
+// itself, and hands them to the mbc to pick the bytes out of
+use alloc::{vec, vec::Vec};
+
+use super::rtc::{RTC_STATE_LEN, Rtc};
+
+// a write the mbc had no use for
+pub(super) enum Unhandled {
+    // what a rom only cart does with every write, and usually a bug in the game
+    Ignored,
+    // hardware that isn't emulated
+    Unimplemented,
+}
+
+pub(super) trait Mbc {
+    // the 16kb bank mapped at i, which is below 0x8000
+    fn rom_bank(&self, i: u16) -> usize;
+    fn read_rom(&self, rom: &[u8], i: u16) -> u8 {
+        rom_offset(rom, self.rom_bank(i), i).map_or(0xFF, |offset| rom[offset])
+    }
+    // writes to the rom area, which all go to the mbc's registers
+    fn write_rom_reg(&mut self, i: u16, val: u8) -> Result<(), Unhandled>;
+    // i is from 0xA000 to 0xBFFF, where ram is the whole of cart ram
+    fn read_ram(&self, ram: &[u8], i: u16) -> u8;
+    fn write_ram(&mut self, ram: &mut [u8], i: u16, val: u8) -> Result<(), Unhandled>;
+    // the bank of ram mapped in, wrapped to how much there is
+    fn ram_bank(&self, _ram: &[u8]) -> usize {
+        0
+    }
+    fn tick(&mut self, _t_cyc: u8, _rtc_speed: u32) {}
+    fn rtc(&self) -> Option<&Rtc> {
+        None
+    }
+    fn rtc_mut(&mut self) -> Option<&mut Rtc> {
+        None
+    }
+    // the registers as bytes, for saving and restoring with restore. None
+    // from restore means the state is from some other kind of mbc
+    fn state(&self) -> Vec<u8>;
+    fn restore(&mut self, state: &[u8]) -> Option<()>;
+}
+
+// where i is in the rom with bank mapped. banks past the end of the rom wrap
+// around, since the pins for them aren't connected. None with no rom loaded
+pub(super) fn rom_offset(rom: &[u8], bank: usize, i: u16) -> Option<usize> {
+    let banks = rom.len() / 0x4000;
+    if banks == 0 {
+        return None;
+    }
+    let offset = bank % banks * 0x4000 + (i & 0x3FFF) as usize;
+    (offset < rom.len()).then_some(offset)
+}
+
+fn ram_banks(ram: &[u8]) -> usize {
+    ram.len().div_ceil(0x2000).max(1)
+}
+
+// where i is in ram with bank mapped, if there is any. carts with only 2kb
+// repeat it through the whole area
+fn ram_index(ram: &[u8], bank: usize, i: u16) -> Option<usize> {
+    if ram.is_empty() {
+        return None;
+    }
+    let offset = bank * 0x2000 + (i - 0xA000) as usize;
+    Some(offset % ram.len())
+}
+
+// disabled or missing cart ram reads 0xFF, and ignores writes
+fn read_banked(ram: &[u8], enabled: bool, bank: usize, i: u16) -> u8 {
+    let index = ram_index(ram, bank, i).filter(|_| enabled);
+    index.map_or(0xFF, |i| ram[i])
+}
+
+fn write_banked(ram: &mut [u8], enabled: bool, bank: usize, i: u16, val: u8) {
+    if let Some(i) = ram_index(ram, bank, i).filter(|_| enabled) {
+        ram[i] = val;
+    }
+}
+
+// rom only, maybe with ram that's always there
+pub(super) struct NoMbc;
+
+impl Mbc for NoMbc {
+    fn rom_bank(&self, i: u16) -> usize {
+        (i >> 14) as usize
+    }
+    fn write_rom_reg(&mut self, _i: u16, _val: u8) -> Result<(), Unhandled> {
+        Err(Unhandled::Ignored)
+    }
+    fn read_ram(&self, ram: &[u8], i: u16) -> u8 {
+        read_banked(ram, true, 0, i)
+    }
+    fn write_ram(&mut self, ram: &mut [u8], i: u16, val: u8) -> Result<(), Unhandled> {
+        write_banked(ram, true, 0, i, val);
+        Ok(())
+    }
+    fn state(&self) -> Vec<u8> {
+        Vec::new()
+    }
+    fn restore(&mut self, state: &[u8]) -> Option<()> {
+        state.is_empty().then_some(())
+    }
+}
+
+pub(super) struct Mbc1 {
+    ram_enabled: bool,
+    // the low 5 bits of the rom bank
+    bank1: u8,
+    // the ram bank, and also the rom bank's upper bits unless ram banking
+    // mode is on
+    bank2: u8,
+    ram_banking: bool,
+}
+
+impl Mbc1 {
+    pub(super) fn new() -> Self {
+        Mbc1 {
+            ram_enabled: false,
+            bank1: 1,
+            bank2: 0,
+            ram_banking: false,
+        }
+    }
+}
+
+impl Mbc for Mbc1 {
+    // bank 00, or a higher one in ram banking mode on big carts
+    fn rom_bank(&self, i: u16) -> usize {
+        let upper = (self.bank2 as usize) << 5;
+        if i >= 0x4000 {
+            upper | self.bank1 as usize
+        } else if self.ram_banking {
+            upper
+        } else {
+            0
+        }
+    }
+    fn write_rom_reg(&mut self, i: u16, val: u8) -> Result<(), Unhandled> {
+        if i < 0x2000 {
+            self.ram_enabled = val & 0xF == 0xA;
+        } else if i < 0x4000 {
+            // bank 0 can't be mapped, so it means bank 1
+            self.bank1 = (val & 0b1_1111).max(1);
+        } else if i < 0x6000 {
+            self.bank2 = val & 0b11;
+        } else {
+            self.ram_banking = val & 1 > 0;
+        }
+        Ok(())
+    }
+    fn read_ram(&self, ram: &[u8], i: u16) -> u8 {
+        read_banked(ram, self.ram_enabled, self.ram_bank(ram), i)
+    }
+    fn write_ram(&mut self, ram: &mut [u8], i: u16, val: u8) -> Result<(), Unhandled> {
+        write_banked(ram, self.ram_enabled, self.ram_bank(ram), i, val);
+        Ok(())
+    }
+    fn ram_bank(&self, ram: &[u8]) -> usize {
+        if !self.ram_banking {
+            return 0;
+        }
+        self.bank2 as usize % ram_banks(ram)
+    }
+    fn state(&self) -> Vec<u8> {
+        vec![
+            self.ram_enabled as u8,
+            self.bank1,
+            self.bank2,
+            self.ram_banking as u8,
+        ]
+    }
+    fn restore(&mut self, state: &[u8]) -> Option<()> {
+        let &[ram_enabled, bank1, bank2, ram_banking] = state else {
+            return None;
+        };
+        self.ram_enabled = ram_enabled > 0;
+        self.bank1 = bank1 & 0b1_1111;
+        self.bank2 = bank2 & 0b11;
+        self.ram_banking = ram_banking > 0;
+        Some(())
+    }
+}
+
+// mbc30 is an mbc3 with a full 8 bit rom bank and 8 ram banks, for 4mb carts
+pub(super) struct Mbc3 {
+    mbc30: bool,
+    ram_enabled: bool,
+    bank1: u8,
+    // the ram bank, or 8 to 0xC for an rtc register
+    bank2: u8,
+    // on carts with a timer
+    rtc: Option<Rtc>,
+}
+
+impl Mbc3 {
+    pub(super) fn new(mbc30: bool, rtc: Option<Rtc>) -> Self {
+        Mbc3 {
+            mbc30,
+            ram_enabled: false,
+            bank1: 1,
+            bank2: 0,
+            rtc,
+        }
+    }
+    // which rtc register is mapped to 0xA000-0xBFFF, if one is
+    fn rtc_register(&self) -> Option<u8> {
+        let mapped = self.ram_enabled && self.rtc.is_some() && (0x8..=0xC).contains(&self.bank2);
+        mapped.then(|| self.bank2 - 0x8)
+    }
+}
+
+impl Mbc for Mbc3 {
+    fn rom_bank(&self, i: u16) -> usize {
+        if i < 0x4000 { 0 } else { self.bank1 as usize }
+    }
+    fn write_rom_reg(&mut self, i: u16, val: u8) -> Result<(), Unhandled> {
+        if i < 0x2000 {
+            self.ram_enabled = val & 0xF == 0xA;
+        } else if i < 0x4000 {
+            // bank 0 can't be mapped, so it means bank 1
+            let mask = if self.mbc30 { 0xFF } else { 0b111_1111 };
+            self.bank1 = (val & mask).max(1);
+        } else if i < 0x6000 {
+            let banks = if self.mbc30 { 7 } else { 3 };
+            match val {
+                _ if val <= banks => self.bank2 = val,
+                // mapping in the rtc registers
+                0x8..=0xC if self.rtc.is_some() => self.bank2 = val,
+                _ => return Err(Unhandled::Unimplemented),
+            }
+        } else {
+            // latching the rtc
+            let Some(rtc) = &mut self.rtc else {
+                return Err(Unhandled::Unimplemented);
+            };
+            rtc.latch(val);
+        }
+        Ok(())
+    }
+    fn read_ram(&self, ram: &[u8], i: u16) -> u8 {
+        if let Some(reg) = self.rtc_register() {
+            return self.rtc.as_ref().unwrap().read(reg);
+        }
+        read_banked(ram, self.ram_enabled, self.ram_bank(ram), i)
+    }
+    fn write_ram(&mut self, ram: &mut [u8], i: u16, val: u8) -> Result<(), Unhandled> {
+        if let Some(reg) = self.rtc_register() {
+            self.rtc.as_mut().unwrap().write(reg, val);
+            return Ok(());
+        }
+        write_banked(ram, self.ram_enabled, self.ram_bank(ram), i, val);
+        Ok(())
+    }
+    fn ram_bank(&self, ram: &[u8]) -> usize {
+        self.bank2 as usize % ram_banks(ram)
+    }
+    fn tick(&mut self, t_cyc: u8, rtc_speed: u32) {
+        if let Some(rtc) = &mut self.rtc {
+            rtc.tick(t_cyc, rtc_speed);
+        }
+    }
+    fn rtc(&self) -> Option<&Rtc> {
+        self.rtc.as_ref()
+    }
+    fn rtc_mut(&mut self) -> Option<&mut Rtc> {
+        self.rtc.as_mut()
+    }
+    // the rtc's on the end, if there is one
+    fn state(&self) -> Vec<u8> {
+        let mut state = vec![self.ram_enabled as u8, self.bank1, self.bank2];
+        if let Some(rtc) = &self.rtc {
+            state.extend(rtc.state());
+        }
+        state
+    }
+    fn restore(&mut self, state: &[u8]) -> Option<()> {
+        let (&[ram_enabled, bank1, bank2], rest) = state.split_first_chunk()?;
+        match &mut self.rtc {
+            Some(rtc) if rest.len() == RTC_STATE_LEN => rtc.restore(rest)?,
+            None if rest.is_empty() => {}
+            _ => return None,
+        }
+        self.ram_enabled = ram_enabled > 0;
+        self.bank1 = bank1;
+        self.bank2 = bank2;
+        Some(())
+    }
+}
+
+pub(super) struct Mbc5 {
+    ram_enabled: bool,
+    // 9 bits, and bank 0 can be mapped
+    bank1: u16,
+    bank2: u8,
+}
+
+impl Mbc5 {
+    pub(super) fn new() -> Self {
+        Mbc5 {
+            ram_enabled: false,
+            bank1: 1,
+            bank2: 0,
+        }
+    }
+}
+
+impl Mbc for Mbc5 {
+    fn rom_bank(&self, i: u16) -> usize {
+        if i < 0x4000 { 0 } else { self.bank1 as usize }
+    }
+    fn write_rom_reg(&mut self, i: u16, val: u8) -> Result<(), Unhandled> {
+        if i < 0x2000 {
+            self.ram_enabled = val & 0xF == 0xA;
+        } else if i < 0x3000 {
+            // the low 8 bits, then the 9th
+            self.bank1 = (self.bank1 & 0x100) | val as u16;
+        } else if i < 0x4000 {
+            self.bank1 = (self.bank1 & 0xFF) | ((val as u16 & 1) << 8);
+        } else if i < 0x6000 {
+            self.bank2 = val & 0xF;
+        } else {
+            return Err(Unhandled::Ignored);
+        }
+        Ok(())
+    }
+    fn read_ram(&self, ram: &[u8], i: u16) -> u8 {
+        read_banked(ram, self.ram_enabled, self.ram_bank(ram), i)
+    }
+    fn write_ram(&mut self, ram: &mut [u8], i: u16, val: u8) -> Result<(), Unhandled> {
+        write_banked(ram, self.ram_enabled, self.ram_bank(ram), i, val);
+        Ok(())
+    }
+    fn ram_bank(&self, ram: &[u8]) -> usize {
+        self.bank2 as usize % ram_banks(ram)
+    }
+    fn state(&self) -> Vec<u8> {
+        let [lo, hi] = self.bank1.to_le_bytes();
+        vec![self.ram_enabled as u8, lo, hi, self.bank2]
+    }
+    fn restore(&mut self, state: &[u8]) -> Option<()> {
+        let &[ram_enabled, lo, hi, bank2] = state else {
+            return None;
+        };
+        self.ram_enabled = ram_enabled > 0;
+        self.bank1 = u16::from_le_bytes([lo, hi & 1]);
+        self.bank2 = bank2 & 0xF;
+        Some(())
+    }
+}
+
+// unlicensed, switching the whole 32kb at once. wisdom tree takes the bank
+// from the address written to, and m161 from the first value written,
+// ignoring every write after that. neither has any ram
+pub(super) struct WholeRom {
+    m161: bool,
+    bank: u8,
+    locked: bool,
+}
+
+impl WholeRom {
+    pub(super) fn new(m161: bool) -> Self {
+        WholeRom {
+            m161,
+            bank: 0,
+            locked: false,
+        }
+    }
+}
+
+impl Mbc for WholeRom {
+    fn rom_bank(&self, i: u16) -> usize {
+        self.bank as usize * 2 + (i >> 14) as usize
+    }
+    fn write_rom_reg(&mut self, i: u16, val: u8) -> Result<(), Unhandled> {
+        if !self.m161 {
+            self.bank = i as u8;
+        } else if !self.locked {
+            self.bank = val & 0b111;
+            self.locked = true;
+        }
+        Ok(())
+    }
+    fn read_ram(&self, _ram: &[u8], _i: u16) -> u8 {
+        0xFF
+    }
+    fn write_ram(&mut self, _ram: &mut [u8], _i: u16, _val: u8) -> Result<(), Unhandled> {
+        Ok(())
+    }
+    fn state(&self) -> Vec<u8> {
+        vec![self.bank, self.locked as u8]
+    }
+    fn restore(&mut self, state: &[u8]) -> Option<()> {
+        let &[bank, locked] = state else {
+            return None;
+        };
+        self.bank = bank;
+        self.locked = locked > 0;
+        Some(())
+    }
+}
+
+// tama5 registers, written a nibble at a time
+const TAMA5_BANK_LO: u8 = 0x0;
+const TAMA5_BANK_HI: u8 = 0x1;
+const TAMA5_WRITE_LO: u8 = 0x4;
+const TAMA5_WRITE_HI: u8 = 0x5;
+// bit 0 is the ram address' 5th bit, and the rest the command
+const TAMA5_COMMAND: u8 = 0x6;
+// writing this runs the command
+const TAMA5_ADDR_LO: u8 = 0x7;
+// read only
+const TAMA5_ACTIVE: u8 = 0xA;
+const TAMA5_READ_LO: u8 = 0xC;
+const TAMA5_READ_HI: u8 = 0xD;
+
+// bandai's, used by tamagotchi 3. everything goes through a pair of
+// registers at 0xA000, with odd addresses selecting a register and even ones
+// writing its low nibble. the cart's 32 bytes of ram are only reachable
+// through commands
+pub(super) struct Tama5 {
+    bank: u8,
+    // the register selected by writing 0xA001, and what's been written to
+    // the writable ones
+    select: u8,
+    regs: [u8; 8],
+}
+
+impl Tama5 {
+    pub(super) fn new() -> Self {
+        Tama5 {
+            bank: 1,
+            select: 0,
+            regs: [0; 8],
+        }
+    }
+    fn addr(&self) -> usize {
+        let regs = self.regs;
+        ((regs[TAMA5_COMMAND as usize] as usize & 1) << 4) | regs[TAMA5_ADDR_LO as usize] as usize
+    }
+}
+
+impl Mbc for Tama5 {
+    fn rom_bank(&self, i: u16) -> usize {
+        if i < 0x4000 { 0 } else { self.bank as usize }
+    }
+    // its registers are all in the ram area
+    fn write_rom_reg(&mut self, _i: u16, _val: u8) -> Result<(), Unhandled> {
+        Err(Unhandled::Ignored)
+    }
+    fn read_ram(&self, ram: &[u8], i: u16) -> u8 {
+        if i & 1 > 0 {
+            return 0xFF;
+        }
+        // only the low nibble is driven
+        match self.select {
+            // the game waits for this before talking to the chip
+            TAMA5_ACTIVE => 0xF1,
+            TAMA5_READ_LO | TAMA5_READ_HI if self.regs[TAMA5_COMMAND as usize] >> 1 == 1 => {
+                let byte = ram[self.addr()];
+                let nibble = match self.select {
+                    TAMA5_READ_HI => byte >> 4,
+                    _ => byte,
+                };
+                nibble | 0xF0
+            }
+            _ => 0xF0,
+        }
+    }
+    fn write_ram(&mut self, ram: &mut [u8], i: u16, val: u8) -> Result<(), Unhandled> {
+        if i & 1 > 0 {
+            self.select = val & 0xF;
+            return Ok(());
+        }
+        let reg = self.select;
+        let Some(r) = self.regs.get_mut(reg as usize) else {
+            return Ok(());
+        };
+        *r = val & 0xF;
+        let regs = self.regs;
+        match reg {
+            TAMA5_BANK_LO | TAMA5_BANK_HI => {
+                self.bank = (regs[TAMA5_BANK_HI as usize] & 1) << 4 | regs[TAMA5_BANK_LO as usize];
+            }
+            TAMA5_ADDR_LO => match regs[TAMA5_COMMAND as usize] >> 1 {
+                0 => {
+                    ram[self.addr()] =
+                        regs[TAMA5_WRITE_HI as usize] << 4 | regs[TAMA5_WRITE_LO as usize];
+                }
+                // the read itself happens in read_ram
+                1 => {}
+                // the rtc and alarm
+                _ => return Err(Unhandled::Unimplemented),
+            },
+            _ => {}
+        }
+        Ok(())
+    }
+    fn state(&self) -> Vec<u8> {
+        let mut state = vec![self.bank, self.select];
+        state.extend(self.regs);
+        state
+    }
+    fn restore(&mut self, state: &[u8]) -> Option<()> {
+        let (&[bank, select], regs) = state.split_first_chunk()?;
+        let regs: [u8; 8] = regs.try_into().ok()?;
+        // masked like writes are, since addr would index past the ram with
+        // more than a nibble in a register
+        self.regs = regs.map(|r| r & 0xF);
+        self.bank = bank & 0x1F;
+        self.select = select & 0xF;
+        Some(())
+    }
+}
@@ -1,31 +1,306 @@
-use std::{
-    collections::HashSet,
-    io::{self, Read, Write, stdin, stdout},
-    process::exit,
-};
+use alloc::{borrow::Cow, boxed::Box, string::String, vec::Vec};
+use core::{fmt, iter::zip, ops::Range, time::Duration};
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write};
 
-use self::{constants::*, cpu::*, ppu::*, ram::*, timer::*};
+use self::{constants::*, cpu::*, interrupts::Interrupt, joypad::*, ppu::*, ram::*, timer::*};
 
+mod apu;
+mod asm;
+mod barcode;
+mod boot;
+mod cartridge;
+mod cheats;
 pub mod constants;
+mod coverage;
 mod cpu;
+#[cfg(feature = "std")]
+mod debugger;
+mod disasm;
+#[cfg(feature = "std")]
+mod expr;
+#[cfg(feature = "fuzz")]
+mod fuzz;
+mod interrupts;
+mod joypad;
+mod link;
+mod listing;
+mod mbc;
+mod model;
+mod power;
 mod ppu;
+mod profile;
 mod ram;
+mod rom_patch;
+mod romdb;
+mod rtc;
+mod serial;
+mod state;
 mod timer;
+mod trigger;
+mod vram;
+
+pub use apu::{MIN_SAMPLE_RATE, SAMPLE_RATE};
+pub use barcode::BarcodeBoy;
+pub use boot::BUILTIN_BOOT_ROM;
+pub use cartridge::{CartridgeHeader, MBC_NAMES, MbcKind};
+pub use cheats::Cheat;
+pub use coverage::{CDL_CODE, Coverage};
+pub use cpu::Registers;
+#[cfg(feature = "fuzz")]
+pub use fuzz::fuzz_cpu;
+pub use joypad::{BUTTON_NAMES, Button};
+pub use link::{Cable, CableEnd, link_cable, run_linked_slice};
+pub use listing::{Symbols, rom_listing};
+pub use model::{MODEL_NAMES, Model};
+pub use power::RamInit;
+pub use ppu::{Layers, Renderer};
+pub use profile::Profile;
+pub use ram::{RomError, RomWarning};
+pub use rom_patch::{PatchError, apply_patch};
+pub use romdb::RomInfo;
+pub use rtc::{RtcTime, parse_duration};
+pub use serial::{NullDevice, SerialDevice};
+pub use state::{DEFAULT_COMPRESSION, StateError, StateInfo};
+pub use trigger::{Fired, Trigger, TriggerAction};
+pub use vram::VRAM_IMAGES;
+
+// the parts of memory that can be dumped, by name
+pub const MEMORY_REGIONS: [&str; 6] = ["vram", "sram", "wram", "oam", "io", "hram"];
+
+fn region_range(name: &str) -> Option<Range<usize>> {
+    Some(match name {
+        "vram" => 0x8000..0xA000,
+        "wram" => 0xC000..0xE000,
+        "oam" => 0xFE00..0xFEA0,
+        "io" => 0xFF00..0xFF80,
+        "hram" => 0xFF80..0xFFFF,
+        _ => return None,
+    })
+}
+
+// the longest serial line that gets logged before it's cut, for binary data
+// without newlines
+const SERIAL_LINE: usize = 256;
+
+// how many of the last instructions trace remembers
+pub const TRACE_LEN: usize = 64;
+// the size of thumbnail, half the screen each way
+pub const THUMB_X: usize = SCRN_X / 2;
+pub const THUMB_Y: usize = SCRN_Y / 2;
+
+// something that stops the game for good, which run_frame pauses on
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Fault {
+    // the cpu hangs on these until it's reset
+    IllegalOpcode { pc: u16, op: u8 },
+    // a write to hardware that isn't emulated, only caught when trapping
+    UnimplementedWrite { pc: u16, addr: u16, val: u8 },
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fault::IllegalOpcode { pc, op } => write!(f, "Illegal opcode ${op:02x} at ${pc:04x}"),
+            Fault::UnimplementedWrite { pc, addr, val } => write!(
+                f,
+                "Unimplemented write of ${val:02x} to ${addr:04x} at ${pc:04x}"
+            ),
+        }
+    }
+}
+
+// an address to stop at, either in one rom bank or whatever's mapped there
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct Breakpoint {
+    pub addr: u16,
+    pub bank: Option<usize>,
+}
+
+impl Breakpoint {
+    pub fn any_bank(addr: u16) -> Self {
+        Breakpoint { addr, bank: None }
+    }
+    // bank:addr in hex, like sym files have them. only rom has banks to
+    // tell apart
+    pub fn parse_banked(s: &str) -> Option<Self> {
+        let (bank, addr) = s.split_once(':')?;
+        let bank = usize::from_str_radix(bank.trim_start_matches('$'), 16).ok()?;
+        let addr = u16::from_str_radix(addr.trim_start_matches('$'), 16).ok()?;
+        let bank = Some(bank);
+        (addr < 0x8000).then_some(Breakpoint { addr, bank })
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.bank {
+            Some(bank) => write!(f, "{bank:02x}:{:04x}", self.addr),
+            None => write!(f, "${:04x}", self.addr),
+        }
+    }
+}
+
+// counters for overlays and benchmarks
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Stats {
+    pub cycles: u64,
+    pub instructions: u64,
+    pub frames: u64,
+    // serviced so far, in IF bit order: vblank, stat, timer, serial, joypad
+    pub interrupts: [u64; 5],
+    pub rom_bank: usize,
+    pub ram_bank: usize,
+}
+
+// what a frame callback is handed at the end of every frame
+pub struct FrameInfo<'a> {
+    pub framebuffer: &'a [u8; SCRN_X * SCRN_Y * 4],
+    pub frame: u64,
+    // t-cycles run so far
+    pub cycles: u64,
+    // sample frames made during the frame, waiting in take_audio
+    pub audio_samples: usize,
+    // a bit per button held, in Button order
+    pub buttons: u8,
+}
+
+// the screen partway through a frame, to see raster effects without waiting
+// for vblank
+pub struct ScreenCapture {
+    // a shade per pixel
+    pub framebuffer: [u8; SCRN_X * SCRN_Y],
+    // pixels before this, in reading order, are from the frame being drawn
+    // and the rest are left over from the last one
+    pub drawn: usize,
+    // the line the ppu's on, which LY doesn't always match, and the dot
+    pub line: u8,
+    pub dot: u32,
+    pub cycles: u64,
+}
+
+// everything outside the rom and inputs that decides how a run goes. the
+// core never reads the host's clock or a random source, so runs with the
+// same config, rom and inputs end in the same state
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DeterminismConfig {
+    // seconds the rtc has already counted when a rom gets loaded
+    pub rtc_start: u64,
+    // rtc seconds per emulated second, with 0 freezing it
+    pub rtc_speed: u32,
+    // run_for normally times mode 3 like the scanline renderer since it's
+    // quicker, which makes skipping ahead end somewhere slightly different
+    // than running with the fifo
+    pub strict_timing: bool,
+}
+
+impl Default for DeterminismConfig {
+    fn default() -> Self {
+        DeterminismConfig {
+            rtc_start: 0,
+            rtc_speed: 1,
+            strict_timing: false,
+        }
+    }
+}
+
+// 64 bit fnv-1a, for comparing runs
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Fnv(0xCBF2_9CE4_8422_2325)
+    }
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0 ^ b as u64).wrapping_mul(0x100_0000_01B3);
+        }
+    }
+}
+
+pub type FrameCallback = Box<dyn FnMut(&FrameInfo)>;
 
 pub struct Emulator {
     cpu: Cpu,
     ppu: Ppu,
     pub ram: Ram,
     timer: Timer,
-    breakpoints: HashSet<u16>,
+    joypad: Joypad,
+    // what gets set up after loading, when there's no boot rom
+    model: Option<Model>,
+    // what wram starts with, see power.rs
+    ram_init: RamInit,
+    // gameshark codes, written at the end of every frame
+    ram_cheats: Vec<(u16, u8)>,
+    // kept sorted. there's only ever a few, so this beats hashing every tick
+    breakpoints: Vec<Breakpoint>,
     debug_mode: bool,
+    // stop at breakpoints instead of opening the terminal debugger, for
+    // frontends with a debugger of their own
+    pause_on_break: bool,
+    paused: bool,
+    // run the next instruction even if there's a breakpoint on it
+    resuming: bool,
+    // set when quitting from the debugger
+    quit: bool,
+    // each shade's rgba bytes packed into a u32
+    palette: [u32; 4],
+    rgba: [u8; SCRN_X * SCRN_Y * 4],
+    // the frame last converted, into rgba or by write_rgba
+    rgba_frame: [u8; SCRN_X * SCRN_Y],
+    rgba_stale: bool,
+    // false when write_rgba converted a frame rgba doesn't have
+    rgba_current: bool,
+    // frames run so far, counting lcd off stretches of a frame's length
+    frames: u64,
+    // t-cycles run so far
+    cycles: u64,
+    // t-cycles into the current frame
+    frame_cycles: u32,
+    fault: Option<Fault>,
+    // addresses of the last TRACE_LEN instructions, as a ring
+    trace: [Option<u16>; TRACE_LEN],
+    trace_next: usize,
+    // where each patch went and the bytes it replaced, newest last
+    patches: Vec<(u16, Vec<u8>)>,
+    // what's come out of the serial port since the last newline
+    serial_line: String,
+    // bytes sent over the serial port since take_serial, if they're kept
+    capture_serial: bool,
+    serial: Vec<u8>,
+    // what's on the other end of the link port, see serial.rs
+    serial_device: Box<dyn SerialDevice>,
+    // whether a transfer on the internal clock is waiting on the device
+    serial_sending: bool,
+    // expressions the debugger prints whenever it stops
+    #[cfg(feature = "std")]
+    displays: Vec<String>,
+    // the registers before the debugger's last step, to show what it changed
+    #[cfg(feature = "std")]
+    last_step: Option<Registers>,
+    frame_callback: Option<FrameCallback>,
+    // cycles by address while profiling, see profile.rs
+    profile: Option<Box<Profile>>,
+    // which rom bytes have run, see coverage.rs
+    coverage: Option<Box<Coverage>>,
+    // see trigger.rs
+    triggers: Vec<trigger::Armed>,
+    fired: Vec<(usize, trigger::Fired)>,
 }
 
-fn parse_addr(s: &str) -> Result<u16, std::num::ParseIntError> {
-    if let Some(s) = s.strip_prefix("$") {
-        u16::from_str_radix(s, 16)
-    } else {
-        s.parse()
+impl Default for Emulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// fb through the palette, a row at a time into pixels
+fn rgba_rows(fb: &[u8; SCRN_X * SCRN_Y], palette: &[u32; 4], pixels: &mut [u8], pitch: usize) {
+    for (row, shades) in fb.chunks_exact(SCRN_X).enumerate() {
+        let (line, _) = pixels[row * pitch..][..SCRN_X * 4].as_chunks_mut::<4>();
+        for (rgba, &shade) in zip(line, shades) {
+            *rgba = palette[shade as usize].to_ne_bytes();
+        }
     }
 }
 
@@ -36,8 +311,42 @@ impl Emulator {
             ppu: Ppu::new(),
             ram: Ram::new(),
             timer: Timer::new(),
-            breakpoints: HashSet::new(),
+            joypad: Joypad::new(),
+            model: None,
+            ram_init: RamInit::Zeroed,
+            ram_cheats: Vec::new(),
+            breakpoints: Vec::new(),
             debug_mode: false,
+            pause_on_break: false,
+            paused: false,
+            resuming: false,
+            quit: false,
+            palette: DEFAULT_PALETTE.map(u32::from_ne_bytes),
+            rgba: [0; SCRN_X * SCRN_Y * 4],
+            rgba_frame: [0; SCRN_X * SCRN_Y],
+            rgba_stale: true,
+            rgba_current: false,
+            frames: 0,
+            cycles: 0,
+            frame_cycles: 0,
+            fault: None,
+            trace: [None; TRACE_LEN],
+            trace_next: 0,
+            patches: Vec::new(),
+            serial_line: String::new(),
+            capture_serial: false,
+            serial: Vec::new(),
+            serial_device: Box::new(NullDevice),
+            serial_sending: false,
+            #[cfg(feature = "std")]
+            displays: Vec::new(),
+            #[cfg(feature = "std")]
+            last_step: None,
+            frame_callback: None,
+            profile: None,
+            coverage: None,
+            triggers: Vec::new(),
+            fired: Vec::new(),
         }
     }
     pub fn with_debug_mode(dm: bool) -> Self {
@@ -45,105 +354,609 @@ impl Emulator {
         emu.debug_mode = dm;
         emu
     }
-    pub fn debug(&mut self) {
-        self.debug_mode = true;
-        println!(
-            "OP at {:04x}: ${:02x}",
-            self.cpu.pc,
-            self.ram.read(self.cpu.pc)
-        );
-        loop {
-            let mut input = String::new();
-            stdin().read_line(&mut input).unwrap();
-            let mut input = input.split_whitespace();
-            if let Some(cmd) = input.next() {
-                match cmd {
-                    "b" => {
-                        if let Some(addr) = input.next().and_then(|s| parse_addr(s).ok()) {
-                            self.breakpoints.insert(addr);
-                            println!("Breakpoint inserted at ${:04x}", addr);
-                        }
-                    }
-                    "c" => {
-                        self.debug_mode = false;
-                        break;
-                    }
-                    "d" => {
-                        self.breakpoints.clear();
-                    }
-                    "r" => {
-                        self.cpu.print_regs();
-                    }
-                    "q" => exit(0),
-                    "x" => {
-                        let Some(s) = input.next() else {
-                            continue;
-                        };
-                        let addr = match s {
-                            // "a" => ...
-                            "sp" => self.cpu.sp,
-                            _ => match parse_addr(s).ok() {
-                                Some(addr) => addr,
-                                None => continue,
-                            },
-                        };
-                        print!("{:04x}:", addr);
-                        for i in 0..16 {
-                            print!(" {:02x}", self.ram.read(addr.wrapping_add(i)));
-                        }
-                        println!();
-                        let addr = addr.wrapping_add(16);
-                        print!("{:04x}:", addr);
-                        for i in 0..16 {
-                            print!(" {:02x}", self.ram.read(addr.wrapping_add(i)));
-                        }
-                        println!();
-                    }
-                    _ => continue,
-                }
-            } else {
-                break;
-            }
-        }
+    // which game boy to act like. only matters without a boot rom, and
+    // takes effect on the next load. None leaves it to the rom database,
+    // and a dmg for anything that isn't in there
+    pub fn set_model(&mut self, model: Option<Model>) {
+        self.model = model;
+    }
+    // start from power on and run boot_rom before the game, like
+    // BUILTIN_BOOT_ROM or a dump of the original. has to be set before loading
+    pub fn set_boot_rom(&mut self, boot_rom: &[u8; 0x100]) {
+        self.ram.set_boot_rom(boot_rom);
+        self.cpu.set_registers(&Registers::default());
+    }
+    // use mbc no matter what the header says, for carts it can't be told
+    // from. takes effect on the next load
+    pub fn set_mbc(&mut self, mbc: Option<MbcKind>) {
+        self.ram.mbc_override = mbc;
+    }
+    // rom database entries by crc32, checked before the built in ones.
+    // takes effect on the next load
+    pub fn set_rom_overrides(&mut self, overrides: &[(u32, RomInfo)]) {
+        self.ram.rom_overrides = overrides.to_vec();
+    }
+    // the crc32 of the loaded rom, and what the rom database has for it
+    pub fn rom_info(&self) -> (u32, RomInfo) {
+        (self.ram.rom_crc, self.ram.rom_info)
+    }
+    // stop with a fault on writes to hardware that isn't emulated, like
+    // unsupported mbc registers, instead of ignoring them
+    pub fn set_trap_unimplemented(&mut self, on: bool) {
+        self.ram.trap_unimplemented = on;
+    }
+    // warn when the cpu runs outside hram or touches memory oam dma has
+    // blocked while it's copying, for checking dma routines. the warnings
+    // are logged to dma, one per transfer
+    pub fn set_check_dma(&mut self, on: bool) {
+        self.ram.check_dma = on;
+    }
+    // warn when the cpu reads wram or hram that hasn't been written since
+    // power on, which is garbage on real hardware rather than 0. the
+    // warnings are logged to ram, one per byte
+    pub fn set_check_uninit(&mut self, on: bool) {
+        self.ram.set_check_uninit(on);
+    }
+    pub fn set_renderer(&mut self, renderer: Renderer) {
+        self.ppu.renderer = renderer;
+    }
+    // hides layers at the compositing stage, without changing timing
+    pub fn set_layers(&mut self, layers: Layers) {
+        self.ppu.layers = layers;
+    }
+    pub fn layers(&self) -> Layers {
+        self.ppu.layers
+    }
+    // draw one frame out of every n + 1. timing and interrupts aren't affected
+    pub fn set_frame_skip(&mut self, n: u8) {
+        self.ppu.set_frame_skip(n);
+    }
+    // don't draw the next frame, for when the frontend falls behind
+    pub fn skip_next_frame(&mut self) {
+        self.ppu.skip_next = true;
     }
     pub fn tick(&mut self) -> u8 {
-        if self.debug_mode || self.breakpoints.contains(&self.cpu.pc) {
-            self.debug();
+        let resuming = core::mem::take(&mut self.resuming);
+        // nothing but two cheap checks unless we're actually debugging
+        if self.debug_mode
+            || !self.breakpoints.is_empty() && self.breakpoint_at(self.cpu.pc).is_some()
+        {
+            // without std there's no terminal to debug in, so always pause
+            if cfg!(feature = "std") && !self.pause_on_break {
+                #[cfg(feature = "std")]
+                self.debug();
+            } else if !resuming {
+                self.paused = true;
+                return 0;
+            }
         }
         // if !self.cpu.halted {
         //     self.cpu.log(&self.ram);
         // }
+        self.ram.pc = self.cpu.pc;
+        // loops like halt would fill the whole trace otherwise
+        if self.trace[(self.trace_next + TRACE_LEN - 1) % TRACE_LEN] != Some(self.cpu.pc) {
+            self.trace[self.trace_next] = Some(self.cpu.pc);
+            self.trace_next = (self.trace_next + 1) % TRACE_LEN;
+        }
+        // the bank has to be known before the instruction can switch it
+        if self.coverage.is_some() {
+            self.coverage_step(self.cpu.pc);
+        }
+        if !self.triggers.is_empty() {
+            self.check_triggers(false);
+        }
+        let profiled = self.profile.is_some().then(|| {
+            let pc = self.cpu.pc;
+            (self.ram.code_bank(pc), pc)
+        });
+        let locked = self.cpu.locked;
         let m_cyc = self.cpu.tick(&mut self.ram);
+        if self.cpu.locked && !locked {
+            let (pc, op) = (self.cpu.pc, self.ram.read(self.cpu.pc));
+            self.fault = Some(Fault::IllegalOpcode { pc, op });
+            self.paused = true;
+        }
+        if let Some((addr, val)) = self.ram.unimplemented.take() {
+            let pc = self.ram.pc;
+            self.fault = Some(Fault::UnimplementedWrite { pc, addr, val });
+            self.paused = true;
+        }
+        if self.ram.check_dma {
+            self.ram.check_dma_access();
+        }
+        self.ram.check_uninit_read();
         let t_cyc = 4 * m_cyc;
+        self.cycles += t_cyc as u64;
+        if let Some((bank, pc)) = profiled {
+            self.profile_step(bank, pc, t_cyc);
+        }
+        self.ram.tick_dma(m_cyc);
+        self.ram.tick_rtc(t_cyc);
         let mut div = self.ram.read(DIV);
         let mut tima = self.ram.read(TIMA);
-        let mut if_ = self.ram.read(IF);
-        self.timer.tick(
-            &mut div,
-            &mut tima,
-            self.ram.read(TMA),
-            self.ram.read(TAC),
-            &mut if_,
-            t_cyc,
-        );
+        let (tma, tac) = (self.ram.read(TMA), self.ram.read(TAC));
+        let interrupts = &mut self.ram.interrupts;
+        let counter = self.timer.counter();
+        self.timer
+            .tick(&mut div, &mut tima, tma, tac, interrupts, t_cyc);
+        // the frame sequencer steps when bit 4 of DIV goes low
+        let div_fell = counter & !self.timer.counter() & 0x1000 > 0;
+        self.ram.apu.tick(&mut self.ram.mem, t_cyc, div_fell);
+        self.joypad.tick(&mut self.ram.mem[P1 as usize], interrupts);
         self.ram.write(DIV, div);
         self.ram.write(TIMA, tima);
-        self.ram.write(IF, if_);
-        self.ppu.tick(&mut self.ram, t_cyc);
-        if self.ram.read(SC) & (1 << 7) > 0 {
-            print!("{}", self.ram.read(SB) as char);
-            stdout().flush().unwrap();
-            self.ram.write(SC, self.ram.read(SC) ^ (1 << 7));
-        }
+        // cpu writes happen on the last m-cycle of an instruction, so the ppu
+        // has to catch up before mid-scanline register changes are seen
+        self.ppu.tick(&mut self.ram, t_cyc - 4);
+        self.ram.flush_ppu_writes();
+        self.ppu.tick(&mut self.ram, 4);
+        self.tick_serial(t_cyc);
         t_cyc
     }
-    pub fn frame_ready(&self) -> bool {
-        self.ppu.mode == Mode::Mode1 && self.ram.read(LY) == 153
+    // logs what SB had, then swaps in received
+    fn finish_transfer(&mut self, received: u8) {
+        let byte = self.ram.read(SB);
+        if self.capture_serial {
+            self.serial.push(byte);
+        }
+        // test roms print their results this way, so it's logged a line
+        // at a time, escaped in case it's really binary
+        if log::log_enabled!(target: "serial", log::Level::Info) {
+            if byte != b'\n' {
+                self.serial_line.extend(byte.escape_ascii().map(char::from));
+            }
+            if byte == b'\n' || self.serial_line.len() >= SERIAL_LINE {
+                let line = core::mem::take(&mut self.serial_line);
+                log::info!(target: "serial", "{line}");
+            }
+        }
+        self.ram.write(SB, received);
+        self.ram.write(SC, self.ram.read(SC) & !(1 << 7));
+        self.ram.interrupts.request(Interrupt::Serial);
+    }
+    // runs until the next frame is ready, or for a frame's worth of cycles if
+    // the lcd is off. returns whether there's a new frame
+    pub fn run_frame(&mut self) -> bool {
+        self.run_slice(u32::MAX).unwrap_or(false)
+    }
+    // runs at most max t-cycles of the current frame, so frontends can do
+    // things partway through one. returns what run_frame would if the frame
+    // ended, or None if it stopped first
+    pub fn run_slice(&mut self, max: u32) -> Option<bool> {
+        let mut cycles = 0;
+        while cycles < max && !self.quit && !self.paused {
+            let t_cyc = self.tick() as u32;
+            cycles += t_cyc;
+            self.frame_cycles += t_cyc;
+            if self.frame_ready() {
+                self.frame_cycles = 0;
+                self.end_frame();
+                return Some(true);
+            }
+            // with the lcd off there's no vblank to end the frame
+            if self.frame_cycles >= FRAME_CYCLES {
+                self.frame_cycles = 0;
+                self.end_frame();
+                return Some(false);
+            }
+        }
+        None
+    }
+    // keeps every byte sent over the serial port until take_serial
+    pub fn set_serial_capture(&mut self, on: bool) {
+        self.capture_serial = on;
+        if !on {
+            self.serial.clear();
+        }
+    }
+    pub fn take_serial(&mut self) -> Vec<u8> {
+        core::mem::take(&mut self.serial)
+    }
+    // runs time's worth of emulated time as fast as it can, to the end of
+    // the frame it lands in. nothing is drawn until it's done, apart from
+    // the rest of the frame it started in. stops early on a fault, quit or
+    // pause, and returns how much it really ran
+    pub fn run_for(&mut self, time: Duration) -> Duration {
+        let cycles = time.as_nanos() * CLOCK_HZ as u128 / 1_000_000_000;
+        let start = self.cycles;
+        let end = start.saturating_add(cycles.try_into().unwrap_or(u64::MAX));
+        self.ppu.headless = true;
+        while self.cycles < end && !self.quit && !self.paused {
+            self.run_frame();
+        }
+        self.ppu.headless = false;
+        let ran = self.cycles - start;
+        Duration::from_nanos((ran as u128 * 1_000_000_000 / CLOCK_HZ as u128) as u64)
+    }
+    fn end_frame(&mut self) {
+        if !self.triggers.is_empty() {
+            self.check_triggers(true);
+        }
+        self.frames += 1;
+        for &(addr, value) in &self.ram_cheats {
+            self.ram.write(addr, value);
+        }
+        let audio_samples = self.ram.apu.take_frame_samples();
+        // taken out for the call, since the info borrows self
+        if let Some(mut callback) = self.frame_callback.take() {
+            let (frame, cycles, buttons) = (self.frames, self.cycles, self.joypad.pressed());
+            callback(&FrameInfo {
+                framebuffer: self.framebuffer_rgba(),
+                frame,
+                cycles,
+                audio_samples,
+                buttons,
+            });
+            self.frame_callback = Some(callback);
+        }
+    }
+    // called at the end of every frame, including lcd off stretches of a
+    // frame's length
+    pub fn set_frame_callback(&mut self, callback: Option<FrameCallback>) {
+        self.frame_callback = callback;
+    }
+    // a bit per button held, in Button order
+    pub fn buttons(&self) -> u8 {
+        self.joypad.pressed()
+    }
+    pub fn frame_count(&self) -> u64 {
+        self.frames
+    }
+    pub fn stats(&self) -> Stats {
+        Stats {
+            cycles: self.cycles,
+            instructions: self.cpu.instructions,
+            frames: self.frames,
+            interrupts: self.cpu.interrupts,
+            rom_bank: self.ram.rom_bank(),
+            ram_bank: self.ram.ram_bank(),
+        }
+    }
+    pub fn set_pause_on_break(&mut self, on: bool) {
+        self.pause_on_break = on;
+    }
+    // whether run_frame is stopped, at a breakpoint or from pause
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+    pub fn resume(&mut self) {
+        self.debug_mode = false;
+        #[cfg(feature = "std")]
+        {
+            self.last_step = None;
+        }
+        self.paused = false;
+        self.resuming = true;
+        // try whatever hung the cpu again, in case it's been patched
+        self.cpu.locked = false;
+    }
+    // runs a single instruction while paused
+    pub fn step(&mut self) -> u8 {
+        self.resuming = true;
+        self.cpu.locked = false;
+        self.tick()
+    }
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+    pub fn add_breakpoint(&mut self, bp: Breakpoint) {
+        if let Err(i) = self.breakpoints.binary_search(&bp) {
+            self.breakpoints.insert(i, bp);
+        }
+    }
+    pub fn remove_breakpoint(&mut self, bp: Breakpoint) {
+        if let Ok(i) = self.breakpoints.binary_search(&bp) {
+            self.breakpoints.remove(i);
+        }
+    }
+    // the breakpoint that code at addr would stop at, with the banks as
+    // they're mapped now
+    pub fn breakpoint_at(&self, addr: u16) -> Option<Breakpoint> {
+        let start = self.breakpoints.partition_point(|bp| bp.addr < addr);
+        let bank = self.code_bank(addr);
+        // they're sorted by address, so anything past it means none matched
+        let mut here = self.breakpoints[start..].iter().copied();
+        here.find(|bp| bp.addr != addr || bp.bank.is_none_or(|b| bank == Some(b)))
+            .filter(|bp| bp.addr == addr)
+    }
+    // the rom bank the code at addr runs from, or None for the boot rom and
+    // anywhere that isn't rom
+    pub fn code_bank(&self, addr: u16) -> Option<usize> {
+        self.ram.code_bank(addr)
+    }
+    pub fn registers(&self) -> Registers {
+        self.cpu.registers()
+    }
+    // the instruction at addr in rgbds syntax, and its length in bytes
+    pub fn disassemble(&self, addr: u16) -> (String, u8) {
+        disasm::disassemble(|i| self.ram.read(i), addr)
+    }
+    // assembles an instruction and patches it in at addr. returns its length
+    pub fn assemble(&mut self, addr: u16, text: &str) -> Result<u8, String> {
+        let bytes = asm::assemble(text, addr)?;
+        self.patch(addr, &bytes);
+        Ok(bytes.len() as u8)
+    }
+    // writes bytes at addr, straight into the rom if that's where it is, and
+    // remembers what was there for undo_patch
+    pub fn patch(&mut self, addr: u16, bytes: &[u8]) {
+        let addrs = (0..bytes.len() as u16).map(|i| addr.wrapping_add(i));
+        let old = addrs.clone().map(|i| self.ram.read(i)).collect();
+        for (i, &byte) in zip(addrs, bytes) {
+            self.ram.poke(i, byte);
+        }
+        self.patches.push((addr, old));
+    }
+    // puts back what the last patch replaced, and returns where it was
+    pub fn undo_patch(&mut self) -> Option<u16> {
+        let (addr, old) = self.patches.pop()?;
+        for (i, byte) in old.into_iter().enumerate() {
+            self.ram.poke(addr.wrapping_add(i as u16), byte);
+        }
+        Some(addr)
+    }
+    // what stopped the game, once per fault
+    pub fn take_fault(&mut self) -> Option<Fault> {
+        self.fault.take()
+    }
+    // the addresses of recent instructions, oldest first
+    pub fn trace(&self) -> impl Iterator<Item = u16> + '_ {
+        let (new, old) = self.trace.split_at(self.trace_next);
+        old.iter().chain(new).flatten().copied()
+    }
+    // whether the user quit from the debugger
+    pub fn quit_requested(&self) -> bool {
+        self.quit
+    }
+    // the raw contents of one of MEMORY_REGIONS
+    pub fn memory_region(&self, name: &str) -> Option<Cow<'_, [u8]>> {
+        // cart ram is kept apart, and this is all of its banks
+        if name == "sram" {
+            return Some(Cow::Borrowed(&self.ram.cart_ram));
+        }
+        let range = region_range(name)?;
+        // so is IF
+        if range.contains(&(IF as usize)) {
+            let mut region = self.ram.mem[range.clone()].to_vec();
+            region[IF as usize - range.start] = self.ram.interrupts.read(IF);
+            return Some(Cow::Owned(region));
+        }
+        Some(Cow::Borrowed(&self.ram.mem[range]))
+    }
+    // writes the current frame as a png
+    #[cfg(feature = "std")]
+    pub fn screenshot<W: Write>(&self, w: W) -> io::Result<()> {
+        let fb = &self.ppu.fetcher.framebuffer;
+        vram::write_png(w, SCRN_X, SCRN_Y, fb, &self.palette)
+    }
+    // a png of what capture_screen got, in the current palette
+    #[cfg(feature = "std")]
+    pub fn capture_png<W: Write>(&self, capture: &ScreenCapture, w: W) -> io::Result<()> {
+        vram::write_png(w, SCRN_X, SCRN_Y, &capture.framebuffer, &self.palette)
+    }
+    pub fn capture_screen(&self) -> ScreenCapture {
+        let (line, dot) = self.ppu.position();
+        ScreenCapture {
+            framebuffer: self.ppu.fetcher.framebuffer,
+            drawn: self.ppu.drawn(),
+            line,
+            dot,
+            cycles: self.cycles,
+        }
+    }
+    // one of VRAM_IMAGES as width, height, and a shade per pixel
+    pub fn vram_image(&self, name: &str) -> Option<(usize, usize, Vec<u8>)> {
+        vram::decode(&self.ram, name)
+    }
+    // writes one of VRAM_IMAGES as a png
+    #[cfg(feature = "std")]
+    pub fn export_vram<W: Write>(&self, name: &str, w: W) -> io::Result<()> {
+        let Some((width, height, pixels)) = vram::decode(&self.ram, name) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("no vram image called {name}"),
+            ));
+        };
+        vram::write_png(w, width, height, &pixels, &self.palette)
+    }
+    // true once per frame, when the ppu enters vblank
+    pub fn frame_ready(&mut self) -> bool {
+        core::mem::take(&mut self.ppu.frame_done)
+    }
+    // the opcode the cpu runs next. test roms like mealybug tearoom's run
+    // ld b,b ($40) to say they're done
+    pub fn next_opcode(&self) -> u8 {
+        self.ram.read(self.cpu.pc)
     }
     pub fn framebuffer(&self) -> &[u8; SCRN_X * SCRN_Y] {
         &self.ppu.fetcher.framebuffer
     }
-    pub fn load<R: Read>(&mut self, input: &mut R) -> io::Result<()> {
-        self.ram.load(input)
+    // the frame at half size, a shade per pixel. each pixel is the darkest
+    // of the 4 it covers, so thin lines and text don't disappear
+    pub fn thumbnail(&self) -> Vec<u8> {
+        let fb = &self.ppu.fetcher.framebuffer;
+        let mut thumb = Vec::with_capacity(THUMB_X * THUMB_Y);
+        for y in 0..THUMB_Y {
+            for x in 0..THUMB_X {
+                let i = y * 2 * SCRN_X + x * 2;
+                let (top, bottom) = (fb[i].max(fb[i + 1]), fb[i + SCRN_X].max(fb[i + SCRN_X + 1]));
+                thumb.push(top.max(bottom));
+            }
+        }
+        thumb
+    }
+    // the whole rom, padded out to a multiple of the bank size
+    pub fn rom(&self) -> &[u8] {
+        self.ram.rom()
+    }
+    // the title from the rom's header
+    pub fn rom_title(&self) -> String {
+        self.header().map(|h| h.title).unwrap_or_default()
+    }
+    // the loaded rom's header, or None before there's a rom
+    pub fn header(&self) -> Option<CartridgeHeader> {
+        self.ram.header()
+    }
+    // whether the cart's ram is kept by a battery, and so worth saving
+    pub fn has_battery(&self) -> bool {
+        self.ram.has_battery() && !self.ram.cart_ram.is_empty()
+    }
+    // fills cart ram from a save. a save of the wrong size fills as much as
+    // they have in common
+    pub fn load_cart_ram(&mut self, data: &[u8]) {
+        let len = data.len().min(self.ram.cart_ram.len());
+        self.ram.cart_ram[..len].copy_from_slice(&data[..len]);
+    }
+    // the mbc's registers, and its rtc if there is one, for save states
+    pub fn mbc_state(&self) -> Vec<u8> {
+        self.ram.mbc.state()
+    }
+    // returns false if state is from some other kind of mbc
+    pub fn restore_mbc_state(&mut self, state: &[u8]) -> bool {
+        self.ram.mbc.restore(state).is_some()
+    }
+    pub fn set_determinism(&mut self, config: DeterminismConfig) {
+        self.ram.rtc_start = config.rtc_start;
+        self.ram.rtc_speed = config.rtc_speed;
+        self.ppu.strict_timing = config.strict_timing;
+    }
+    pub fn determinism(&self) -> DeterminismConfig {
+        DeterminismConfig {
+            rtc_start: self.ram.rtc_start,
+            rtc_speed: self.ram.rtc_speed,
+            strict_timing: self.ppu.strict_timing,
+        }
+    }
+    // how far along the rtc is when a rom gets loaded, in seconds
+    pub fn set_rtc_start(&mut self, secs: u64) {
+        self.ram.rtc_start = secs;
+    }
+    // how many seconds the rtc counts for each emulated second, with 0
+    // freezing it. it's separate from the game halting it
+    pub fn set_rtc_speed(&mut self, speed: u32) {
+        self.ram.rtc_speed = speed;
+    }
+    pub fn rtc_speed(&self) -> u32 {
+        self.ram.rtc_speed
+    }
+    // None if the cart doesn't have one
+    pub fn rtc(&self) -> Option<RtcTime> {
+        self.ram.mbc.rtc().map(|rtc| rtc.time())
+    }
+    // returns false if the cart doesn't have an rtc
+    pub fn set_rtc(&mut self, time: RtcTime) -> bool {
+        let rtc = self.ram.mbc.rtc_mut();
+        rtc.map(|rtc| rtc.set_time(time)).is_some()
+    }
+    pub fn advance_rtc(&mut self, secs: u64) -> bool {
+        let rtc = self.ram.mbc.rtc_mut();
+        rtc.map(|rtc| rtc.advance(secs)).is_some()
+    }
+    // a 64 bit fnv-1a hash of the framebuffer, for comparing runs
+    pub fn frame_hash(&self) -> u64 {
+        let mut hash = Fnv::new();
+        hash.write(&self.ppu.fetcher.framebuffer);
+        hash.0
+    }
+    // a hash of the cpu, memory, cart and where the timer and ppu are up to,
+    // for checking two runs are still in step. it misses the ppu's fifos,
+    // which catch up with the rest within a line
+    pub fn state_hash(&self) -> u64 {
+        let mut hash = Fnv::new();
+        let r = self.cpu.registers();
+        hash.write(&[r.a, r.f, r.b, r.c, r.d, r.e, r.h, r.l]);
+        hash.write(&r.sp.to_le_bytes());
+        hash.write(&r.pc.to_le_bytes());
+        hash.write(&self.cpu.status());
+        hash.write(&self.cycles.to_le_bytes());
+        hash.write(&self.ram.mem);
+        hash.write(&[self.ram.interrupts.read(IF), self.ram.interrupts.read(IE)]);
+        hash.write(&self.ram.cart_ram);
+        hash.write(&self.ram.mbc.state());
+        hash.write(&self.timer.counter().to_le_bytes());
+        let (line, dot) = self.ppu.position();
+        hash.write(&[line, self.ppu.mode_number(), self.joypad.pressed()]);
+        hash.write(&dot.to_le_bytes());
+        hash.0
+    }
+    // the colors framebuffer_rgba uses for each shade, lightest first
+    pub fn palette(&self) -> [[u8; 4]; 4] {
+        self.palette.map(u32::to_ne_bytes)
+    }
+    pub fn set_palette(&mut self, palette: [[u8; 4]; 4]) {
+        self.palette = palette.map(u32::from_ne_bytes);
+        self.rgba_stale = true;
+    }
+    // whether the frame looks different from the last time it was converted
+    pub fn frame_changed(&self) -> bool {
+        self.rgba_stale || self.ppu.fetcher.framebuffer != self.rgba_frame
+    }
+    // the frame with the palette applied, 4 bytes per pixel in rgba order
+    pub fn framebuffer_rgba(&mut self) -> &[u8; SCRN_X * SCRN_Y * 4] {
+        if self.frame_changed() || !self.rgba_current {
+            let fb = &self.ppu.fetcher.framebuffer;
+            rgba_rows(fb, &self.palette, &mut self.rgba, SCRN_X * 4);
+            self.rgba_frame = *fb;
+            self.rgba_stale = false;
+            self.rgba_current = true;
+        }
+        &self.rgba
+    }
+    // the same as framebuffer_rgba, but straight into pixels with pitch bytes
+    // from one row to the next, like a locked texture
+    pub fn write_rgba(&mut self, pixels: &mut [u8], pitch: usize) {
+        let fb = &self.ppu.fetcher.framebuffer;
+        rgba_rows(fb, &self.palette, pixels, pitch);
+        if self.frame_changed() {
+            self.rgba_frame = *fb;
+            self.rgba_stale = false;
+            self.rgba_current = false;
+        }
+    }
+    // replaces the cheats in use
+    pub fn set_cheats(&mut self, cheats: &[Cheat]) {
+        self.ram.rom_patches.clear();
+        self.ram_cheats.clear();
+        for &cheat in cheats {
+            match cheat {
+                Cheat::GameGenie {
+                    addr,
+                    value,
+                    compare,
+                } => self.ram.rom_patches.push((addr, value, compare)),
+                Cheat::GameShark { addr, value } => self.ram_cheats.push((addr, value)),
+            }
+        }
+    }
+    pub fn set_button(&mut self, button: Button, pressed: bool) {
+        self.joypad.set(button, pressed);
+    }
+    // the warnings are about a header that's wrong but still got the rom
+    // loaded
+    #[cfg(feature = "std")]
+    pub fn load<R: Read>(&mut self, input: &mut R) -> Result<Vec<RomWarning>, RomError> {
+        let result = self.ram.load(input);
+        self.boot(result)
+    }
+    // for when the rom's already in memory
+    pub fn load_bytes(&mut self, rom: &[u8]) -> Result<Vec<RomWarning>, RomError> {
+        let result = self.ram.set_rom(rom.to_vec());
+        self.boot(result)
+    }
+    // skips straight to the end of the boot rom if there isn't one to run
+    fn boot(
+        &mut self,
+        result: Result<Vec<RomWarning>, RomError>,
+    ) -> Result<Vec<RomWarning>, RomError> {
+        if result.is_ok() {
+            self.init_ram(0x8000..0xA000);
+            self.init_ram(0xC000..0xE000);
+            self.power_on();
+        }
+        result
     }
 }
@@ -0,0 +1,153 @@
+// the game boys a game can start on. without a boot rom to run, loading sets
+// up everything the way the model's own boot rom leaves it, per pan docs
+use super::{Emulator, Registers, constants::*};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Model {
+    // the first dmgs, with a slightly different boot rom
+    Dmg0,
+    Dmg,
+    // game boy pocket
+    Mgb,
+    Sgb,
+    Sgb2,
+}
+
+// the names frontends use for models, in the same order as Model
+pub const MODEL_NAMES: [&str; 5] = ["dmg0", "dmg", "mgb", "sgb", "sgb2"];
+
+impl Model {
+    pub const ALL: [Model; 5] = [Model::Dmg0, Model::Dmg, Model::Mgb, Model::Sgb, Model::Sgb2];
+    pub fn from_name(name: &str) -> Option<Self> {
+        let i = MODEL_NAMES
+            .iter()
+            .position(|&n| n.eq_ignore_ascii_case(name))?;
+        Some(Self::ALL[i])
+    }
+    // the later boot roms set h and c when the header checksum isn't 0
+    fn registers(self, checksum: u8) -> Registers {
+        let f = if checksum == 0 { 0x80 } else { 0xB0 };
+        let (a, f, b, c, e, h, l) = match self {
+            Model::Dmg0 => (0x01, 0x00, 0xFF, 0x13, 0xC1, 0x84, 0x03),
+            Model::Dmg => (0x01, f, 0x00, 0x13, 0xD8, 0x01, 0x4D),
+            Model::Mgb => (0xFF, f, 0x00, 0x13, 0xD8, 0x01, 0x4D),
+            Model::Sgb => (0x01, 0x00, 0x00, 0x14, 0x00, 0xC0, 0x60),
+            Model::Sgb2 => (0xFF, 0x00, 0x00, 0x14, 0x00, 0xC0, 0x60),
+        };
+        Registers {
+            a,
+            f,
+            b,
+            c,
+            d: 0,
+            e,
+            h,
+            l,
+            sp: 0xFFFE,
+            pc: 0x100,
+        }
+    }
+    // the sgb's isn't known, so it's left at 0
+    fn div(self) -> u8 {
+        match self {
+            Model::Dmg0 => 0x18,
+            Model::Dmg | Model::Mgb => 0xAB,
+            Model::Sgb | Model::Sgb2 => 0,
+        }
+    }
+    // only the dmg and mgb boot roms draw the logo. the sgb leaves vram empty
+    fn draws_logo(self) -> bool {
+        matches!(self, Model::Dmg0 | Model::Dmg | Model::Mgb)
+    }
+}
+
+// io registers after booting, the same on every model but for NR52. STAT
+// and LY are left to the ppu
+const IO: [(u16, u8); 36] = [
+    (P1, 0xCF),
+    (SB, 0x00),
+    (SC, 0x7E),
+    (TIMA, 0x00),
+    (TMA, 0x00),
+    (TAC, 0xF8),
+    (IF, 0xE1),
+    // sound, with the boot rom's chime finished
+    (0xFF10, 0x80),
+    (0xFF11, 0xBF),
+    (0xFF12, 0xF3),
+    (0xFF13, 0xFF),
+    (0xFF14, 0xBF),
+    (0xFF16, 0x3F),
+    (0xFF17, 0x00),
+    (0xFF18, 0xFF),
+    (0xFF19, 0xBF),
+    (0xFF1A, 0x7F),
+    (0xFF1B, 0xFF),
+    (0xFF1C, 0x9F),
+    (0xFF1D, 0xFF),
+    (0xFF1E, 0xBF),
+    (0xFF20, 0xFF),
+    (0xFF21, 0x00),
+    (0xFF22, 0x00),
+    (0xFF23, 0xBF),
+    (0xFF24, 0x77),
+    (0xFF25, 0xF3),
+    // lcd and ppu on, bg and window tiles at 0x8000, bg on
+    (LCDC, 0x91),
+    (SCY, 0x00),
+    (SCX, 0x00),
+    (LYC, 0x00),
+    (DMA, 0xFF),
+    (BGP, 0xFC),
+    (WY, 0x00),
+    (WX, 0x00),
+    (IE, 0x00),
+];
+
+// the (r) drawn after the logo
+const REGISTERED: [u8; 8] = [0x3C, 0x42, 0xB9, 0xA5, 0xB9, 0xA5, 0x42, 0x3C];
+
+// does what the boot rom would have, for a rom that's just been loaded
+pub(super) fn skip_boot(emu: &mut Emulator, model: Model) {
+    let checksum = emu.ram.read(0x14D);
+    emu.cpu.set_registers(&model.registers(checksum));
+    // written straight to memory, since writing DMA would start a transfer.
+    // IF and IE aren't kept in memory at all
+    for (addr, val) in IO {
+        match addr {
+            IF | IE => emu.ram.interrupts.write(addr, val),
+            _ => emu.ram.mem[addr as usize] = val,
+        }
+    }
+    let sgb = matches!(model, Model::Sgb | Model::Sgb2);
+    emu.ram.mem[NR52 as usize] = if sgb { 0xF0 } else { 0xF1 };
+    emu.ram.mem[BOOT as usize] = 0x01;
+    emu.timer.set_div(model.div());
+    emu.ram.mem[0x8000..0xA000].fill(0);
+    if model.draws_logo() {
+        draw_logo(emu);
+    }
+}
+
+// tiles 1-24 from the header's logo, with each nibble a row of 4 pixels
+// doubled both ways, then the (r) as tile 25 and all of it on the tile map
+fn draw_logo(emu: &mut Emulator) {
+    let double = |n: u8| (0..4).fold(0, |row, bit| (row << 2) | (((n >> (3 - bit)) & 1) * 0b11));
+    for i in 0..48 {
+        let byte = emu.ram.read(0x104 + i);
+        for (j, nibble) in [byte >> 4, byte & 0xF].into_iter().enumerate() {
+            // only the low bit plane, with the row drawn twice
+            let addr = 0x8010 + i as usize * 8 + j * 4;
+            emu.ram.mem[addr] = double(nibble);
+            emu.ram.mem[addr + 2] = double(nibble);
+        }
+    }
+    for (i, row) in REGISTERED.into_iter().enumerate() {
+        emu.ram.mem[0x8190 + i * 2] = row;
+    }
+    for i in 0..12 {
+        emu.ram.mem[0x9904 + i] = i as u8 + 1;
+        emu.ram.mem[0x9924 + i] = i as u8 + 13;
+    }
+    emu.ram.mem[0x9910] = 0x19;
+}
@@ -0,0 +1,108 @@
+// power cycles and the reset button. a hard reset is everything a power
+// cycle does, and a soft one keeps wram like the reset button on some flash
+// carts. either way the rom, what's kept on the cart and the emulator's
+// settings all stay
+use core::ops::Range;
+
+use super::{Emulator, Model, Registers, TRACE_LEN, cpu::Cpu, model, timer::Timer};
+
+// what wram and vram hold at power on. it's really whatever the cells
+// settled to, which some games seed their random numbers from. the boot rom
+// clears vram, so that only shows while one's running
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum RamInit {
+    #[default]
+    Zeroed,
+    // evenly random bytes from a seed, so the same seed plays back the same
+    Random(u64),
+    // the shape real ones power up with, from a seed
+    Pattern(u64),
+}
+
+// xorshift64, which is plenty for filling ram
+struct Rng(u64);
+
+impl Rng {
+    fn byte(&mut self) -> u8 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 as u8
+    }
+}
+
+impl Emulator {
+    // for the next load or hard reset
+    pub fn set_ram_init(&mut self, init: RamInit) {
+        self.ram_init = init;
+    }
+    pub fn hard_reset(&mut self) {
+        self.reset(false);
+    }
+    pub fn soft_reset(&mut self) {
+        self.reset(true);
+    }
+    fn reset(&mut self, keep_wram: bool) {
+        self.ram.reset(keep_wram);
+        self.init_ram(0x8000..0xA000);
+        if !keep_wram {
+            self.init_ram(0xC000..0xE000);
+        }
+        // the counters are for stats, so they keep going
+        let cpu = core::mem::replace(&mut self.cpu, Cpu::new());
+        self.cpu.instructions = cpu.instructions;
+        self.cpu.interrupts = cpu.interrupts;
+        self.ppu.reset();
+        self.timer = Timer::new();
+        self.frame_cycles = 0;
+        self.fault = None;
+        self.trace = [None; TRACE_LEN];
+        self.serial_line.clear();
+        self.serial_sending = false;
+        self.rgba_stale = true;
+        self.power_on();
+    }
+    // range is vram or wram. everything emulated so far has the dmg's ram
+    // chips as far as this goes, so there's one pattern for each
+    pub(super) fn init_ram(&mut self, range: Range<usize>) {
+        let vram = range.start == 0x8000;
+        let mem = &mut self.ram.mem[range];
+        // xorshift can't start at 0, and wram and vram get different bytes
+        let seed = |seed: u64| Rng((seed ^ vram as u64) | 1);
+        match self.ram_init {
+            RamInit::Zeroed => mem.fill(0),
+            RamInit::Random(s) => {
+                let mut rng = seed(s);
+                mem.iter_mut().for_each(|b| *b = rng.byte());
+            }
+            // vram's noise with only the odd bit set
+            RamInit::Pattern(s) if vram => {
+                let mut rng = seed(s);
+                mem.iter_mut()
+                    .for_each(|b| *b = rng.byte() & rng.byte() & rng.byte());
+            }
+            // wram's noise leaning towards set bits and clear bits in
+            // alternating runs of 256 bytes
+            RamInit::Pattern(s) => {
+                let mut rng = seed(s);
+                for (i, b) in mem.iter_mut().enumerate() {
+                    let noise = rng.byte();
+                    *b = if i & 0x100 > 0 {
+                        noise & rng.byte()
+                    } else {
+                        noise | rng.byte()
+                    };
+                }
+            }
+        }
+    }
+    // runs the boot rom from the start, or skips to where it would finish
+    pub(super) fn power_on(&mut self) {
+        if self.ram.boot_rom_mapped() {
+            self.cpu.set_registers(&Registers::default());
+        } else {
+            let model = self.model.or(self.ram.rom_info.model);
+            model::skip_boot(self, model.unwrap_or(Model::Dmg));
+        }
+    }
+}
@@ -1,15 +1,30 @@
-use std::cmp::Ordering;
+use core::cmp::Ordering;
 
-use super::{Ram, constants::*};
+use super::{
+    Ram,
+    constants::*,
+    interrupts::Interrupt,
+    state::{StateReader, StateWriter},
+};
 use FetchState::*;
 use Mode::*;
 use arrayvec::ArrayVec;
 
+#[derive(Clone, Copy)]
 struct Object {
     y: u8,
     x: u8,
     index: u8,
     flags: u8,
+    // where it is in oam, which decides between objects at the same x
+    slot: u8,
+}
+
+#[derive(Clone, Copy, Default)]
+struct ObjPixel {
+    color: u8,
+    // the attribute byte of the object this pixel came from
+    flags: u8,
 }
 
 impl Ord for Object {
@@ -17,7 +32,7 @@ impl Ord for Object {
         if self.x != other.x {
             self.x.cmp(&other.x)
         } else {
-            self.index.cmp(&other.index)
+            self.slot.cmp(&other.slot)
         }
     }
 }
@@ -30,13 +45,165 @@ impl PartialOrd for Object {
 
 impl PartialEq for Object {
     fn eq(&self, other: &Self) -> bool {
-        self.x == other.x && self.index == other.index
+        self.x == other.x && self.slot == other.slot
     }
 }
 
 impl Eq for Object {}
 
-#[derive(Debug, PartialEq, Eq)]
+impl Object {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(&[self.y, self.x, self.index, self.flags, self.slot]);
+    }
+    fn load_state(r: &mut StateReader) -> Option<Self> {
+        let [y, x, index, flags, slot] = r.bytes(5)?.try_into().ok()?;
+        Some(Object {
+            y,
+            x,
+            index,
+            flags,
+            slot,
+        })
+    }
+}
+
+// each byte with its bits spread out to the even bits, so a tile row's low
+// and high bytes interleave into 2 bits a pixel
+const SPREAD: [u16; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut bit = 0;
+        while bit < 8 {
+            table[i] |= ((i as u16 >> bit) & 1) << (bit * 2);
+            bit += 1;
+        }
+        i += 1;
+    }
+    table
+};
+
+// the colors of a whole 2bpp tile row, leftmost first
+pub(super) fn tile_row(low: u8, high: u8) -> [u8; 8] {
+    let row = SPREAD[low as usize] | (SPREAD[high as usize] << 1);
+    core::array::from_fn(|col| (row >> (14 - col * 2)) as u8 & 0b11)
+}
+
+// dots an object fetch waits on the bg fetcher before starting, see pandocs
+// mode 3 length. it's up to 5, but only for the first object in a bg tile.
+// last_tile is the tile the previous object landed in
+fn obj_wait(obj: &Object, scx: u8, last_tile: &mut Option<u8>) -> u8 {
+    if obj.x == 0 {
+        return 5;
+    }
+    let pos = obj.x as u16 + scx as u16;
+    let tile = (pos / 8) as u8;
+    let wait = if *last_tile == Some(tile) {
+        0
+    } else {
+        (7 - (pos % 8) as u8).saturating_sub(2)
+    };
+    *last_tile = Some(tile);
+    wait
+}
+
+// tile_row, backwards for objects that are x flipped
+fn obj_pixels(low: u8, high: u8, flags: u8) -> [u8; 8] {
+    let mut pixels = tile_row(low, high);
+    if flags & (1 << 5) > 0 {
+        pixels.reverse();
+    }
+    pixels
+}
+
+// address of a row of a bg/window tile, for either addressing mode
+pub(super) fn tile_addr(lcdc: u8, index: u8, row: u8) -> u16 {
+    2 * row as u16
+        + if lcdc & (1 << 4) > 0 {
+            0x8000 + index as u16 * 16
+        } else {
+            (0x9000_u16 as i16).wrapping_add(index as i8 as i16 * 16) as u16
+        }
+}
+
+// the low and high bytes of the row of obj that lands on ly
+fn obj_row(obj: &Object, ram: &Ram, lcdc: u8, ly: u8) -> (u8, u8) {
+    let tall = lcdc & (1 << 2) > 0;
+    let height = if tall { 16 } else { 8 };
+    // lcdc can change size after the oam scan, so keep the row in bounds
+    let mut row = (ly + 16 - obj.y) & (height - 1);
+    // y flip
+    if obj.flags & (1 << 6) > 0 {
+        row = height - 1 - row;
+    }
+    // in 8x16 mode the top tile is always even
+    let index = if tall { obj.index & 0xFE } else { obj.index };
+    let addr = 0x8000 + index as u16 * 16 + 2 * row as u16;
+    (ram.read(addr), ram.read(addr + 1))
+}
+
+// mixes a bg and object pixel and applies the palettes
+fn pixel_color(ram: &Ram, lcdc: u8, bg: u8, obj: ObjPixel) -> u8 {
+    // with lcdc bit 0 off the bg and window are plain white, so objects
+    // always win even if they're behind the bg
+    let bg_on = lcdc & 1 > 0;
+    let bg = if bg_on { bg } else { 0 };
+    // bit 7 of the attributes puts bg colors 1-3 over the object
+    if obj.color != 0 && (obj.flags & (1 << 7) == 0 || bg == 0) {
+        let obp = if obj.flags & (1 << 4) == 0 {
+            OBP0
+        } else {
+            OBP1
+        };
+        (ram.read(obp) >> (obj.color * 2)) & 0b11
+    } else if bg_on {
+        (ram.read(BGP) >> (bg * 2)) & 0b11
+    } else {
+        0
+    }
+}
+
+/// Which layers get drawn, for telling which one a glitch is on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Layers {
+    pub bg: bool,
+    pub window: bool,
+    pub objects: bool,
+}
+
+impl Default for Layers {
+    fn default() -> Self {
+        Layers {
+            bg: true,
+            window: true,
+            objects: true,
+        }
+    }
+}
+
+impl Layers {
+    // a hidden bg or window draws like lcdc bit 0 is off, so objects behind
+    // it still show
+    fn mask(self, lcdc: u8, window: bool, mut obj: ObjPixel) -> (u8, ObjPixel) {
+        let shown = if window { self.window } else { self.bg };
+        let lcdc = if shown { lcdc } else { lcdc & !1 };
+        if !self.objects {
+            obj.color = 0;
+        }
+        (lcdc, obj)
+    }
+}
+
+/// How the ppu turns tiles into pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Renderer {
+    /// Dot by dot through the pixel fifo, like the real thing.
+    Fifo,
+    /// A whole line at once at the start of mode 3.
+    Scanline,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum FetchState {
     GetTile,
     GetTileDataLow,
@@ -44,92 +211,304 @@ enum FetchState {
     Push,
 }
 
+impl FetchState {
+    // in order, for save states
+    const ALL: [FetchState; 4] = [GetTile, GetTileDataLow, GetTileDataHigh, Push];
+}
+
+// the steps of an object fetch, which holds up the bg fetcher and the pixel
+// output until the object is in the object fifo
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ObjFetchState {
+    // for the bg fetcher to get through the tile it's on
+    Wait,
+    GetTile,
+    GetTileDataLow,
+    GetTileDataHigh,
+}
+
+impl ObjFetchState {
+    // in order, for save states
+    const ALL: [ObjFetchState; 4] = [
+        ObjFetchState::Wait,
+        ObjFetchState::GetTile,
+        ObjFetchState::GetTileDataLow,
+        ObjFetchState::GetTileDataHigh,
+    ];
+}
+
+struct ObjFetch {
+    obj: Object,
+    state: ObjFetchState,
+    // dots left in the current state
+    dots: u8,
+}
+
 pub(super) struct Fetcher {
     pub(super) framebuffer: [u8; SCRN_X * SCRN_Y],
+    // frame skipping still runs the fifo for timing, but doesn't draw
+    skip: bool,
+    // the line being drawn
+    ly: u8,
     x: u8,
     draw_x: u8,
+    // pixels left to throw away for scx % 8
+    discard: u8,
+    // the object being fetched, if any
+    obj_fetch: Option<ObjFetch>,
+    // bg tile the last object fetch landed on
+    obj_tile: Option<u8>,
     objects: ArrayVec<Object, 10>,
     bg_fifo: ArrayVec<u8, 8>,
-    obj_fifo: ArrayVec<u8, 8>,
+    obj_fifo: ArrayVec<ObjPixel, 8>,
     state: FetchState,
     delay: u8,
     index: u8,
     tile: (u8, u8),
+    // whether ly has matched wy yet this frame, which the window needs
+    wy_hit: bool,
+    // the window's own line counter, which only counts lines it was on
+    window_line: u8,
+    // whether the fetcher's switched to the window on this line
+    in_window: bool,
 }
 
 impl Fetcher {
-    // TODO: window
-    fn tick(&mut self, ram: &Ram) {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(&self.framebuffer);
+        w.bool(self.skip);
+        w.bytes(&[self.ly, self.x, self.draw_x, self.discard]);
+        w.bool(self.obj_fetch.is_some());
+        if let Some(fetch) = &self.obj_fetch {
+            fetch.obj.save_state(w);
+            w.bytes(&[fetch.state as u8, fetch.dots]);
+        }
+        w.bool(self.obj_tile.is_some());
+        w.u8(self.obj_tile.unwrap_or(0));
+        w.u8(self.objects.len() as u8);
+        for obj in &self.objects {
+            obj.save_state(w);
+        }
+        w.u8(self.bg_fifo.len() as u8);
+        w.bytes(&self.bg_fifo);
+        w.u8(self.obj_fifo.len() as u8);
+        for pixel in &self.obj_fifo {
+            w.bytes(&[pixel.color, pixel.flags]);
+        }
+        w.u8(self.state as u8);
+        w.bytes(&[self.delay, self.index, self.tile.0, self.tile.1]);
+        w.bool(self.wy_hit);
+        w.u8(self.window_line);
+        w.bool(self.in_window);
+    }
+    fn load_state(&mut self, r: &mut StateReader) -> Option<()> {
+        r.fill(&mut self.framebuffer)?;
+        self.skip = r.bool()?;
+        [self.ly, self.x, self.draw_x, self.discard] = r.bytes(4)?.try_into().ok()?;
+        self.obj_fetch = None;
+        if r.bool()? {
+            let obj = Object::load_state(r)?;
+            let [state, dots] = r.bytes(2)?.try_into().ok()?;
+            let state = *ObjFetchState::ALL.get(state as usize)?;
+            self.obj_fetch = Some(ObjFetch { obj, state, dots });
+        }
+        let has_tile = r.bool()?;
+        self.obj_tile = Some(r.u8()?).filter(|_| has_tile);
+        self.objects.clear();
+        for _ in 0..r.u8()? {
+            self.objects.try_push(Object::load_state(r)?).ok()?;
+        }
+        self.bg_fifo.clear();
+        let len = r.u8()? as usize;
+        let pixels = r.bytes(len)?;
+        self.bg_fifo.try_extend_from_slice(pixels).ok()?;
+        self.obj_fifo.clear();
+        for _ in 0..r.u8()? {
+            let [color, flags] = r.bytes(2)?.try_into().ok()?;
+            self.obj_fifo.try_push(ObjPixel { color, flags }).ok()?;
+        }
+        self.state = *FetchState::ALL.get(r.u8()? as usize)?;
+        [self.delay, self.index, self.tile.0, self.tile.1] = r.bytes(4)?.try_into().ok()?;
+        self.wy_hit = r.bool()?;
+        self.window_line = r.u8()?;
+        self.in_window = r.bool()?;
+        Some(())
+    }
+    fn tick(&mut self, ram: &Ram, layers: Layers) {
+        if self.obj_fetch.is_some() {
+            self.tick_obj_fetch(ram);
+            return;
+        }
+        if !self.in_window && self.window_starts(ram) {
+            // the bg pixels so far get dropped and fetching starts over
+            // from the window's first tile
+            self.in_window = true;
+            self.bg_fifo.clear();
+            self.x = 0;
+            self.state = GetTile;
+            self.delay = 0;
+            // with wx under 7 the window starts off the left edge
+            self.discard = 7u8.saturating_sub(ram.read(WX));
+            return;
+        }
+        // this goes before the fetcher, so an object is caught even on the dot
+        // the fifo gets refilled and drawn from
+        if self.discard == 0 && self.start_obj_fetch(ram) {
+            return;
+        }
         if self.delay == 0 {
             self.tick_fetcher(ram);
         } else {
             self.delay -= 1;
         }
-        if self.x == 0 || self.draw_x as usize >= SCRN_X {
+        if self.draw_x as usize >= SCRN_X {
             return;
         }
-        let pixel = self.bg_fifo.pop().unwrap_or(0);
-        self.framebuffer[ram.read(LY) as usize * SCRN_X + self.draw_x as usize] = pixel;
+        let Some(pixel) = self.bg_fifo.pop() else {
+            return;
+        };
+        // the first scx % 8 pixels get shifted out without being drawn
+        if self.discard > 0 {
+            self.discard -= 1;
+            return;
+        }
+        let obj = self.obj_fifo.pop().unwrap_or_default();
+        if !self.skip {
+            let (lcdc, obj) = layers.mask(ram.read(LCDC), self.in_window, obj);
+            let color = pixel_color(ram, lcdc, pixel, obj);
+            self.framebuffer[self.ly as usize * SCRN_X + self.draw_x as usize] = color;
+        }
         self.draw_x += 1;
     }
+    // whether the window covers draw_x and the rest of the line
+    fn window_starts(&self, ram: &Ram) -> bool {
+        ram.read(LCDC) & (1 << 5) > 0 && self.wy_hit && self.draw_x + 7 >= ram.read(WX)
+    }
+    // returns true if an object starting at draw_x paused the pipeline.
+    // objects sharing an x get fetched one after the other
+    fn start_obj_fetch(&mut self, ram: &Ram) -> bool {
+        if ram.read(LCDC) & (1 << 1) == 0 {
+            return false;
+        }
+        let Some(&obj) = self.objects.last() else {
+            return false;
+        };
+        if obj.x > self.draw_x + 8 {
+            return false;
+        }
+        self.objects.pop();
+        let wait = obj_wait(&obj, ram.read(SCX), &mut self.obj_tile);
+        let (state, dots) = if wait > 0 {
+            (ObjFetchState::Wait, wait)
+        } else {
+            (ObjFetchState::GetTile, 2)
+        };
+        self.obj_fetch = Some(ObjFetch { obj, state, dots });
+        // this dot counts as the first one of the fetch
+        self.tick_obj_fetch(ram);
+        true
+    }
+    // each state but the wait takes 2 dots, and the object goes into the
+    // fifo at the end of the last
+    fn tick_obj_fetch(&mut self, ram: &Ram) {
+        let fetch = self.obj_fetch.as_mut().unwrap();
+        fetch.dots -= 1;
+        if fetch.dots > 0 {
+            return;
+        }
+        fetch.dots = 2;
+        fetch.state = match fetch.state {
+            ObjFetchState::Wait => ObjFetchState::GetTile,
+            ObjFetchState::GetTile => ObjFetchState::GetTileDataLow,
+            ObjFetchState::GetTileDataLow => ObjFetchState::GetTileDataHigh,
+            ObjFetchState::GetTileDataHigh => {
+                let obj = fetch.obj;
+                self.obj_fetch = None;
+                self.push_object(obj, ram, ram.read(LCDC));
+                return;
+            }
+        };
+    }
+    // mixes the current row of obj into the object fifo
+    fn push_object(&mut self, obj: Object, ram: &Ram, lcdc: u8) {
+        let (low, high) = obj_row(&obj, ram, lcdc, self.ly);
+        let pixels = obj_pixels(low, high, obj.flags);
+        // objects hanging off the left edge start part way through the tile
+        let start = self.draw_x + 8 - obj.x;
+        // line the fifo up with the next 8 pixels
+        while !self.obj_fifo.is_full() {
+            self.obj_fifo.insert(0, ObjPixel::default());
+        }
+        for col in start..8 {
+            let color = pixels[col as usize];
+            let slot = &mut self.obj_fifo[(7 - (col - start)) as usize];
+            // objects fetched earlier win, so only fill transparent pixels
+            if slot.color == 0 {
+                *slot = ObjPixel {
+                    color,
+                    flags: obj.flags,
+                };
+            }
+        }
+    }
     fn tick_fetcher(&mut self, ram: &Ram) {
-        let ly = ram.read(LY);
+        let ly = self.ly;
         let lcdc = ram.read(LCDC);
         let scy = ram.read(SCY);
         let scx = ram.read(SCX);
+        // the window's map is picked by bit 6 instead of 3, and it doesn't
+        // scroll
+        let (map_bit, x, y) = if self.in_window {
+            (6, self.x, self.window_line)
+        } else {
+            (3, scx.wrapping_add(self.x), ly.wrapping_add(scy))
+        };
         match self.state {
             GetTile => {
-                let base = if lcdc & (1 << 3) == 0 { 0x9800 } else { 0x9C00 };
-                let tile_x = (((scx + self.x) / 8) % 32) as u16;
-                let tile_y = ((ly.wrapping_add(scy)) / 8) as u16;
+                let high_map = lcdc & (1 << map_bit) > 0;
+                let base = if high_map { 0x9C00 } else { 0x9800 };
+                let tile_x = ((x / 8) % 32) as u16;
+                let tile_y = (y / 8) as u16;
                 self.index = ram.read(base + tile_y * 32 + tile_x);
                 self.state = GetTileDataLow;
                 self.delay = 1;
             }
             GetTileDataLow => {
-                let row = (ly.wrapping_add(scy)) % 8;
-                let addr = 2 * row as u16
-                    + if lcdc & (1 << 4) > 0 {
-                        0x8000 + self.index as u16 * 16
-                    } else {
-                        (0x9000_u16 as i16).wrapping_add(self.index as i8 as i16 * 16) as u16
-                    };
+                let addr = tile_addr(lcdc, self.index, y % 8);
                 self.tile.0 = ram.read(addr);
                 self.state = GetTileDataHigh;
                 self.delay = 1;
             }
             GetTileDataHigh => {
-                let row = (ly.wrapping_add(scy)) % 8;
-                let addr = 2 * row as u16
-                    + if lcdc & (1 << 4) > 0 {
-                        0x8000 + self.index as u16 * 16
-                    } else {
-                        (0x9000_u16 as i16).wrapping_add(self.index as i8 as i16 * 16) as u16
-                    };
+                let addr = tile_addr(lcdc, self.index, y % 8);
                 self.tile.1 = ram.read(addr + 1);
                 self.state = Push;
                 self.delay = 1;
             }
             Push => {
                 if self.bg_fifo.is_empty() {
-                    let start = if self.x == 0 { scx % 8 } else { 0 };
-                    for bit in start..8 {
-                        let pixel = ((self.tile.0 >> bit) & 1) | (((self.tile.1 >> bit) & 1) << 1);
-                        self.bg_fifo.push(pixel);
-                        self.x += 1;
-                    }
+                    // the fifo pops from the back, so the leftmost goes in last
+                    let pixels = tile_row(self.tile.0, self.tile.1);
+                    self.bg_fifo.extend(pixels.into_iter().rev());
+                    self.x += 8;
                     self.state = GetTile;
                 }
             }
         }
     }
-    fn reset(&mut self) {
+    fn reset(&mut self, ly: u8, scx: u8) {
+        self.ly = ly;
         self.x = 0;
         self.draw_x = 0;
+        self.discard = scx % 8;
+        self.obj_fetch = None;
+        self.obj_tile = None;
         self.bg_fifo.clear();
         self.obj_fifo.clear();
+        self.in_window = false;
         self.state = GetTile;
-        self.delay = 0;
+        // the first tile gets fetched twice, and the first fetch is thrown away
+        self.delay = 6;
     }
 }
 
@@ -142,20 +521,56 @@ pub(super) enum Mode {
 }
 
 pub struct Ppu {
+    pub(super) renderer: Renderer,
+    pub(super) layers: Layers,
+    // the dot mode 3 ends on with the scanline renderer
+    mode3_end: u32,
     counter: u32,
+    // the real line number, which LY doesn't always match
+    line: u8,
     pub(super) mode: Mode,
     pub(super) fetcher: Fetcher,
+    lcd_on: bool,
+    // the first line after turning the lcd on has no oam scan
+    first_line: bool,
+    // the OR of every enabled stat source, interrupts fire on its rising edge
+    stat_line: bool,
+    // draw one frame, then skip this many
+    frame_skip: u8,
+    skipped: u8,
+    // set by the frontend when it falls behind
+    pub(super) skip_next: bool,
+    // nothing gets drawn while running as fast as possible, and mode 3 is
+    // timed like the scanline renderer's since that's quicker than the fifo
+    pub(super) headless: bool,
+    // headless keeps the renderer's mode 3 timing, so skipping ahead ends
+    // in the same state as running
+    pub(super) strict_timing: bool,
+    // whether this line's mode 3 ends at mode3_end, or when the fifo's done
+    timed_line: bool,
+    // set on entering vblank, and when the lcd turns off so the blank
+    // screen still gets presented
+    pub(super) frame_done: bool,
 }
 
 impl Ppu {
     pub fn new() -> Self {
         Ppu {
+            renderer: Renderer::Fifo,
+            layers: Layers::default(),
+            mode3_end: 0,
             counter: 0,
+            line: 0,
             mode: Mode0,
             fetcher: Fetcher {
                 framebuffer: [0; SCRN_X * SCRN_Y],
+                skip: false,
+                ly: 0,
                 x: 0,
                 draw_x: 0,
+                discard: 0,
+                obj_fetch: None,
+                obj_tile: None,
                 objects: ArrayVec::new(),
                 bg_fifo: ArrayVec::new(),
                 obj_fifo: ArrayVec::new(),
@@ -163,30 +578,123 @@ impl Ppu {
                 delay: 0,
                 index: 0,
                 tile: (0, 0),
+                wy_hit: false,
+                window_line: 0,
+                in_window: false,
             },
+            lcd_on: true,
+            first_line: false,
+            stat_line: false,
+            frame_skip: 0,
+            skipped: 0,
+            skip_next: false,
+            headless: false,
+            strict_timing: false,
+            timed_line: false,
+            frame_done: false,
         }
     }
-    // TODO: implement STAT
+    // the settings aren't saved, and neither is frame_done, which is for
+    // the frontend
+    pub(super) fn save_state(&self, w: &mut StateWriter) {
+        w.u32(self.mode3_end);
+        w.u32(self.counter);
+        w.u8(self.line);
+        w.u8(match self.mode {
+            Mode0 => 0,
+            Mode1 => 1,
+            Mode2 => 2,
+            Mode3 => 3,
+        });
+        w.bool(self.lcd_on);
+        w.bool(self.first_line);
+        w.bool(self.stat_line);
+        w.u8(self.skipped);
+        w.bool(self.timed_line);
+        self.fetcher.save_state(w);
+    }
+    pub(super) fn load_state(&mut self, r: &mut StateReader) -> Option<()> {
+        self.mode3_end = r.u32()?;
+        self.counter = r.u32()?;
+        self.line = r.u8()?;
+        self.mode = match r.u8()? {
+            0 => Mode0,
+            1 => Mode1,
+            2 => Mode2,
+            3 => Mode3,
+            _ => return None,
+        };
+        self.lcd_on = r.bool()?;
+        self.first_line = r.bool()?;
+        self.stat_line = r.bool()?;
+        self.skipped = r.u8()?;
+        self.timed_line = r.bool()?;
+        self.fetcher.load_state(r)
+    }
+    // back to power on, keeping the settings
+    pub(super) fn reset(&mut self) {
+        *self = Ppu {
+            renderer: self.renderer,
+            layers: self.layers,
+            frame_skip: self.frame_skip,
+            headless: self.headless,
+            strict_timing: self.strict_timing,
+            ..Ppu::new()
+        };
+    }
     pub fn tick(&mut self, ram: &mut Ram, dots: u8) {
         const SCANLINE_DOTS: u32 = 456;
         let lcdc = ram.read(LCDC);
         if lcdc & (1 << 7) == 0 {
+            if self.lcd_on {
+                // which can damage a real dmg's screen
+                if self.mode != Mode1 {
+                    let line = self.line;
+                    log::debug!(target: "ppu", "LCD turned off outside vblank, on line {line}");
+                }
+                // everything resets and the screen goes white until it's turned back on
+                self.lcd_on = false;
+                self.mode = Mode0;
+                self.counter = 0;
+                self.line = 0;
+                self.fetcher.wy_hit = false;
+                self.fetcher.window_line = 0;
+                self.fetcher.framebuffer.fill(0);
+                self.frame_done = true;
+                self.stat_line = false;
+                ram.write(LY, 0);
+                ram.write(STAT, ram.read(STAT) & !0b11);
+            }
             return;
         }
-        let mut ly = ram.read(LY);
+        if !self.lcd_on {
+            self.lcd_on = true;
+            self.first_line = true;
+        }
         for _ in 0..dots {
             match self.mode {
                 Mode0 => {
                     self.counter += 1;
+                    // line 0 sits in mode 0 where the oam scan would be
+                    if self.first_line && self.counter == 80 {
+                        self.first_line = false;
+                        self.fetcher.objects.clear();
+                        self.start_mode3(ram);
+                    }
                     if self.counter == SCANLINE_DOTS {
                         self.counter = 0;
-                        ly += 1;
-                        if ly < 144 {
+                        self.line += 1;
+                        if self.line < 144 {
                             self.mode = Mode2;
                             self.oam_scan(ram);
                         } else {
                             self.mode = Mode1;
-                            ram.write(IF, ram.read(IF) | 1);
+                            self.fetcher.wy_hit = false;
+                            self.fetcher.window_line = 0;
+                            // skipped frames never get presented
+                            self.frame_done = !self.fetcher.skip;
+                            self.choose_skip();
+                            ram.interrupts.request(Interrupt::VBlank);
                         }
                     }
                 }
@@ -194,9 +702,9 @@ impl Ppu {
                     self.counter += 1;
                     if self.counter == SCANLINE_DOTS {
                         self.counter = 0;
-                        ly += 1;
-                        if ly > 153 {
-                            ly = 0;
+                        self.line += 1;
+                        if self.line > 153 {
+                            self.line = 0;
                             self.mode = Mode2;
                             self.oam_scan(ram);
                         }
@@ -205,81 +713,221 @@ impl Ppu {
                 Mode2 => {
                     self.counter += 1;
                     if self.counter == 80 {
-                        self.mode = Mode3;
-                        self.fetcher.reset();
-                        // self.draw_scanline(ram);
+                        self.start_mode3(ram);
                     }
                 }
                 Mode3 => {
-                    // self.counter += 1;
-                    // if self.counter == 80 + 172 {
-                    //     self.mode = Mode0;
-                    // }
+                    // mode 3 is at least 172 dots, but scx and objects make it longer
                     self.counter += 1;
-                    self.fetcher.tick(ram);
-                    if self.fetcher.x as usize >= SCRN_X {
+                    let done = if self.timed_line {
+                        self.counter == self.mode3_end
+                    } else {
+                        self.fetcher.tick(ram, self.layers);
+                        self.fetcher.draw_x as usize >= SCRN_X
+                    };
+                    if done {
                         self.mode = Mode0;
+                        if self.fetcher.in_window {
+                            self.fetcher.window_line += 1;
+                        }
                     }
                 }
             }
+            self.update_stat(ram);
+        }
+        ram.write(LY, self.ly());
+    }
+    pub(super) fn set_frame_skip(&mut self, n: u8) {
+        // frames skipped under the old amount don't count towards the new one
+        if n != self.frame_skip {
+            self.frame_skip = n;
+            self.skipped = 0;
         }
-        ram.write(LY, ly);
     }
-    // TODO: window/objects
-    // dot-accurate rendering
-    fn _draw_scanline(&mut self, ram: &Ram) {
-        let ly = ram.read(LY);
+    // decides if the next frame gets drawn
+    fn choose_skip(&mut self) {
+        // headless frames and the frontend's skips don't count towards
+        // frame_skip, and the count starts over after them
+        if self.headless || core::mem::take(&mut self.skip_next) {
+            self.skipped = 0;
+            self.fetcher.skip = true;
+            return;
+        }
+        let skip = if self.fetcher.skip {
+            self.skipped < self.frame_skip
+        } else {
+            self.skipped = 0;
+            self.frame_skip > 0
+        };
+        if skip {
+            self.skipped += 1;
+        }
+        self.fetcher.skip = skip;
+    }
+    fn start_mode3(&mut self, ram: &Ram) {
+        self.mode = Mode3;
+        if ram.read(WY) == self.line {
+            self.fetcher.wy_hit = true;
+        }
+        self.timed_line =
+            self.renderer == Renderer::Scanline || (self.headless && !self.strict_timing);
+        if self.timed_line {
+            self.draw_scanline(ram);
+        } else {
+            self.fetcher.reset(self.line, ram.read(SCX));
+        }
+    }
+    fn update_stat(&mut self, ram: &mut Ram) {
+        let lyc_match = self.ly() == ram.read(LYC);
+        let mode = self.mode_number();
+        let stat = (ram.read(STAT) & 0b0111_1000) | ((lyc_match as u8) << 2) | mode;
+        ram.write(STAT, (1 << 7) | stat);
+        let line = |enable: u8| {
+            (lyc_match && enable & (1 << 6) > 0)
+                || (mode == 2 && enable & (1 << 5) > 0)
+                || (mode == 1 && enable & (1 << 4) > 0)
+                || (mode == 0 && enable & (1 << 3) > 0)
+        };
+        // on dmg, writing to stat acts like the hblank, vblank and lyc sources
+        // are all enabled for a cycle. the oam one isn't among them
+        let quirk = (1 << 6) | (1 << 4) | (1 << 3);
+        if core::mem::take(&mut ram.stat_written) && line(quirk) && !self.stat_line {
+            ram.interrupts.request(Interrupt::Stat);
+        }
+        let line = line(stat);
+        if line && !self.stat_line {
+            ram.interrupts.request(Interrupt::Stat);
+        }
+        self.stat_line = line;
+    }
+    // the line being drawn and how many dots into it
+    pub(super) fn position(&self) -> (u8, u32) {
+        (self.line, self.counter)
+    }
+    pub(super) fn mode_number(&self) -> u8 {
+        match self.mode {
+            Mode0 => 0,
+            Mode1 => 1,
+            Mode2 => 2,
+            Mode3 => 3,
+        }
+    }
+    // how many pixels of the framebuffer, in reading order, are from the
+    // frame being drawn. the rest are left over from the last one
+    pub(super) fn drawn(&self) -> usize {
+        let line = self.line as usize * SCRN_X;
+        match self.mode {
+            // the screen's blank while the lcd's off
+            _ if !self.lcd_on => SCRN_X * SCRN_Y,
+            Mode1 => SCRN_X * SCRN_Y,
+            _ if self.fetcher.skip => 0,
+            Mode2 => line,
+            // the scanline renderer draws the whole line as mode 3 starts
+            Mode3 if self.timed_line => line + SCRN_X,
+            Mode3 => line + self.fetcher.draw_x as usize,
+            // waiting for mode 3 on the first line after the lcd turns on
+            Mode0 if self.first_line => line,
+            Mode0 => line + SCRN_X,
+        }
+    }
+    // LY only reads 153 for the first few dots of line 153, then reads 0
+    // for the rest of it
+    fn ly(&self) -> u8 {
+        if self.line == 153 && self.counter >= 4 {
+            0
+        } else {
+            self.line
+        }
+    }
+    // draws the whole line at once at the start of mode 3. much faster than
+    // the fifo, but mid-scanline register writes don't show up
+    fn draw_scanline(&mut self, ram: &Ram) {
+        let ly = self.line;
         let lcdc = ram.read(LCDC);
         let scy = ram.read(SCY);
         let scx = ram.read(SCX);
-        let tile_row = (ly.wrapping_add(scy) / 8) as u16;
-        let mut tile_col = (scx / 8) as u16;
+        let wx = ram.read(WX);
+        let window = lcdc & (1 << 5) > 0 && self.fetcher.wy_hit && wx < SCRN_X as u8 + 7;
+        // the same length as the fifo takes, with scx, the window and
+        // objects
+        self.mode3_end = self.counter + 172 + (scx % 8) as u32 + if window { 6 } else { 0 };
+        if lcdc & (1 << 1) > 0 {
+            let mut last_tile = None;
+            // ones past the right edge never get fetched
+            let fetched = self.fetcher.objects.iter().rev().filter(|o| o.x < 168);
+            for obj in fetched {
+                self.mode3_end += 6 + obj_wait(obj, scx, &mut last_tile) as u32;
+            }
+        }
+        let window_line = self.fetcher.window_line;
+        if window {
+            self.fetcher.window_line += 1;
+        }
+        if self.fetcher.skip {
+            return;
+        }
+        let y = ly.wrapping_add(scy);
         let base = if lcdc & (1 << 3) == 0 { 0x9800 } else { 0x9C00 };
-        let row = (ly.wrapping_add(scy)) % 8;
-        let method8000 = lcdc & (1 << 4) > 0;
-        let mut next_tile = || {
-            let index = ram.read(base + tile_row * 32 + tile_col);
-            tile_col += 1;
-            tile_col %= 32;
-            let addr = 2 * row as u16
-                + if method8000 {
-                    0x8000 + index as u16 * 16
-                } else {
-                    (0x9000_u16 as i16).wrapping_add(index as i8 as i16 * 16) as u16
-                };
-            (ram.read(addr), ram.read(addr + 1))
-        };
-        let mut x = 0;
-        let mut draw_tile = |bit_range: std::ops::Range<u8>| {
-            let tile = next_tile();
-            for bit in bit_range.rev() {
-                let color = ((tile.0 >> bit) & 1) | (((tile.1 >> bit) & 1) << 1);
-                self.fetcher.framebuffer[ly as usize * SCRN_X + x] = color;
-                x += 1;
+        // one extra tile for when scx isn't a multiple of 8
+        let mut bg = [0; SCRN_X + 8];
+        for (i, tile) in bg.chunks_exact_mut(8).enumerate() {
+            let tile_x = (scx / 8) as u16 + i as u16;
+            let index = ram.read(base + (y / 8) as u16 * 32 + tile_x % 32);
+            let addr = tile_addr(lcdc, index, y % 8);
+            tile.copy_from_slice(&tile_row(ram.read(addr), ram.read(addr + 1)));
+        }
+        let mut bg: [u8; SCRN_X] = bg[(scx % 8) as usize..][..SCRN_X].try_into().unwrap();
+        let start = (wx as usize).saturating_sub(7);
+        if window {
+            let base = if lcdc & (1 << 6) == 0 { 0x9800 } else { 0x9C00 };
+            // with wx under 7 the window starts off the left edge
+            for (x, pixel) in bg.iter_mut().enumerate().skip(start) {
+                let win_x = x + 7 - wx as usize;
+                let tile_x = (win_x / 8) as u16;
+                let index = ram.read(base + (window_line / 8) as u16 * 32 + tile_x % 32);
+                let addr = tile_addr(lcdc, index, window_line % 8);
+                *pixel = tile_row(ram.read(addr), ram.read(addr + 1))[win_x % 8];
             }
-        };
-        // do first tile
-        draw_tile((scx % 8)..8);
-        // do every tile in-between
-        for _ in 1..20 {
-            draw_tile(0..8);
         }
-        // do last tile
-        draw_tile(0..(scx % 8));
+        let mut objs = [ObjPixel::default(); SCRN_X];
+        if lcdc & (1 << 1) > 0 {
+            // highest priority first, so later objects only fill the gaps
+            for obj in self.fetcher.objects.iter().rev() {
+                let (low, high) = obj_row(obj, ram, lcdc, ly);
+                let pixels = obj_pixels(low, high, obj.flags);
+                for (col, color) in pixels.into_iter().enumerate() {
+                    let x = obj.x as usize + col;
+                    if !(8..SCRN_X + 8).contains(&x) || objs[x - 8].color != 0 {
+                        continue;
+                    }
+                    objs[x - 8] = ObjPixel {
+                        color,
+                        flags: obj.flags,
+                    };
+                }
+            }
+        }
+        let row = &mut self.fetcher.framebuffer[ly as usize * SCRN_X..][..SCRN_X];
+        for (x, pixel) in row.iter_mut().enumerate() {
+            let (lcdc, obj) = self.layers.mask(lcdc, window && x >= start, objs[x]);
+            *pixel = pixel_color(ram, lcdc, bg[x], obj);
+        }
     }
     fn oam_scan(&mut self, ram: &Ram) {
         self.fetcher.objects.clear();
-        let ly = ram.read(LY);
+        let ly = self.line;
         let lcdc = ram.read(LCDC);
         let obj_height = if lcdc & (1 << 2) > 0 { 16 } else { 8 };
-        for i in (0xFE00..0xFEA0).step_by(4) {
+        for (slot, i) in (0xFE00..0xFEA0).step_by(4).enumerate() {
             let y = ram.read(i);
-            if (y..(y + obj_height)).contains(&ly) {
+            // object y is offset by 16 so objects can be partially above the screen
+            if (y as u16..y as u16 + obj_height).contains(&(ly as u16 + 16)) {
                 self.fetcher.objects.push(Object {
                     y,
                     x: ram.read(i + 1),
                     index: ram.read(i + 2),
                     flags: ram.read(i + 3),
+                    slot: slot as u8,
                 });
                 if self.fetcher.objects.is_full() {
                     break;
@@ -0,0 +1,146 @@
+// where the game spends its time, for homebrew. with it on, each step's
+// t-cycles go to the instruction it started at, by rom bank. halts count
+// against the halt, so waiting for vblank shows up as its own line
+use alloc::{boxed::Box, collections::BTreeMap, format, string::String, vec, vec::Vec};
+use core::{cmp::Reverse, fmt::Write};
+
+use super::{Emulator, listing::Symbols};
+
+// the most addresses the flat profile lists
+const TOP_ADDRESSES: usize = 40;
+
+#[derive(Default)]
+pub struct Profile {
+    // t-cycles for each address in a 16kb bank, for the banks that have run
+    banks: Vec<Option<Box<[u64]>>>,
+    // code run from anywhere but the rom, like an oam dma routine in hram,
+    // by address. the boot rom's counted here too
+    other: Option<Box<[u64]>>,
+    total: u64,
+}
+
+impl Profile {
+    fn add(&mut self, bank: Option<usize>, pc: u16, t_cyc: u8) {
+        let (counts, len, i) = match bank {
+            Some(bank) => {
+                if self.banks.len() <= bank {
+                    self.banks.resize_with(bank + 1, || None);
+                }
+                (&mut self.banks[bank], 0x4000, pc as usize % 0x4000)
+            }
+            None => (&mut self.other, 0x10000, pc as usize),
+        };
+        counts.get_or_insert_with(|| vec![0; len].into_boxed_slice())[i] += t_cyc as u64;
+        self.total += t_cyc as u64;
+    }
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+    // bank, address and t-cycles for everything that's run, with None for
+    // the bank outside the rom
+    fn entries(&self) -> impl Iterator<Item = (Option<usize>, u16, u64)> + '_ {
+        let banks = self.banks.iter().enumerate().filter_map(|(bank, counts)| {
+            // anything but bank 0 is usually run from 0x4000
+            let base = if bank == 0 { 0 } else { 0x4000 };
+            Some((Some(bank), base, counts.as_ref()?))
+        });
+        let other = self.other.as_ref().map(|counts| (None, 0, counts));
+        banks.chain(other).flat_map(|(bank, base, counts)| {
+            let counts = counts.iter().enumerate().filter(|&(_, &n)| n > 0);
+            counts.map(move |(i, &n)| (bank, (base + i) as u16, n))
+        })
+    }
+    // t-cycles by function, where a function is whatever's between one
+    // label and the next. code before any label goes to its bank
+    fn functions(&self, symbols: &Symbols) -> BTreeMap<String, u64> {
+        let mut functions = BTreeMap::new();
+        for (bank, addr, n) in self.entries() {
+            *functions.entry(function(symbols, bank, addr)).or_default() += n;
+        }
+        functions
+    }
+    // a plain text summary: time by bank, by function when there are
+    // symbols, and the busiest addresses
+    pub fn flat(&self, symbols: &Symbols) -> String {
+        let mut out = String::new();
+        let total = self.total.max(1);
+        let line = |out: &mut String, n: u64, what: &str| {
+            let percent = n as f64 * 100.0 / total as f64;
+            let _ = writeln!(out, "{percent:>7.2}% {n:>14}  {what}");
+        };
+        let _ = writeln!(out, "{} t-cycles\n\nby bank:", self.total);
+        let mut banks = BTreeMap::new();
+        for (bank, _, n) in self.entries() {
+            *banks.entry(bank).or_insert(0) += n;
+        }
+        for (bank, n) in banks {
+            line(&mut out, n, &bank_name(bank));
+        }
+        let mut functions: Vec<_> = self.functions(symbols).into_iter().collect();
+        functions.sort_by_key(|&(_, n)| Reverse(n));
+        let _ = writeln!(out, "\nby function:");
+        for (name, n) in functions {
+            line(&mut out, n, &name);
+        }
+        let mut addresses: Vec<_> = self.entries().collect();
+        addresses.sort_by_key(|&(_, _, n)| Reverse(n));
+        let _ = writeln!(out, "\nbusiest addresses:");
+        for (bank, addr, n) in addresses.into_iter().take(TOP_ADDRESSES) {
+            let at = match bank {
+                Some(bank) => format!("{bank:02x}:{addr:04x}"),
+                None => format!("--:{addr:04x}"),
+            };
+            let function = function(symbols, bank, addr);
+            line(&mut out, n, &format!("{at} in {function}"));
+        }
+        out
+    }
+    // the format valgrind's callgrind writes, for kcachegrind and the like.
+    // each bank is a file and costs are by instruction address. there's no
+    // call graph, only the time spent in each function itself
+    pub fn callgrind(&self, symbols: &Symbols, rom: &str) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# callgrind format\nversion: 1\ncreator: sethboy");
+        let _ = writeln!(out, "cmd: {rom}\npositions: instr\nevents: Cycles");
+        let _ = writeln!(out, "summary: {}\n\nob={rom}", self.total);
+        let mut last = None;
+        for (bank, addr, n) in self.entries() {
+            let function = function(symbols, bank, addr);
+            if last.as_ref() != Some(&(bank, function.clone())) {
+                let _ = writeln!(out, "fl={}\nfn={function}", bank_name(bank));
+                last = Some((bank, function));
+            }
+            let _ = writeln!(out, "0x{addr:04x} {n}");
+        }
+        out
+    }
+}
+
+fn bank_name(bank: Option<usize>) -> String {
+    match bank {
+        Some(bank) => format!("rom bank ${bank:02x}"),
+        None => "outside the rom".into(),
+    }
+}
+
+// the label the code at addr comes after, or its bank if there isn't one
+fn function(symbols: &Symbols, bank: Option<usize>, addr: u16) -> String {
+    // labels outside the rom are listed under bank 0
+    let label = symbols.containing(bank.unwrap_or(0), addr);
+    label.map_or_else(|| bank_name(bank), Into::into)
+}
+
+impl Emulator {
+    // starts a fresh profile, or stops keeping one
+    pub fn set_profiling(&mut self, on: bool) {
+        self.profile = on.then(Box::default);
+    }
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_deref()
+    }
+    pub(super) fn profile_step(&mut self, bank: Option<usize>, pc: u16, t_cyc: u8) {
+        if let Some(profile) = &mut self.profile {
+            profile.add(bank, pc, t_cyc);
+        }
+    }
+}
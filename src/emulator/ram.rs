@@ -1,144 +1,708 @@
-use std::io::{self, ErrorKind, Read};
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::{cell::Cell, fmt};
+#[cfg(feature = "std")]
+use std::io::{self, Read};
 
-use super::constants::*;
+use arrayvec::ArrayVec;
+
+use super::{
+    apu::Apu,
+    cartridge::{self, CartridgeHeader, MbcKind},
+    constants::*,
+    interrupts::{Interrupt, InterruptController},
+    mbc::{Mbc, NoMbc, Unhandled, rom_offset},
+    romdb::{self, RomInfo},
+    state::{StateReader, StateWriter},
+};
+
+// the most any mbc can address, 512 banks on mbc5
+pub(super) const MAX_ROM_SIZE: usize = 512 * 0x4000;
+
+#[derive(Debug)]
+pub enum RomError {
+    // not even long enough for the header
+    TooShort(usize),
+    TooLong(usize),
+    // a cart type there's no mbc for, unless one's picked by hand
+    UnsupportedMbc(u8),
+    #[cfg(feature = "std")]
+    Io(io::Error),
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RomError::TooShort(len) => write!(f, "{len} bytes is too short for a rom"),
+            RomError::TooLong(len) => write!(f, "{len} bytes is too long for a rom"),
+            RomError::UnsupportedMbc(kind) => {
+                let name = cartridge::cart_type_name(*kind);
+                write!(f, "cart type ${kind:02x} ({name}) is unsupported")
+            }
+            #[cfg(feature = "std")]
+            RomError::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+// problems with a header that still get the rom loaded
+#[derive(Debug)]
+pub enum RomWarning {
+    // the header's size doesn't match the file, or isn't a size at all,
+    // which bad dumps and homebrew often do
+    SizeMismatch {
+        header: Option<usize>,
+        actual: usize,
+    },
+    // the header checksum is wrong, which a real boot rom hangs on
+    BadChecksum {
+        header: u8,
+        actual: u8,
+    },
+}
+
+impl fmt::Display for RomWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RomWarning::SizeMismatch {
+                header: Some(header),
+                actual,
+            } => write!(f, "the header says {header} bytes, but the rom is {actual}"),
+            RomWarning::SizeMismatch { header: None, .. } => {
+                write!(f, "the header has no valid size")
+            }
+            RomWarning::BadChecksum { header, actual } => write!(
+                f,
+                "the header checksum is ${header:02x}, but the header adds up to ${actual:02x}"
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RomError {}
+
+struct OamDma {
+    source: u16,
+    // the next byte to copy
+    index: u16,
+    // m-cycles before the first byte gets copied
+    delay: u8,
+}
 
 pub struct Ram {
     // mem goes from 0x0000 to 0xFFFF
     pub mem: [u8; 0x10000],
-    // each bank has 16kb or rom
-    banks: Vec<[u8; 0x4000]>,
-    active_bank: usize,
+    // the whole rom, split into 16kb banks
+    rom: Vec<u8>,
+    // cart ram, in 8kb banks mapped to 0xA000-0xBFFF. it's sized from the
+    // header, and the mbc decides which bank is where
+    pub(super) cart_ram: Vec<u8>,
+    pub(super) mbc: Box<dyn Mbc>,
+    // used instead of what the header says when set
+    pub(super) mbc_override: Option<MbcKind>,
+    // the frontend's own rom database entries
+    pub(super) rom_overrides: Vec<(u32, RomInfo)>,
+    // what the rom database had for the loaded rom, and its crc32, which
+    // save states are checked against
+    pub(super) rom_info: RomInfo,
+    pub(super) rom_crc: u32,
+    // mapped over the start of the rom until the game writes to 0xFF50
+    boot_rom: Option<[u8; 0x100]>,
+    // what set_boot_rom was given, to map back in on a reset
+    reset_boot_rom: Option<[u8; 0x100]>,
+    // IF and IE live here rather than in mem
+    pub(super) interrupts: InterruptController,
+    // the sound registers are in mem, but writing them does more than that
+    pub(super) apu: Apu,
+    // seconds the rtc has counted when a rom is loaded
+    pub(super) rtc_start: u64,
+    // rtc seconds per emulated second, 0 freezing it
+    pub(super) rtc_speed: u32,
+    // game genie codes as address, new byte, and the byte it has to replace
+    pub(super) rom_patches: Vec<(u16, u8, Option<u8>)>,
+    // catch writes to hardware that isn't emulated, for debugging
+    pub(super) trap_unimplemented: bool,
+    // the last write that got caught, for the emulator to pick up
+    pub(super) unimplemented: Option<(u16, u8)>,
+    // the instruction being run, for diagnostics
+    pub(super) pc: u16,
+    // set by cpu writes to STAT for the ppu to emulate the dmg stat write bug
+    pub(super) stat_written: bool,
+    // cpu writes to registers the ppu reads mid-scanline, held until the
+    // ppu has caught up to the cycle they happen on
+    ppu_writes: ArrayVec<(u16, u8), 2>,
+    dma: Option<OamDma>,
+    // the byte the dma last copied, which is what the cpu sees on its bus
+    dma_byte: u8,
+    // warn when the cpu runs or touches anything but hram while oam dma
+    // has the bus, which games have to avoid on real hardware
+    pub(super) check_dma: bool,
+    // the first address the last instruction couldn't get at because of
+    // the dma. it's a cell since reads catch it too
+    dma_blocked: Cell<Option<u16>>,
+    // so each transfer only gets one warning
+    dma_warned: bool,
+    // a dma started by the current instruction
+    pending_dma: Option<u8>,
+    // which wram and hram bytes have been written since power on, when
+    // checking for reads of ones that haven't. empty when not checking
+    written: Vec<bool>,
+    // the first uninitialized byte the last instruction read
+    uninit_read: Cell<Option<u16>>,
 }
 
 pub trait CpuBus {
     fn read(&self, i: u16) -> u8;
     fn write(&mut self, i: u16, byte: u8);
+    // the highest priority interrupt that's requested and enabled
+    fn pending_interrupt(&self) -> Option<Interrupt> {
+        let pending = self.read(IF) & self.read(IE) & 0b1_1111;
+        Interrupt::from_bit(pending.trailing_zeros() as u8)
+    }
+    fn acknowledge_interrupt(&mut self, interrupt: Interrupt) {
+        self.write(IF, self.read(IF) & !(1 << interrupt as u8));
+    }
 }
 
 impl CpuBus for Ram {
     fn read(&self, i: u16) -> u8 {
-        // if i == LY {
-        //     return 0x90;
-        // }
-        // bank 01-NN
-        if (0x4000..0x8000).contains(&i) {
-            return self.banks[self.active_bank][i as usize - 0x4000];
+        if self.dma_conflict(i) {
+            self.block_dma_access(i);
+            // oam itself is locked, and the rest of the bus belongs to the dma
+            return if i >= 0xFE00 { 0xFF } else { self.dma_byte };
         }
-        // echo ram
-        if (0xE000..=0xFDFF).contains(&i) {
-            return self.mem[i as usize - 0x2000];
+        if let Some(index) = written_index(i)
+            && self.written.get(index) == Some(&false)
+            && self.uninit_read.get().is_none()
+        {
+            self.uninit_read.set(Some(i));
         }
-        self.mem[i as usize]
+        Ram::read(self, i)
     }
-    // TODO: support other kinds of mbc
     fn write(&mut self, i: u16, val: u8) {
-        // ram enable
-        if i < 0x2000 {
-            panic!("RAM enable register not implemented!");
-        }
-        // rom bank number
-        // FIX: blah blah some logic with only using as many bits as needed
-        if i < 0x4000 {
-            self.active_bank = match val & 0b11111 {
-                0 => 0,
-                // we sub 1 because bank 1 is at index 0
-                b => b - 1,
-            } as usize;
+        if self.dma_conflict(i) {
+            self.block_dma_access(i);
             return;
         }
-        // ram bank number or upper bits of rom bank number
-        if i < 0x6000 {
-            panic!("RAM bank number register not implemented!");
+        if i == DMA {
+            self.pending_dma = Some(val);
+            // sources past wram are approximated with its echo
+            if val > 0xDF {
+                self.unimplemented_write(i, val);
+            }
         }
-        // mode select
-        if i < 0x8000 {
-            panic!("Mode select register not implemented!");
+        if matches!(i, LCDC | SCY | SCX | BGP | OBP0 | OBP1 | WY | WX) {
+            // an instruction writes 2 bytes at most, but anything else going
+            // through the bus before a flush just makes the older ones land
+            if self.ppu_writes.is_full() {
+                self.flush_ppu_writes();
+            }
+            self.ppu_writes.push((i, val));
+            return;
         }
-        // echo ram
-        if (0xE000..0xFE00).contains(&i) {
-            self.mem[i as usize - 0x2000] = val;
+        // only the select bits can be written
+        if i == P1 {
+            self.mem[i as usize] = (val & 0b0011_0000) | (self.mem[i as usize] & 0b1100_1111);
             return;
         }
-        self.mem[i as usize] = val;
+        if (NR10..WAVE_RAM + 0x10).contains(&i) {
+            self.apu.write(&mut self.mem, i, val);
+            return;
+        }
+        // the mode and lyc bits are read only
+        if i == STAT {
+            self.mem[i as usize] = (val & 0b0111_1000) | (self.mem[i as usize] & 0b1000_0111);
+            self.stat_written = true;
+            return;
+        }
+        Ram::write(self, i, val);
+    }
+    fn pending_interrupt(&self) -> Option<Interrupt> {
+        self.interrupts.pending()
+    }
+    fn acknowledge_interrupt(&mut self, interrupt: Interrupt) {
+        self.interrupts.acknowledge(interrupt);
+    }
+}
+
+// where i is in written, for wram, its echo and hram
+fn written_index(i: u16) -> Option<usize> {
+    match i {
+        0xC000..0xE000 => Some(i as usize - 0xC000),
+        0xE000..0xFE00 => Some(i as usize - 0xE000),
+        0xFF80..=0xFFFE => Some(i as usize - 0xFF80 + 0x2000),
+        _ => None,
+    }
+}
+
+// bits of io registers that don't exist and always read as 1
+fn unused_bits(i: u16) -> u8 {
+    match i {
+        P1 => 0b1100_0000,
+        SC => 0b0111_1110,
+        TAC => 0b1111_1000,
+        STAT => 0b1000_0000,
+        // the sound registers' write only bits
+        NR10 => 0b1000_0000,
+        NR11 | NR21 => 0b0011_1111,
+        NR13 | NR23 | NR31 | NR33 | NR41 => 0xFF,
+        NR14 | NR24 | NR34 | NR44 => 0b1011_1111,
+        NR30 => 0b0111_1111,
+        NR32 => 0b1001_1111,
+        NR52 => 0b0111_0000,
+        // unmapped
+        0xFF03 | 0xFF08..=0xFF0E | 0xFF15 | 0xFF1F | 0xFF27..=0xFF2F | 0xFF4C..=0xFF7F => 0xFF,
+        _ => 0,
     }
 }
 
 impl Ram {
+    // everything's 0 like at power on, until the boot rom or skip_boot
+    // sets it up
     pub fn new() -> Self {
-        let mut mem = [0; 0x10000];
-        // LCDC (0xFF40) defaults to 10010001 (0x91) meaning
-        // bit 7 - LCD and PPU enabled
-        // bit 6 - Window tile map area is 9800-9BFF
-        // bit 5 - Window disabled
-        // bit 4 - BG and Window tile data area is 8000-8FFF
-        // bit 3 - BG tile map area is 9800-9BFF
-        // bit 2 - Object size is 8x8
-        // bit 1 - Objects are disabled
-        // bit 0 - BG and Window are enabled (basically)
-        mem[LCDC as usize] = 0x91;
-        mem[IF as usize] = 0xE1;
         Ram {
-            mem,
-            banks: Vec::new(),
-            active_bank: 0,
+            mem: [0; 0x10000],
+            rom: Vec::new(),
+            cart_ram: Vec::new(),
+            mbc: Box::new(NoMbc),
+            mbc_override: None,
+            rom_overrides: Vec::new(),
+            rom_info: RomInfo::default(),
+            rom_crc: 0,
+            boot_rom: None,
+            reset_boot_rom: None,
+            interrupts: InterruptController::new(),
+            apu: Apu::new(),
+            rtc_start: 0,
+            rtc_speed: 1,
+            rom_patches: Vec::new(),
+            trap_unimplemented: false,
+            unimplemented: None,
+            pc: 0,
+            stat_written: false,
+            ppu_writes: ArrayVec::new(),
+            dma: None,
+            dma_byte: 0xFF,
+            check_dma: false,
+            dma_blocked: Cell::new(None),
+            dma_warned: false,
+            pending_dma: None,
+            written: Vec::new(),
+            uninit_read: Cell::new(None),
         }
     }
     pub fn read(&self, i: u16) -> u8 {
-        // bank 01-NN
-        if (0x4000..0x8000).contains(&i) {
-            return self.banks[self.active_bank][i as usize - 0x4000];
+        if let Some(boot_rom) = &self.boot_rom
+            && i < 0x100
+        {
+            return boot_rom[i as usize];
+        }
+        if i < 0x8000 {
+            let byte = self.read_rom(i);
+            let patch = self
+                .rom_patches
+                .iter()
+                .find(|&&(addr, _, compare)| addr == i && compare.is_none_or(|c| c == byte));
+            return patch.map_or(byte, |&(_, value, _)| value);
+        }
+        if (0xA000..0xC000).contains(&i) {
+            return self.mbc.read_ram(&self.cart_ram, i);
         }
         // echo ram
         if (0xE000..=0xFDFF).contains(&i) {
             return self.mem[i as usize - 0x2000];
         }
+        // prohibited area, which on dmg reads 0xFF while the ppu has oam
+        // blocked and 0x00 otherwise
+        if (0xFEA0..0xFF00).contains(&i) {
+            return if self.mem[STAT as usize] & 0b10 > 0 {
+                0xFF
+            } else {
+                0
+            };
+        }
+        if i == IF || i == IE {
+            return self.interrupts.read(i);
+        }
+        if (0xFF00..0xFF80).contains(&i) {
+            return self.mem[i as usize] | unused_bits(i);
+        }
         self.mem[i as usize]
     }
-    pub fn write(&mut self, i: u16, val: u8) {
-        // ram enable
-        if i < 0x2000 {
-            panic!("RAM enable register not implemented!");
-        }
-        // rom bank number
-        // FIX: blah blah some logic with only using as many bits as needed
-        if i < 0x4000 {
-            self.active_bank = match val & 0b11111 {
-                0 => 0,
-                // we sub 1 because bank 1 is at index 0
-                b => b - 1,
-            } as usize;
-            return;
-        }
-        if i == DMA {
-            panic!("OAM DMA transfer not implemented!");
+    fn read_rom(&self, i: u16) -> u8 {
+        self.mbc.read_rom(&self.rom, i)
+    }
+    // writes to the rom itself rather than the mbc, and like a normal write
+    // anywhere else. for patching code from the debugger
+    pub(super) fn poke(&mut self, i: u16, val: u8) {
+        if i >= 0x8000 {
+            self.write(i, val);
+        } else if let Some(offset) = rom_offset(&self.rom, self.mbc.rom_bank(i), i) {
+            self.rom[offset] = val;
         }
-        // ram bank number or upper bits of rom bank number
-        if i < 0x6000 {
-            panic!("RAM bank number register not implemented!");
+    }
+    // the rom bank code at pc is running from, or None for the boot rom and
+    // anywhere that isn't rom
+    pub(super) fn code_bank(&self, pc: u16) -> Option<usize> {
+        if pc >= 0x8000 || self.boot_rom.is_some() && pc < 0x100 {
+            return None;
         }
-        // mode select
+        rom_offset(&self.rom, self.mbc.rom_bank(pc), pc).map(|offset| offset / 0x4000)
+    }
+    // the bank mapped to 0x4000-0x7FFF
+    pub(super) fn rom_bank(&self) -> usize {
+        self.mbc.rom_bank(0x4000)
+    }
+    pub(super) fn ram_bank(&self) -> usize {
+        self.mbc.ram_bank(&self.cart_ram)
+    }
+    pub fn write(&mut self, i: u16, val: u8) {
+        // the rom itself is never written, only mbc registers
         if i < 0x8000 {
-            panic!("Mode select register not implemented!");
+            let result = self.mbc.write_rom_reg(i, val);
+            self.unhandled_write(i, val, result);
+            return;
+        }
+        if (0xA000..0xC000).contains(&i) {
+            let result = self.mbc.write_ram(&mut self.cart_ram, i, val);
+            self.unhandled_write(i, val, result);
+            return;
         }
         // echo ram
         if (0xE000..0xFE00).contains(&i) {
-            self.mem[i as usize - 0x2000] = val;
+            self.write(i - 0x2000, val);
+            return;
+        }
+        // prohibited area
+        if (0xFEA0..0xFF00).contains(&i) {
+            return;
+        }
+        if i == IF || i == IE {
+            self.interrupts.write(i, val);
             return;
         }
+        if let Some(index) = written_index(i)
+            && let Some(written) = self.written.get_mut(index)
+        {
+            *written = true;
+        }
+        // the boot rom can't be mapped back in once it's gone
+        if i == BOOT && val > 0 {
+            self.boot_rom = None;
+        }
         self.mem[i as usize] = val;
     }
-    pub(super) fn load<R: Read>(&mut self, input: &mut R) -> io::Result<()> {
-        input.read_exact(&mut self.mem[..0x4000])?;
-        let mut buf = [0; 0x4000];
-        loop {
-            if let Err(e) = input.read_exact(&mut buf) {
-                if e.kind() == ErrorKind::UnexpectedEof {
-                    return Ok(());
-                } else {
-                    return Err(e);
-                }
+    fn unhandled_write(&mut self, i: u16, val: u8, result: Result<(), Unhandled>) {
+        match result {
+            Ok(()) => {}
+            Err(Unhandled::Ignored) => self.ignore_rom_write(i, val),
+            Err(Unhandled::Unimplemented) => self.unimplemented_write(i, val),
+        }
+    }
+    fn unimplemented_write(&mut self, i: u16, val: u8) {
+        let target = if i < 0xC000 { "mbc" } else { "io" };
+        let pc = self.pc;
+        log::debug!(
+            target: target,
+            "Unimplemented write of ${val:02x} to ${i:04x} (PC: ${pc:04x})"
+        );
+        if self.trap_unimplemented {
+            self.unimplemented = Some((i, val));
+        }
+    }
+    // usually a bug in the game
+    fn ignore_rom_write(&self, i: u16, val: u8) {
+        let pc = self.pc;
+        log::debug!(
+            target: "mbc",
+            "Ignored write of ${val:02x} to ROM at ${i:04x} (PC: ${pc:04x})"
+        );
+    }
+    // whether the cpu can't access i because oam dma is using its bus. only
+    // io and hram are always safe
+    fn dma_conflict(&self, i: u16) -> bool {
+        let Some(OamDma {
+            source, delay: 0, ..
+        }) = self.dma
+        else {
+            return false;
+        };
+        // vram has its own bus, and everything else below oam shares the other one
+        let vram = |i| (0x8000..0xA000).contains(&i);
+        (0xFE00..0xFEA0).contains(&i) || i < 0xFE00 && vram(i) == vram(source)
+    }
+    pub(super) fn tick_rtc(&mut self, t_cyc: u8) {
+        self.mbc.tick(t_cyc, self.rtc_speed);
+    }
+    fn block_dma_access(&self, i: u16) {
+        if self.check_dma && self.dma_blocked.get().is_none() {
+            self.dma_blocked.set(Some(i));
+        }
+    }
+    // warns if the instruction at pc that's just run didn't keep to hram
+    // while the dma had the bus
+    pub(super) fn check_dma_access(&mut self) {
+        let blocked = self.dma_blocked.take();
+        let running = matches!(self.dma, Some(OamDma { delay: 0, .. }));
+        if self.dma_warned || !running {
+            return;
+        }
+        let pc = self.pc;
+        if !(0xFF80..0xFFFF).contains(&pc) {
+            log::warn!(target: "dma", "Running at ${pc:04x}, outside HRAM, during OAM DMA");
+        } else if let Some(i) = blocked {
+            log::warn!(
+                target: "dma",
+                "Access to ${i:04x}, which OAM DMA blocks (PC: ${pc:04x})"
+            );
+        } else {
+            return;
+        }
+        self.dma_warned = true;
+    }
+    // starts or stops tracking which bytes have been written. everything
+    // counts as unwritten when it starts
+    pub(super) fn set_check_uninit(&mut self, on: bool) {
+        self.written.clear();
+        if on {
+            self.written.resize(0x2000 + 0x7F, false);
+        }
+    }
+    // warns if the instruction at pc that's just run read wram or hram
+    // that nothing's written to. each byte only gets one warning
+    pub(super) fn check_uninit_read(&mut self) {
+        let Some(i) = self.uninit_read.take() else {
+            return;
+        };
+        let pc = self.pc;
+        log::warn!(target: "ram", "Read of uninitialized ${i:04x} (PC: ${pc:04x})");
+        if let Some(index) = written_index(i) {
+            self.written[index] = true;
+        }
+    }
+    // runs oam dma for m_cyc m-cycles, copying a byte each cycle
+    pub(super) fn tick_dma(&mut self, m_cyc: u8) {
+        for _ in 0..m_cyc {
+            let Some(dma) = &mut self.dma else {
+                break;
+            };
+            if dma.delay > 0 {
+                dma.delay -= 1;
+                continue;
             }
-            self.banks.push(buf);
+            let (source, index) = (dma.source + dma.index, dma.index);
+            dma.index += 1;
+            if dma.index == 0xA0 {
+                self.dma = None;
+            }
+            // sources past wram read from its echo
+            let source = if source >= 0xE000 {
+                source - 0x2000
+            } else {
+                source
+            };
+            self.dma_byte = self.read(source);
+            self.mem[0xFE00 + index as usize] = self.dma_byte;
+        }
+        // dma starts after the instruction that wrote to the register, and
+        // writing again restarts it
+        if let Some(val) = self.pending_dma.take() {
+            log::trace!(target: "dma", "OAM DMA from ${val:02x}00 (PC: ${:04x})", self.pc);
+            self.dma_warned = false;
+            self.dma = Some(OamDma {
+                source: (val as u16) << 8,
+                index: 0,
+                delay: 1,
+            });
+        }
+    }
+    // memory, the banking and what's in flight. the rom, settings and the
+    // diagnostics aren't saved
+    pub(super) fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(&self.mem);
+        w.blob(&self.cart_ram);
+        w.blob(&self.mbc.state());
+        w.bool(self.boot_rom.is_some());
+        if let Some(boot_rom) = &self.boot_rom {
+            w.bytes(boot_rom);
+        }
+        self.interrupts.save_state(w);
+        self.apu.save_state(w);
+        w.u16(self.pc);
+        w.bool(self.stat_written);
+        w.u8(self.ppu_writes.len() as u8);
+        for &(i, val) in &self.ppu_writes {
+            w.u16(i);
+            w.u8(val);
+        }
+        w.bool(self.dma.is_some());
+        if let Some(dma) = &self.dma {
+            w.u16(dma.source);
+            w.u16(dma.index);
+            w.u8(dma.delay);
+        }
+        w.u8(self.dma_byte);
+        w.bool(self.pending_dma.is_some());
+        w.u8(self.pending_dma.unwrap_or(0));
+    }
+    pub(super) fn load_state(&mut self, r: &mut StateReader) -> Option<()> {
+        r.fill(&mut self.mem)?;
+        // the size comes from the rom, which is the same one
+        let cart_ram = r.blob()?;
+        if cart_ram.len() != self.cart_ram.len() {
+            return None;
+        }
+        self.cart_ram.copy_from_slice(cart_ram);
+        // and the mbc too
+        self.mbc.restore(r.blob()?)?;
+        self.boot_rom = None;
+        if r.bool()? {
+            self.boot_rom = Some(r.bytes(0x100)?.try_into().ok()?);
+        }
+        self.interrupts.load_state(r)?;
+        self.apu.load_state(r)?;
+        self.pc = r.u16()?;
+        self.stat_written = r.bool()?;
+        self.ppu_writes.clear();
+        for _ in 0..r.u8()? {
+            self.ppu_writes.try_push((r.u16()?, r.u8()?)).ok()?;
+        }
+        self.dma = None;
+        if r.bool()? {
+            let (source, index, delay) = (r.u16()?, r.u16()?, r.u8()?);
+            self.dma = Some(OamDma {
+                source,
+                index: index.min(0x9F),
+                delay,
+            });
+        }
+        self.dma_byte = r.u8()?;
+        let pending = r.bool()?;
+        self.pending_dma = Some(r.u8()?).filter(|_| pending);
+        self.dma_blocked.set(None);
+        self.uninit_read.set(None);
+        Some(())
+    }
+    pub(super) fn flush_ppu_writes(&mut self) {
+        for (i, val) in self.ppu_writes.drain(..) {
+            self.mem[i as usize] = val;
+        }
+    }
+    #[cfg(feature = "std")]
+    pub(super) fn load<R: Read>(&mut self, input: &mut R) -> Result<Vec<RomWarning>, RomError> {
+        let mut rom = Vec::new();
+        // one byte past the limit is enough to tell it's too long
+        let limit = MAX_ROM_SIZE as u64 + 1;
+        input
+            .take(limit)
+            .read_to_end(&mut rom)
+            .map_err(RomError::Io)?;
+        self.set_rom(rom)
+    }
+    pub(super) fn set_boot_rom(&mut self, boot_rom: &[u8; 0x100]) {
+        self.boot_rom = Some(*boot_rom);
+        self.reset_boot_rom = Some(*boot_rom);
+        self.mem[BOOT as usize] = 0;
+    }
+    // back to power on, keeping the rom, the settings and whatever's kept
+    // on the cart itself: battery backed ram and the rtc. wram too if
+    // keep_wram is set
+    pub(super) fn reset(&mut self, keep_wram: bool) {
+        let old = core::mem::replace(self, Ram::new());
+        if keep_wram {
+            self.mem[0xC000..0xE000].copy_from_slice(&old.mem[0xC000..0xE000]);
+        }
+        self.set_check_uninit(!old.written.is_empty());
+        if keep_wram && !self.written.is_empty() {
+            self.written[..0x2000].copy_from_slice(&old.written[..0x2000]);
+        }
+        self.mbc_override = old.mbc_override;
+        self.rom_overrides = old.rom_overrides;
+        self.rom_info = old.rom_info;
+        self.rom_crc = old.rom_crc;
+        self.boot_rom = old.reset_boot_rom;
+        self.reset_boot_rom = old.reset_boot_rom;
+        if self.boot_rom.is_some() {
+            self.mem[BOOT as usize] = 0;
+        }
+        self.rtc_start = old.rtc_start;
+        self.rtc_speed = old.rtc_speed;
+        self.rom_patches = old.rom_patches;
+        self.trap_unimplemented = old.trap_unimplemented;
+        self.check_dma = old.check_dma;
+        self.apu = old.apu;
+        self.apu.reset();
+        self.rom = old.rom;
+        if self.rom.is_empty() {
+            return;
+        }
+        // it only got loaded if there's an mbc for it
+        let kind = self.mbc_kind(self.rom_info, MbcKind::from_header(&self.rom));
+        let kind = kind.unwrap_or(MbcKind::None);
+        self.mbc = cartridge::new_mbc(&self.rom, kind, self.rtc_start);
+        if let Some(rtc) = self.mbc.rtc_mut()
+            && let Some(old) = old.mbc.rtc()
+        {
+            rtc.set_time(old.time());
+        }
+        self.cart_ram = old.cart_ram;
+        if !self.has_battery() {
+            self.cart_ram.fill(0);
+        }
+    }
+    // the override, then the rom database, then the header's. None for a
+    // cart type there's no mbc for
+    fn mbc_kind(&self, info: RomInfo, header: Option<MbcKind>) -> Option<MbcKind> {
+        self.mbc_override.or(info.mbc).or(header)
+    }
+    pub(super) fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+    pub(super) fn has_battery(&self) -> bool {
+        let battery = self.rom_info.battery;
+        battery.unwrap_or_else(|| cartridge::has_battery(&self.rom))
+    }
+    pub(super) fn boot_rom_mapped(&self) -> bool {
+        self.boot_rom.is_some()
+    }
+    pub(super) fn header(&self) -> Option<CartridgeHeader> {
+        CartridgeHeader::parse(&self.rom)
+    }
+    // warns about a header that's wrong but still gets the rom loaded
+    pub(super) fn set_rom(&mut self, mut rom: Vec<u8>) -> Result<Vec<RomWarning>, RomError> {
+        let actual = rom.len();
+        let Some(header) = CartridgeHeader::parse(&rom) else {
+            return Err(RomError::TooShort(actual));
+        };
+        if actual > MAX_ROM_SIZE {
+            return Err(RomError::TooLong(actual));
+        }
+        // the database can pick the mbc, so it's looked up before knowing
+        // whether the rom is kept
+        let crc = romdb::crc32(&rom);
+        let info = romdb::lookup(&self.rom_overrides, crc);
+        let Some(kind) = self.mbc_kind(info, header.mbc) else {
+            return Err(RomError::UnsupportedMbc(header.cart_type));
+        };
+        log::debug!(target: "rom", "crc32 {crc:08x}");
+        if info != RomInfo::default() {
+            log::info!(target: "rom", "Using the rom database's {info:?}");
+        }
+        (self.rom_crc, self.rom_info) = (crc, info);
+        // fill out a partial last bank with what an empty bus reads as
+        rom.resize(actual.next_multiple_of(0x4000), 0xFF);
+        self.rom = rom;
+        self.mbc = cartridge::new_mbc(&self.rom, kind, self.rtc_start);
+        self.cart_ram = vec![0; cartridge::ram_size(&self.rom, kind)];
+        let mut warnings = Vec::new();
+        if header.rom_size != Some(actual) {
+            let header = header.rom_size;
+            warnings.push(RomWarning::SizeMismatch { header, actual });
+        }
+        if !header.checksum_ok() {
+            let (header, actual) = (header.header_checksum, header.actual_checksum);
+            warnings.push(RomWarning::BadChecksum { header, actual });
         }
+        Ok(warnings)
     }
 }
@@ -0,0 +1,214 @@
+// ips and bps patches, for playing rom hacks and translations without
+// patching the file first
+use alloc::{vec, vec::Vec};
+use core::fmt;
+
+use super::ram::MAX_ROM_SIZE;
+use super::romdb::crc32;
+
+#[derive(Debug)]
+pub enum PatchError {
+    // doesn't start like an ips or bps
+    UnknownFormat,
+    // ends partway through a record
+    Truncated,
+    // copies from past the end of the source or the output so far
+    OutOfBounds,
+    // would make a rom bigger than any mbc can address
+    TooLong(usize),
+    // the rom isn't the one a bps was made for
+    SourceSize {
+        expected: usize,
+        actual: usize,
+    },
+    // a bps's crc32 of the source, target or patch itself didn't match
+    Checksum {
+        of: &'static str,
+        expected: u32,
+        actual: u32,
+    },
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PatchError::UnknownFormat => write!(f, "not an ips or bps patch"),
+            PatchError::Truncated => write!(f, "the patch ends too soon"),
+            PatchError::OutOfBounds => write!(f, "the patch copies from outside the rom"),
+            PatchError::TooLong(len) => write!(f, "{len} bytes is too long for a rom"),
+            PatchError::SourceSize { expected, actual } => write!(
+                f,
+                "the patch is for a {expected} byte rom, but this one is {actual}"
+            ),
+            PatchError::Checksum {
+                of,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "the {of} checksum is {actual:08x}, but the patch expects {expected:08x}"
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PatchError {}
+
+// returns rom with patch applied, working out which kind it is from its
+// header
+pub fn apply_patch(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if let Some(records) = patch.strip_prefix(b"PATCH") {
+        ips(rom, records)
+    } else if patch.starts_with(b"BPS1") {
+        bps(rom, patch)
+    } else {
+        Err(PatchError::UnknownFormat)
+    }
+}
+
+// records of a 3 byte offset, 2 byte length and the bytes, or a length of
+// 0 then a 2 byte count and a byte to repeat. "EOF" ends them, and can be
+// followed by a size to cut the rom down to
+fn ips(rom: &[u8], mut records: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let mut out = rom.to_vec();
+    let mut take = |n: usize| {
+        let (taken, rest) = records.split_at_checked(n).ok_or(PatchError::Truncated)?;
+        records = rest;
+        Ok(taken)
+    };
+    loop {
+        let offset = take(3)?;
+        if offset == b"EOF" {
+            break;
+        }
+        let offset = u32::from_be_bytes([0, offset[0], offset[1], offset[2]]) as usize;
+        let len = take(2)?;
+        let bytes = match u16::from_be_bytes([len[0], len[1]]) {
+            0 => {
+                let run = take(3)?;
+                vec![run[2]; u16::from_be_bytes([run[0], run[1]]) as usize]
+            }
+            len => take(len as usize)?.to_vec(),
+        };
+        let end = offset + bytes.len();
+        if end > MAX_ROM_SIZE {
+            return Err(PatchError::TooLong(end));
+        }
+        if out.len() < end {
+            out.resize(end, 0);
+        }
+        out[offset..end].copy_from_slice(&bytes);
+    }
+    if let Ok(size) = take(3) {
+        out.truncate(u32::from_be_bytes([0, size[0], size[1], size[2]]) as usize);
+    }
+    Ok(out)
+}
+
+// bps numbers are variable length, 7 bits a byte with the top bit set on the
+// last, and each extra byte also adding the next power of 128
+fn number(data: &[u8], pos: &mut usize) -> Result<usize, PatchError> {
+    let mut n: usize = 0;
+    let mut shift: usize = 1;
+    loop {
+        let byte = *data.get(*pos).ok_or(PatchError::Truncated)?;
+        *pos += 1;
+        n = n.saturating_add((byte & 0x7F) as usize * shift);
+        if byte & 0x80 > 0 {
+            return Ok(n);
+        }
+        shift = shift.saturating_mul(128);
+        n = n.saturating_add(shift);
+    }
+}
+
+// see byuu's bps spec. the output is built from runs copied out of the rom,
+// the patch, or what's been written already, then checked against the crc32s
+// at the end
+fn bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let Some(body_len) = patch.len().checked_sub(12) else {
+        return Err(PatchError::Truncated);
+    };
+    let (body, footer) = patch.split_at(body_len);
+    let crc = |i: usize| u32::from_le_bytes(footer[i * 4..][..4].try_into().unwrap());
+    let check = |of, expected, actual| {
+        if expected == actual {
+            Ok(())
+        } else {
+            Err(PatchError::Checksum {
+                of,
+                expected,
+                actual,
+            })
+        }
+    };
+    check("patch", crc(2), crc32(&patch[..patch.len() - 4]))?;
+    let mut pos = 4;
+    let source_size = number(body, &mut pos)?;
+    let target_size = number(body, &mut pos)?;
+    let metadata_size = number(body, &mut pos)?;
+    pos = pos.saturating_add(metadata_size);
+    if source_size != rom.len() {
+        return Err(PatchError::SourceSize {
+            expected: source_size,
+            actual: rom.len(),
+        });
+    }
+    check("rom", crc(0), crc32(rom))?;
+    if target_size > MAX_ROM_SIZE {
+        return Err(PatchError::TooLong(target_size));
+    }
+    let mut out = Vec::with_capacity(target_size);
+    let (mut source_offset, mut target_offset) = (0_usize, 0_usize);
+    while pos < body.len() {
+        let action = number(body, &mut pos)?;
+        let len = (action >> 2) + 1;
+        if out.len() + len > target_size {
+            return Err(PatchError::OutOfBounds);
+        }
+        match action & 3 {
+            // the same bytes as the rom
+            0 => {
+                let bytes = rom.get(out.len()..out.len() + len);
+                out.extend_from_slice(bytes.ok_or(PatchError::OutOfBounds)?);
+            }
+            // bytes from the patch
+            1 => {
+                let bytes = body.get(pos..pos + len).ok_or(PatchError::Truncated)?;
+                out.extend_from_slice(bytes);
+                pos += len;
+            }
+            // the other two are from a relative offset, the low bit being
+            // its sign. target copies can overlap what they're writing
+            kind => {
+                let offset = number(body, &mut pos)?;
+                let delta = offset >> 1;
+                let base = if kind == 2 {
+                    &mut source_offset
+                } else {
+                    &mut target_offset
+                };
+                *base = match offset & 1 {
+                    0 => base.checked_add(delta),
+                    _ => base.checked_sub(delta),
+                }
+                .ok_or(PatchError::OutOfBounds)?;
+                for _ in 0..len {
+                    let byte = match kind {
+                        2 => rom.get(*base),
+                        _ => out.get(*base),
+                    };
+                    let byte = *byte.ok_or(PatchError::OutOfBounds)?;
+                    out.push(byte);
+                    *base += 1;
+                }
+            }
+        }
+    }
+    if out.len() != target_size {
+        return Err(PatchError::Truncated);
+    }
+    check("patched rom", crc(1), crc32(&out))?;
+    Ok(out)
+}
@@ -0,0 +1,53 @@
+// settings for carts whose headers get something wrong, by the crc32 of the
+// whole rom since that's what no-intro and most other lists go by. frontends
+// can add their own entries, which win over the built in ones
+use super::{MbcKind, Model};
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct RomInfo {
+    pub mbc: Option<MbcKind>,
+    pub model: Option<Model>,
+    // whether the cart ram is kept, whatever the cart type says
+    pub battery: Option<bool>,
+}
+
+// carts the header heuristics in cartridge.rs don't already catch. a crc
+// only goes in here once it's been checked against a real dump
+const BUILTIN: &[(u32, RomInfo)] = &[];
+
+// the reflected 0xEDB88320 polynomial, a byte at a time
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 > 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+pub(super) fn crc32(data: &[u8]) -> u32 {
+    let crc = data.iter().fold(!0, |crc, &b| {
+        (crc >> 8) ^ CRC_TABLE[((crc ^ b as u32) & 0xFF) as usize]
+    });
+    !crc
+}
+
+// overrides are checked first
+pub(super) fn lookup(overrides: &[(u32, RomInfo)], crc: u32) -> RomInfo {
+    let mut entries = overrides.iter().chain(BUILTIN);
+    entries
+        .find(|&&(c, _)| c == crc)
+        .map(|&(_, info)| info)
+        .unwrap_or_default()
+}
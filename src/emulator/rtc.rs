@@ -0,0 +1,228 @@
+// the mbc3's real time clock. it counts emulated time rather than the host's,
+// so it keeps in step with the game however fast or slow that runs, and can
+// be moved around for games with events on certain days
+use core::fmt;
+
+use super::constants::CLOCK_HZ;
+
+const MINUTE: u64 = 60;
+const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 24 * HOUR;
+// the day counter is 9 bits, and sets its carry when it wraps
+const DAYS: u64 = 512;
+// the time and latched time, the last latch write and the cycles into the
+// second
+pub(super) const RTC_STATE_LEN: usize = 6 + 6 + 1 + 8;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct RtcTime {
+    pub days: u16,
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+    // stopped by the game
+    pub halted: bool,
+    // the day counter went past 511
+    pub carry: bool,
+}
+
+impl RtcTime {
+    // games can write things like 61 seconds, which still count up to the
+    // top of their bits before wrapping to 0
+    fn in_range(&self) -> bool {
+        self.seconds < 60 && self.minutes < 60 && self.hours < 24
+    }
+    fn to_bytes(self) -> [u8; 6] {
+        let [lo, hi] = self.days.to_le_bytes();
+        let flags = self.halted as u8 | (self.carry as u8) << 1;
+        [lo, hi, self.hours, self.minutes, self.seconds, flags]
+    }
+    // masked to what the registers can hold
+    fn from_bytes(bytes: [u8; 6]) -> Self {
+        let [lo, hi, hours, minutes, seconds, flags] = bytes;
+        RtcTime {
+            days: u16::from_le_bytes([lo, hi]) % DAYS as u16,
+            hours: hours & 0x1F,
+            minutes: minutes & 0x3F,
+            seconds: seconds & 0x3F,
+            halted: flags & 1 > 0,
+            carry: flags & 2 > 0,
+        }
+    }
+}
+
+impl fmt::Display for RtcTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "day {}, {:02}:{:02}:{:02}",
+            self.days, self.hours, self.minutes, self.seconds
+        )?;
+        if self.halted {
+            write!(f, " (halted)")?;
+        }
+        if self.carry {
+            write!(f, " (day carry)")?;
+        }
+        Ok(())
+    }
+}
+
+// like 90, 45s, 2h or 1d12h30m, in seconds
+pub fn parse_duration(s: &str) -> Option<u64> {
+    if let Ok(secs) = s.parse() {
+        return Some(secs);
+    }
+    let mut total: u64 = 0;
+    let mut n: Option<u64> = None;
+    for c in s.chars() {
+        if let Some(digit) = c.to_digit(10) {
+            n = Some(n.unwrap_or(0).checked_mul(10)?.checked_add(digit as u64)?);
+            continue;
+        }
+        let unit = match c {
+            'd' => DAY,
+            'h' => HOUR,
+            'm' => MINUTE,
+            's' => 1,
+            _ => return None,
+        };
+        total = total.checked_add(n.take()?.checked_mul(unit)?)?;
+    }
+    // a number without a unit on the end
+    (n.is_none() && !s.is_empty()).then_some(total)
+}
+
+pub(super) struct Rtc {
+    time: RtcTime,
+    // what the game reads, copied from time when it latches
+    latched: RtcTime,
+    // it latches on a write of 0 then 1
+    last_latch_write: u8,
+    // t-cycles into the current second
+    cycles: u64,
+}
+
+impl Rtc {
+    // start is how many seconds the clock has already counted
+    pub(super) fn new(start: u64) -> Self {
+        let mut rtc = Rtc {
+            time: RtcTime::default(),
+            latched: RtcTime::default(),
+            last_latch_write: 0xFF,
+            cycles: 0,
+        };
+        rtc.advance(start);
+        rtc
+    }
+    pub(super) fn time(&self) -> RtcTime {
+        self.time
+    }
+    pub(super) fn set_time(&mut self, time: RtcTime) {
+        self.time = time;
+        self.cycles = 0;
+    }
+    // speed is how many seconds go by for each emulated one, or 0 to freeze
+    pub(super) fn tick(&mut self, t_cyc: u8, speed: u32) {
+        if self.time.halted {
+            return;
+        }
+        self.cycles += t_cyc as u64 * speed as u64;
+        if self.cycles >= CLOCK_HZ {
+            self.advance(self.cycles / CLOCK_HZ);
+            self.cycles %= CLOCK_HZ;
+        }
+    }
+    pub(super) fn advance(&mut self, mut secs: u64) {
+        while secs > 0 && !self.time.in_range() {
+            self.second();
+            secs -= 1;
+        }
+        if !self.time.in_range() {
+            return;
+        }
+        let t = &mut self.time;
+        let into_day = t.hours as u64 * HOUR + t.minutes as u64 * MINUTE + t.seconds as u64;
+        let secs = secs.saturating_add(into_day);
+        let days = t.days as u64 + secs / DAY;
+        t.hours = (secs % DAY / HOUR) as u8;
+        t.minutes = (secs % HOUR / MINUTE) as u8;
+        t.seconds = (secs % MINUTE) as u8;
+        t.carry |= days >= DAYS;
+        t.days = (days % DAYS) as u16;
+    }
+    // one second at a time, for out of range values
+    fn second(&mut self) {
+        let t = &mut self.time;
+        t.seconds = (t.seconds + 1) & 0x3F;
+        if t.seconds != 60 {
+            return;
+        }
+        t.seconds = 0;
+        t.minutes = (t.minutes + 1) & 0x3F;
+        if t.minutes != 60 {
+            return;
+        }
+        t.minutes = 0;
+        t.hours = (t.hours + 1) & 0x1F;
+        if t.hours != 24 {
+            return;
+        }
+        t.hours = 0;
+        t.days = (t.days + 1) % DAYS as u16;
+        t.carry |= t.days == 0;
+    }
+    pub(super) fn state(&self) -> [u8; RTC_STATE_LEN] {
+        let mut state = [0; RTC_STATE_LEN];
+        state[..6].copy_from_slice(&self.time.to_bytes());
+        state[6..12].copy_from_slice(&self.latched.to_bytes());
+        state[12] = self.last_latch_write;
+        state[13..].copy_from_slice(&self.cycles.to_le_bytes());
+        state
+    }
+    // None if state is the wrong length
+    pub(super) fn restore(&mut self, state: &[u8]) -> Option<()> {
+        let state: &[u8; RTC_STATE_LEN] = state.try_into().ok()?;
+        self.time = RtcTime::from_bytes(state[..6].try_into().unwrap());
+        self.latched = RtcTime::from_bytes(state[6..12].try_into().unwrap());
+        self.last_latch_write = state[12];
+        self.cycles = u64::from_le_bytes(state[13..].try_into().unwrap()) % CLOCK_HZ;
+        Some(())
+    }
+    pub(super) fn latch(&mut self, val: u8) {
+        if self.last_latch_write == 0 && val == 1 {
+            self.latched = self.time;
+        }
+        self.last_latch_write = val;
+    }
+    // reg is 0 to 4 for seconds, minutes, hours, the day's low 8 bits and
+    // then its top bit with the halt and carry flags
+    pub(super) fn read(&self, reg: u8) -> u8 {
+        let t = &self.latched;
+        match reg {
+            0 => t.seconds,
+            1 => t.minutes,
+            2 => t.hours,
+            3 => t.days as u8,
+            _ => (t.days >> 8) as u8 | (t.halted as u8) << 6 | (t.carry as u8) << 7,
+        }
+    }
+    pub(super) fn write(&mut self, reg: u8, val: u8) {
+        let t = &mut self.time;
+        match reg {
+            0 => {
+                t.seconds = val & 0x3F;
+                // starts the second over
+                self.cycles = 0;
+            }
+            1 => t.minutes = val & 0x3F,
+            2 => t.hours = val & 0x1F,
+            3 => t.days = (t.days & 0x100) | val as u16,
+            _ => {
+                t.days = (t.days & 0xFF) | (val as u16 & 1) << 8;
+                t.halted = val & 1 << 6 > 0;
+                t.carry = val & 1 << 7 > 0;
+            }
+        }
+    }
+}
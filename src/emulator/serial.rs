@@ -0,0 +1,66 @@
+// whatever's plugged into the link port. the emulator asks the device for
+// the other half of each transfer, so another emulator, a network bridge or
+// a peripheral can sit on the other end without the serial code knowing
+use alloc::boxed::Box;
+
+use super::{Emulator, constants::*};
+
+pub trait SerialDevice {
+    // the game boy started shifting byte out on its own clock. returns what
+    // got shifted in, or None if that isn't known yet, in which case poll is
+    // asked every step until it is
+    fn send(&mut self, byte: u8) -> Option<u8>;
+    fn poll(&mut self) -> Option<u8> {
+        None
+    }
+    // the game boy is waiting on the device's clock with sb to send. returns
+    // the byte the device clocks in, if it's sending one
+    fn receive(&mut self, _sb: u8) -> Option<u8> {
+        None
+    }
+    // every step, for devices with timing of their own
+    fn tick(&mut self, _t_cyc: u8) {}
+}
+
+// nothing plugged in. transfers finish right away on either clock and SB is
+// left as it was, which test roms printing over serial are happy with
+pub struct NullDevice;
+
+impl SerialDevice for NullDevice {
+    fn send(&mut self, byte: u8) -> Option<u8> {
+        Some(byte)
+    }
+    fn receive(&mut self, sb: u8) -> Option<u8> {
+        Some(sb)
+    }
+}
+
+impl Emulator {
+    // None unplugs whatever was there
+    pub fn set_serial_device(&mut self, device: Option<Box<dyn SerialDevice>>) {
+        self.serial_device = device.unwrap_or_else(|| Box::new(NullDevice));
+        self.serial_sending = false;
+    }
+    pub(super) fn tick_serial(&mut self, t_cyc: u8) {
+        self.serial_device.tick(t_cyc);
+        let sc = self.ram.read(SC);
+        if sc & (1 << 7) == 0 {
+            // a transfer the game gave up on
+            self.serial_sending = false;
+            return;
+        }
+        let sb = self.ram.read(SB);
+        let received = if sc & 1 == 0 {
+            self.serial_device.receive(sb)
+        } else if self.serial_sending {
+            self.serial_device.poll()
+        } else {
+            self.serial_sending = true;
+            self.serial_device.send(sb)
+        };
+        if let Some(received) = received {
+            self.serial_sending = false;
+            self.finish_transfer(received);
+        }
+    }
+}
@@ -0,0 +1,338 @@
+// save states: everything that changes as the game runs, in a binary format
+// that starts with a magic number and a version. settings, the rom itself
+// and what the debugger's up to aren't in it, so a state only loads with
+// the rom it came from. numbers are little endian. after the info, the rest
+// can be deflated, or be a delta against another state for keeping lots of
+// them around
+use alloc::{borrow::Cow, string::String, vec::Vec};
+use core::{fmt, iter::zip};
+use miniz_oxide::{deflate::compress_to_vec, inflate::decompress_to_vec_with_limit};
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write};
+
+use super::{Emulator, THUMB_X, THUMB_Y, TRACE_LEN, romdb::crc32};
+
+const MAGIC: &[u8; 4] = b"SBST";
+// bumped whenever what's saved changes, since old states won't line up
+const VERSION: u16 = 20;
+// how the rest of the state after the info is stored
+const PLAIN: u8 = 0;
+const DEFLATED: u8 = 1;
+// the crc32 of the base's rest, then the deflated xor of the two, which is
+// mostly zeroes
+const DELTA: u8 = 2;
+// far more than any state inflates to, so a bad one can't ask for all the
+// memory there is
+const MAX_LEN: usize = 1 << 20;
+// the deflate level states get, from 0 for none to 10
+pub const DEFAULT_COMPRESSION: u8 = 6;
+
+// what a state says about itself, which comes before the rest so it can be
+// read without loading the state
+#[derive(Clone, Debug)]
+pub struct StateInfo {
+    pub rom_crc: u32,
+    pub title: String,
+    // seconds since 1970 in utc, or 0 if whatever saved it didn't say. the
+    // core never reads the clock itself
+    pub saved_at: u64,
+    pub frames: u64,
+    // THUMB_X by THUMB_Y shades, like Emulator::thumbnail
+    pub thumbnail: Vec<u8>,
+}
+
+impl StateInfo {
+    // everything up to the state proper, checking it's a state this
+    // version can load
+    pub fn read(data: &[u8]) -> Result<Self, StateError> {
+        Self::read_from(&mut StateReader(data))
+    }
+    fn read_from(r: &mut StateReader) -> Result<Self, StateError> {
+        if r.bytes(MAGIC.len()) != Some(MAGIC) {
+            return Err(StateError::NotAState);
+        }
+        match r.u16() {
+            Some(VERSION) => {}
+            Some(v) => return Err(StateError::Version(v)),
+            None => return Err(StateError::Corrupt),
+        }
+        Self::fields(r).ok_or(StateError::Corrupt)
+    }
+    fn fields(r: &mut StateReader) -> Option<Self> {
+        Some(StateInfo {
+            rom_crc: r.u32()?,
+            title: String::from_utf8(r.blob()?.to_vec()).ok()?,
+            saved_at: r.u64()?,
+            frames: r.u64()?,
+            thumbnail: unpack(r.bytes(THUMB_X * THUMB_Y / 4)?),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum StateError {
+    NotAState,
+    // from some other version of the format
+    Version(u16),
+    // saved with a different rom, by its crc32
+    OtherRom(u32),
+    // cut short, or with values that can't happen
+    Corrupt,
+    // a delta loaded without a base, or with the wrong one
+    NoBase,
+    WrongBase,
+    #[cfg(feature = "std")]
+    Io(io::Error),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::NotAState => write!(f, "not a save state"),
+            StateError::Version(v) => write!(f, "the state is version {v}, not {VERSION}"),
+            StateError::OtherRom(crc) => write!(f, "the state is from another rom ({crc:08x})"),
+            StateError::Corrupt => write!(f, "the state is corrupt"),
+            StateError::NoBase => write!(f, "the state only has changes from another state"),
+            StateError::WrongBase => write!(f, "the state has changes from a different state"),
+            #[cfg(feature = "std")]
+            StateError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for StateError {}
+
+pub(super) struct StateWriter(Vec<u8>);
+
+impl StateWriter {
+    pub(super) fn u8(&mut self, val: u8) {
+        self.0.push(val);
+    }
+    pub(super) fn bool(&mut self, val: bool) {
+        self.0.push(val as u8);
+    }
+    pub(super) fn u16(&mut self, val: u16) {
+        self.0.extend_from_slice(&val.to_le_bytes());
+    }
+    pub(super) fn u32(&mut self, val: u32) {
+        self.0.extend_from_slice(&val.to_le_bytes());
+    }
+    pub(super) fn u64(&mut self, val: u64) {
+        self.0.extend_from_slice(&val.to_le_bytes());
+    }
+    // bytes of a length the reader already knows
+    pub(super) fn bytes(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+    // bytes with their length in front
+    pub(super) fn blob(&mut self, bytes: &[u8]) {
+        self.u32(bytes.len() as u32);
+        self.bytes(bytes);
+    }
+}
+
+// every read returns None once it runs out
+pub(super) struct StateReader<'a>(&'a [u8]);
+
+impl<'a> StateReader<'a> {
+    pub(super) fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let (bytes, rest) = self.0.split_at_checked(len)?;
+        self.0 = rest;
+        Some(bytes)
+    }
+    fn array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.bytes(N)?.try_into().ok()
+    }
+    pub(super) fn u8(&mut self) -> Option<u8> {
+        Some(self.array::<1>()?[0])
+    }
+    pub(super) fn bool(&mut self) -> Option<bool> {
+        match self.u8()? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+    pub(super) fn u16(&mut self) -> Option<u16> {
+        self.array().map(u16::from_le_bytes)
+    }
+    pub(super) fn u32(&mut self) -> Option<u32> {
+        self.array().map(u32::from_le_bytes)
+    }
+    pub(super) fn u64(&mut self) -> Option<u64> {
+        self.array().map(u64::from_le_bytes)
+    }
+    // fills out, which has to be the length it was saved with
+    pub(super) fn fill(&mut self, out: &mut [u8]) -> Option<()> {
+        out.copy_from_slice(self.bytes(out.len())?);
+        Some(())
+    }
+    pub(super) fn blob(&mut self) -> Option<&'a [u8]> {
+        let len = self.u32()?;
+        self.bytes(len as usize)
+    }
+}
+
+impl Emulator {
+    // the whole state, for restore_state. it says it was saved at 0, since
+    // the core has no clock
+    pub fn state(&self) -> Vec<u8> {
+        self.state_saved_at(0)
+    }
+    // saved_at is in seconds since 1970, for StateInfo
+    pub fn state_saved_at(&self, saved_at: u64) -> Vec<u8> {
+        let mut w = StateWriter(Vec::new());
+        w.bytes(MAGIC);
+        w.u16(VERSION);
+        w.u32(self.ram.rom_crc);
+        w.blob(self.rom_title().as_bytes());
+        w.u64(saved_at);
+        w.u64(self.frames);
+        w.bytes(&pack(&self.thumbnail()));
+        w.u8(PLAIN);
+        w.u64(self.cycles);
+        w.u64(self.frames);
+        w.u32(self.frame_cycles);
+        // the link cable's wire is saved on its own, see Cable
+        w.bool(self.serial_sending);
+        self.cpu.save_state(&mut w);
+        self.ppu.save_state(&mut w);
+        self.timer.save_state(&mut w);
+        self.ram.save_state(&mut w);
+        w.0
+    }
+    // the state deflated at level, from 0 for none to 10. with a plain
+    // state from state() as the base, only what's changed since it is kept,
+    // and it takes restore_delta with the same base to load
+    pub fn compact_state(&self, base: Option<&[u8]>, level: u8) -> Vec<u8> {
+        compress(&self.state(), base, level)
+    }
+    // a state that doesn't load leaves everything as it was. it can be plain
+    // or deflated, but not a delta
+    pub fn restore_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        self.restore(data, None)
+    }
+    // a state from compact_state, with the base it was given
+    pub fn restore_delta(&mut self, data: &[u8], base: &[u8]) -> Result<(), StateError> {
+        self.restore(data, Some(base))
+    }
+    fn restore(&mut self, data: &[u8], base: Option<&[u8]>) -> Result<(), StateError> {
+        let (info, kind, rest) = split(data)?;
+        if info.rom_crc != self.ram.rom_crc {
+            return Err(StateError::OtherRom(info.rom_crc));
+        }
+        let rest = expand(kind, rest, base)?;
+        let old = self.state();
+        let mut r = StateReader(&rest);
+        if self.load_parts(&mut r).is_none() || !r.0.is_empty() {
+            // the emulator's own state always loads
+            let (_, _, old) = split(&old).unwrap();
+            self.load_parts(&mut StateReader(old)).unwrap();
+            return Err(StateError::Corrupt);
+        }
+        self.fault = None;
+        self.trace = [None; TRACE_LEN];
+        self.serial_line.clear();
+        self.ppu.frame_done = false;
+        self.rgba_stale = true;
+        #[cfg(feature = "std")]
+        {
+            self.last_step = None;
+        }
+        Ok(())
+    }
+    fn load_parts(&mut self, r: &mut StateReader) -> Option<()> {
+        self.cycles = r.u64()?;
+        self.frames = r.u64()?;
+        self.frame_cycles = r.u32()?;
+        self.serial_sending = r.bool()?;
+        self.cpu.load_state(r)?;
+        self.ppu.load_state(r)?;
+        self.timer.load_state(r)?;
+        self.ram.load_state(r)
+    }
+    // stamped with the time it's saved, and deflated at level
+    #[cfg(feature = "std")]
+    pub fn save_state<W: Write>(&self, mut w: W, level: u8) -> io::Result<()> {
+        let now = std::time::UNIX_EPOCH.elapsed().unwrap_or_default();
+        w.write_all(&compress(&self.state_saved_at(now.as_secs()), None, level))
+    }
+    #[cfg(feature = "std")]
+    pub fn load_state<R: Read>(&mut self, mut r: R) -> Result<(), StateError> {
+        let mut data = Vec::new();
+        r.read_to_end(&mut data).map_err(StateError::Io)?;
+        self.restore_state(&data)
+    }
+}
+
+// the info, how the rest is stored, and the rest
+fn split(data: &[u8]) -> Result<(StateInfo, u8, &[u8]), StateError> {
+    let mut r = StateReader(data);
+    let info = StateInfo::read_from(&mut r)?;
+    let kind = r.u8().ok_or(StateError::Corrupt)?;
+    Ok((info, kind, r.0))
+}
+
+// the rest of a state as state() would have it
+fn expand<'a>(kind: u8, rest: &'a [u8], base: Option<&[u8]>) -> Result<Cow<'a, [u8]>, StateError> {
+    let inflate =
+        |data| decompress_to_vec_with_limit(data, MAX_LEN).map_err(|_| StateError::Corrupt);
+    match kind {
+        PLAIN => Ok(Cow::Borrowed(rest)),
+        DEFLATED => Ok(Cow::Owned(inflate(rest)?)),
+        DELTA => {
+            let (_, base_kind, base) = split(base.ok_or(StateError::NoBase)?)?;
+            let mut r = StateReader(rest);
+            let crc = r.u32().ok_or(StateError::Corrupt)?;
+            if base_kind != PLAIN || crc32(base) != crc {
+                return Err(StateError::WrongBase);
+            }
+            let mut rest = inflate(r.0)?;
+            for (byte, base) in zip(&mut rest, base) {
+                *byte ^= base;
+            }
+            Ok(Cow::Owned(rest))
+        }
+        _ => Err(StateError::Corrupt),
+    }
+}
+
+// state is plain, straight from state_saved_at. a base that isn't plain is
+// left out
+fn compress(state: &[u8], base: Option<&[u8]>, level: u8) -> Vec<u8> {
+    let (_, _, rest) = split(state).unwrap();
+    let mut w = StateWriter(state[..state.len() - rest.len() - 1].to_vec());
+    let base = base.and_then(|base| split(base).ok());
+    match base {
+        Some((_, PLAIN, base)) => {
+            w.u8(DELTA);
+            w.u32(crc32(base));
+            let mut delta = rest.to_vec();
+            for (byte, base) in zip(&mut delta, base) {
+                *byte ^= base;
+            }
+            w.bytes(&compress_to_vec(&delta, level));
+        }
+        _ if level == 0 => {
+            w.u8(PLAIN);
+            w.bytes(rest);
+        }
+        _ => {
+            w.u8(DEFLATED);
+            w.bytes(&compress_to_vec(rest, level));
+        }
+    }
+    w.0
+}
+
+// shades 4 to a byte, the first in the low bits
+fn pack(shades: &[u8]) -> Vec<u8> {
+    let pack = |four: &[u8]| four.iter().rev().fold(0, |byte, &shade| byte << 2 | shade);
+    shades.chunks(4).map(pack).collect()
+}
+
+fn unpack(packed: &[u8]) -> Vec<u8> {
+    let shades = |byte: u8| (0..4).map(move |i| byte >> (i * 2) & 3);
+    packed.iter().flat_map(|&byte| shades(byte)).collect()
+}
@@ -1,13 +1,39 @@
+use super::{
+    interrupts::{Interrupt, InterruptController},
+    state::{StateReader, StateWriter},
+};
+
 pub struct Timer {
     counter: u16,
 }
 
 impl Timer {
     pub fn new() -> Self {
-        // TODO: set this to 1024 to simulate boot rom cycles
         Timer { counter: 0 }
     }
-    pub fn tick(&mut self, div: &mut u8, tima: &mut u8, tma: u8, tac: u8, if_: &mut u8, t_cyc: u8) {
+    pub(super) fn save_state(&self, w: &mut StateWriter) {
+        w.u16(self.counter);
+    }
+    pub(super) fn load_state(&mut self, r: &mut StateReader) -> Option<()> {
+        self.counter = r.u16()?;
+        Some(())
+    }
+    pub(super) fn counter(&self) -> u16 {
+        self.counter
+    }
+    // for starting where a boot rom would have left it
+    pub(super) fn set_div(&mut self, div: u8) {
+        self.counter = (div as u16) << 8;
+    }
+    pub(super) fn tick(
+        &mut self,
+        div: &mut u8,
+        tima: &mut u8,
+        tma: u8,
+        tac: u8,
+        interrupts: &mut InterruptController,
+        t_cyc: u8,
+    ) {
         // tima increment enabled
         if tac & 0b100 > 0 {
             let mask = 1
@@ -25,7 +51,7 @@ impl Timer {
                     let (sum, over) = tima.overflowing_add(1);
                     if over {
                         *tima = tma;
-                        *if_ |= 1 << 2;
+                        interrupts.request(Interrupt::Timer);
                     } else {
                         *tima = sum;
                     }
@@ -0,0 +1,137 @@
+// conditions checked before every instruction, which capture the screen or
+// save a state the moment they hold. for glitches that come and go too fast
+// to catch by hand. the capture is what the lcd shows right then, so lines
+// the ppu hasn't reached yet are still the last frame's
+use alloc::{boxed::Box, format, string::String, vec::Vec};
+use core::fmt;
+
+use super::{Breakpoint, Emulator, ScreenCapture};
+
+// captures and states waiting for take_fired past this are dropped
+const MAX_PENDING: usize = 16;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Trigger {
+    // code at an address runs
+    Pc(Breakpoint),
+    // the byte at an address changes
+    Changed(u16),
+    // a frame, numbered like frame_count while it's drawn, once it's done
+    // or once the ppu reaches a line of it
+    Frame(u64, Option<u8>),
+}
+
+impl Trigger {
+    // pc=<addr>, pc=<bank>:<addr>, mem=<addr>, frame=<n> or
+    // frame=<n>:<line>, with addresses in hex
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let hex = |s: &str| u16::from_str_radix(s.trim_start_matches('$'), 16).ok();
+        let trigger = match s.split_once('=') {
+            Some(("pc", at)) if at.contains(':') => Breakpoint::parse_banked(at).map(Trigger::Pc),
+            Some(("pc", at)) => hex(at).map(|addr| Trigger::Pc(Breakpoint::any_bank(addr))),
+            Some(("mem", at)) => hex(at).map(Trigger::Changed),
+            Some(("frame", n)) => {
+                let (n, line) = match n.split_once(':') {
+                    // only the lines that get drawn
+                    Some((n, line)) => (n, line.parse().ok().filter(|&l| l < 144).map(Some)),
+                    None => (n, Some(None)),
+                };
+                n.parse()
+                    .ok()
+                    .zip(line)
+                    .map(|(n, line)| Trigger::Frame(n, line))
+            }
+            _ => None,
+        };
+        trigger.ok_or(format!(
+            "'{s}' isn't pc=<addr>, pc=<bank>:<addr>, mem=<addr> or frame=<n>[:<line>]"
+        ))
+    }
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Trigger::Pc(bp) => write!(f, "pc={bp}"),
+            Trigger::Changed(addr) => write!(f, "mem=${addr:04x}"),
+            Trigger::Frame(n, None) => write!(f, "frame={n}"),
+            Trigger::Frame(n, Some(line)) => write!(f, "frame={n}:{line}"),
+        }
+    }
+}
+
+// what a trigger does when it fires
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TriggerAction {
+    Screenshot,
+    SaveState,
+}
+
+// what's left once a trigger's fired, for the frontend to save
+pub enum Fired {
+    Screenshot(Box<ScreenCapture>),
+    // from Emulator::state
+    State(Vec<u8>),
+}
+
+pub(super) struct Armed {
+    trigger: Trigger,
+    action: TriggerAction,
+    // the byte Changed last saw
+    last: Option<u8>,
+    // the frame it last fired in, so it's only once a frame
+    fired: Option<u64>,
+}
+
+impl Emulator {
+    pub fn add_trigger(&mut self, trigger: Trigger, action: TriggerAction) {
+        self.triggers.push(Armed {
+            trigger,
+            action,
+            last: None,
+            fired: None,
+        });
+    }
+    pub fn triggers(&self) -> impl Iterator<Item = Trigger> + '_ {
+        self.triggers.iter().map(|armed| armed.trigger)
+    }
+    // the triggers that have fired since the last call, by index, and the
+    // screen or state at the time
+    pub fn take_fired(&mut self) -> Vec<(usize, Fired)> {
+        core::mem::take(&mut self.fired)
+    }
+    // from tick, with end_frame for whole frames
+    pub(super) fn check_triggers(&mut self, frame_done: bool) {
+        let pc = self.cpu.pc;
+        let bank = self.code_bank(pc);
+        let line = self.ppu.position().0;
+        for i in 0..self.triggers.len() {
+            let armed = &mut self.triggers[i];
+            let fire = match armed.trigger {
+                Trigger::Pc(bp) => {
+                    !frame_done && bp.addr == pc && bp.bank.is_none_or(|b| bank == Some(b))
+                }
+                Trigger::Changed(addr) => {
+                    let val = self.ram.read(addr);
+                    let changed = armed.last.is_some_and(|last| last != val);
+                    armed.last = Some(val);
+                    changed
+                }
+                Trigger::Frame(n, None) => frame_done && self.frames == n,
+                Trigger::Frame(n, Some(l)) => !frame_done && self.frames == n && line == l,
+            };
+            if fire && armed.fired != Some(self.frames) {
+                armed.fired = Some(self.frames);
+                if self.fired.len() < MAX_PENDING {
+                    let fired = match armed.action {
+                        TriggerAction::Screenshot => {
+                            Fired::Screenshot(Box::new(self.capture_screen()))
+                        }
+                        TriggerAction::SaveState => Fired::State(self.state()),
+                    };
+                    self.fired.push((i, fired));
+                }
+            }
+        }
+    }
+}
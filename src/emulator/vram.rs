@@ -0,0 +1,64 @@
+use alloc::{vec, vec::Vec};
+#[cfg(feature = "std")]
+use std::io::{self, Write};
+
+use super::{
+    Ram,
+    constants::*,
+    ppu::{tile_addr, tile_row},
+};
+
+// the images of vram that can be exported, by name
+pub const VRAM_IMAGES: [&str; 3] = ["tiles", "map0", "map1"];
+
+// decodes one of VRAM_IMAGES into shades with bgp applied.
+// returns the width, height, and pixels
+pub(super) fn decode(ram: &Ram, name: &str) -> Option<(usize, usize, Vec<u8>)> {
+    let bgp = ram.read(BGP);
+    let (width, height, tiles): (usize, usize, Vec<u16>) = match name {
+        // all 384 tiles in order, 16 to a row
+        "tiles" => (128, 192, (0..384).map(|i| 0x8000 + i * 16).collect()),
+        // the 32x32 tile maps, using whichever addressing mode lcdc picks
+        "map0" | "map1" => {
+            let base = if name == "map0" { 0x9800 } else { 0x9C00 };
+            let lcdc = ram.read(LCDC);
+            let tiles = (0..32 * 32).map(|i| tile_addr(lcdc, ram.read(base + i), 0));
+            (256, 256, tiles.collect())
+        }
+        _ => return None,
+    };
+    let mut pixels = vec![0; width * height];
+    let per_row = width / 8;
+    for (i, addr) in tiles.into_iter().enumerate() {
+        let (x, y) = (i % per_row * 8, i / per_row * 8);
+        for row in 0..8 {
+            let (low, high) = (ram.read(addr + 2 * row), ram.read(addr + 2 * row + 1));
+            let line = &mut pixels[(y + row as usize) * width + x..][..8];
+            for (pixel, color) in line.iter_mut().zip(tile_row(low, high)) {
+                *pixel = (bgp >> (color * 2)) & 0b11;
+            }
+        }
+    }
+    Some((width, height, pixels))
+}
+
+// writes shades as an rgba png, with palette holding each shade's rgba bytes
+#[cfg(feature = "std")]
+pub(super) fn write_png<W: Write>(
+    w: W,
+    width: usize,
+    height: usize,
+    pixels: &[u8],
+    palette: &[u32; 4],
+) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(w, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    let rgba: Vec<u8> = pixels
+        .iter()
+        .flat_map(|&shade| palette[shade as usize].to_ne_bytes())
+        .collect();
+    writer.write_image_data(&rgba)?;
+    Ok(())
+}
@@ -0,0 +1,189 @@
+// a c api for embedding the emulator. include/sethboy.h is the matching
+// header, and can be regenerated with cbindgen. build the shared library
+// with cargo rustc --lib --release --crate-type cdylib, since a cdylib
+// crate type in the manifest would stop the core building without std
+use std::{
+    ffi::{c_int, c_void},
+    slice,
+};
+
+use crate::emulator::{Button, Emulator, FrameInfo, StateError};
+
+/// Free it with `sethboy_free`.
+#[unsafe(no_mangle)]
+pub extern "C" fn sethboy_new() -> *mut Emulator {
+    Box::into_raw(Box::new(Emulator::new()))
+}
+
+/// # Safety
+/// `emu` must come from `sethboy_new` and not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sethboy_free(emu: *mut Emulator) {
+    if !emu.is_null() {
+        drop(unsafe { Box::from_raw(emu) });
+    }
+}
+
+/// Returns 0 on success, or -1 if the rom is too short or too long. A header
+/// that gets the rom's size wrong isn't an error.
+///
+/// # Safety
+/// `emu` must come from `sethboy_new`, and `rom` must point to `len` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sethboy_load_rom(emu: *mut Emulator, rom: *const u8, len: usize) -> c_int {
+    let (emu, rom) = unsafe { (&mut *emu, slice::from_raw_parts(rom, len)) };
+    emu.load_bytes(rom).map_or(-1, |_| 0)
+}
+
+/// Runs until the next frame. Returns 1 if there's a new frame, which isn't
+/// the case while the lcd is off.
+///
+/// # Safety
+/// `emu` must come from `sethboy_new` and have a rom loaded.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sethboy_run_frame(emu: *mut Emulator) -> c_int {
+    unsafe { (*emu).run_frame() as c_int }
+}
+
+/// The screen as 160x144 rgba pixels. Valid until the next call taking `emu`.
+///
+/// # Safety
+/// `emu` must come from `sethboy_new`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sethboy_framebuffer(emu: *mut Emulator) -> *const u8 {
+    unsafe { (*emu).framebuffer_rgba().as_ptr() }
+}
+
+/// Sets which buttons are held, a bit each: right, left, up, down, a, b,
+/// select, start from the lowest bit up.
+///
+/// # Safety
+/// `emu` must come from `sethboy_new`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sethboy_set_buttons(emu: *mut Emulator, buttons: u8) {
+    let emu = unsafe { &mut *emu };
+    for (i, button) in Button::ALL.into_iter().enumerate() {
+        emu.set_button(button, buttons & (1 << i) > 0);
+    }
+}
+
+/// Copies the emulator's state into `out`, which has room for `*len` bytes,
+/// and sets `*len` to the state's size. Returns 0 on success, or -1 if it
+/// didn't fit and nothing was copied, which is how a null `out` with a `*len`
+/// of 0 gets the size. The size can change by a few bytes as the game runs.
+///
+/// # Safety
+/// `emu` must come from `sethboy_new`, `len` must be valid to read and write,
+/// and `out` must have room for `*len` bytes unless it's null.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sethboy_save_state(
+    emu: *mut Emulator,
+    out: *mut u8,
+    len: *mut usize,
+) -> c_int {
+    let (emu, len) = unsafe { (&*emu, &mut *len) };
+    let state = emu.state();
+    let room = *len;
+    *len = state.len();
+    if out.is_null() || room < state.len() {
+        return -1;
+    }
+    unsafe { slice::from_raw_parts_mut(out, state.len()) }.copy_from_slice(&state);
+    0
+}
+
+/// Loads a state from `sethboy_save_state`. Returns 0 on success, -1 if it
+/// isn't a state, -2 if it's from another version, -3 if it's from another
+/// rom, or -4 if it's corrupt. Nothing changes unless it loads.
+///
+/// # Safety
+/// `emu` must come from `sethboy_new`, and `state` must point to `len` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sethboy_load_state(
+    emu: *mut Emulator,
+    state: *const u8,
+    len: usize,
+) -> c_int {
+    let (emu, state) = unsafe { (&mut *emu, slice::from_raw_parts(state, len)) };
+    match emu.restore_state(state) {
+        Ok(()) => 0,
+        Err(StateError::NotAState) => -1,
+        Err(StateError::Version(_)) => -2,
+        Err(StateError::OtherRom(_)) => -3,
+        Err(_) => -4,
+    }
+}
+
+/// Sample frames a second, 48000 to start with, or 0 to not make any.
+/// Returns 0 on success, or -1 for a rate from 1 to 7999, which changes
+/// nothing.
+///
+/// # Safety
+/// `emu` must come from `sethboy_new`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sethboy_set_sample_rate(emu: *mut Emulator, rate: u32) -> c_int {
+    if unsafe { (*emu).set_sample_rate(rate) } {
+        0
+    } else {
+        -1
+    }
+}
+
+/// Copies up to `len` samples of sound into `out`, left then right for each
+/// sample frame, and returns how many. Only the last quarter of a second is
+/// kept, so call it at least that often.
+///
+/// # Safety
+/// `emu` must come from `sethboy_new`, and `out` must have room for `len`
+/// samples.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sethboy_read_audio(
+    emu: *mut Emulator,
+    out: *mut i16,
+    len: usize,
+) -> usize {
+    let (emu, out) = unsafe { (&mut *emu, slice::from_raw_parts_mut(out, len)) };
+    emu.read_audio(out)
+}
+
+/// What the frame callback gets. `framebuffer` is 160x144 rgba pixels, only
+/// valid during the call. `audio_samples` is how many sample frames the
+/// frame made, ready for `sethboy_read_audio`.
+#[repr(C)]
+pub struct SethboyFrameInfo {
+    pub framebuffer: *const u8,
+    pub frame: u64,
+    pub cycles: u64,
+    pub audio_samples: usize,
+    pub buttons: u8,
+}
+
+pub type SethboyFrameCallback =
+    extern "C" fn(info: *const SethboyFrameInfo, user_data: *mut c_void);
+
+/// Calls `callback` with `user_data` at the end of every frame, from inside
+/// `sethboy_run_frame`. Pass a null callback to stop.
+///
+/// # Safety
+/// `emu` must come from `sethboy_new`, and `user_data` must stay usable for
+/// as long as the callback is set.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sethboy_set_frame_callback(
+    emu: *mut Emulator,
+    callback: Option<SethboyFrameCallback>,
+    user_data: *mut c_void,
+) {
+    let emu = unsafe { &mut *emu };
+    emu.set_frame_callback(callback.map(|callback| {
+        Box::new(move |info: &FrameInfo| {
+            let info = SethboyFrameInfo {
+                framebuffer: info.framebuffer.as_ptr(),
+                frame: info.frame,
+                cycles: info.cycles,
+                audio_samples: info.audio_samples,
+                buttons: info.buttons,
+            };
+            callback(&info, user_data);
+        }) as _
+    }));
+}
@@ -0,0 +1,30 @@
+// a line per drawn frame with its number and a hash of the framebuffer, so
+// runs of different builds can be diffed to find where rendering changed
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write, stdout},
+};
+
+use gameboy::emulator::*;
+
+pub struct FrameHashes {
+    out: Box<dyn Write>,
+}
+
+impl FrameHashes {
+    // - writes to stdout
+    pub fn create(path: &str) -> io::Result<Self> {
+        let out: Box<dyn Write> = if path == "-" {
+            Box::new(stdout())
+        } else {
+            Box::new(BufWriter::new(File::create(path)?))
+        };
+        Ok(FrameHashes { out })
+    }
+    pub fn record(&mut self, emu: &Emulator) {
+        let line = format!("{} {:016x}\n", emu.frame_count(), emu.frame_hash());
+        if let Err(e) = self.out.write_all(line.as_bytes()) {
+            eprintln!("Unable to write a frame hash: {e}");
+        }
+    }
+}
@@ -0,0 +1,67 @@
+// --golden checks a test rom's screen against the picture it should end on,
+// once the rom runs ld b,b to say it's done. that's how mealybug tearoom's
+// ppu tests work, and their expected pngs can be used as they are, one at a
+// time or as the directory they come in. tests/mealybug.sh runs them all
+use std::{fs::File, io::BufReader, iter::zip, path::Path, process::ExitCode};
+
+use gameboy::emulator::{constants::*, *};
+
+// far longer than any of the tests take
+const MAX_FRAMES: u32 = 600;
+
+// the shade of each pixel. the expected pngs are greys from white to black,
+// so red alone tells the 4 apart
+fn expected_shades(path: &Path) -> Result<Vec<u8>, String> {
+    let file = File::open(path).map_err(|e| format!("Unable to open {}: {e}", path.display()))?;
+    let mut decoder = png::Decoder::new(BufReader::new(file));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let bad = |e| format!("{} isn't a png: {e}", path.display());
+    let mut reader = decoder.read_info().map_err(bad)?;
+    let mut buf = vec![0; reader.output_buffer_size().unwrap_or(0)];
+    let info = reader.next_frame(&mut buf).map_err(bad)?;
+    if (info.width, info.height) != (SCRN_X as u32, SCRN_Y as u32) {
+        return Err(format!("{} isn't {SCRN_X}x{SCRN_Y}", path.display()));
+    }
+    let pixels = buf[..info.buffer_size()].chunks_exact(info.color_type.samples());
+    Ok(pixels.map(|px| 3 - px[0].saturating_add(42) / 85).collect())
+}
+
+pub fn run(mut emu: Emulator, rom: &str, expected: &Path) -> ExitCode {
+    // a directory has the png named after the rom
+    let path = match Path::new(rom).file_stem() {
+        Some(stem) if expected.is_dir() => expected.join(stem).with_extension("png"),
+        _ => expected.to_path_buf(),
+    };
+    let shades = match expected_shades(&path) {
+        Ok(shades) => shades,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut frames = 0;
+    while emu.next_opcode() != 0x40 {
+        emu.tick();
+        if let Some(fault) = emu.take_fault() {
+            eprintln!("{rom}: {fault} on frame {}", frames + 1);
+            return ExitCode::FAILURE;
+        }
+        if emu.frame_ready() {
+            frames += 1;
+            if frames == MAX_FRAMES {
+                eprintln!("{rom}: no ld b,b in {MAX_FRAMES} frames");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let differ = |(_, (a, b)): &(usize, (&u8, &u8))| a != b;
+    let mut wrong = zip(emu.framebuffer(), &shades).enumerate().filter(differ);
+    let Some((first, _)) = wrong.next() else {
+        println!("{rom}: pass");
+        return ExitCode::SUCCESS;
+    };
+    let (x, y) = (first % SCRN_X, first / SCRN_X);
+    let count = wrong.count() + 1;
+    println!("{rom}: fail, {count} pixels differ from ({x}, {y}) on");
+    ExitCode::FAILURE
+}
@@ -0,0 +1,442 @@
+// a graphical debugger in a window of its own, drawn with egui
+use std::{iter::zip, sync::Arc, time::Instant};
+
+use egui::{
+    Color32, ColorImage, Context, Event as EguiEvent, Key, Modifiers, OutputCommand, PointerButton,
+    Pos2, RawInput, Rect, Sense, Stroke, TextureHandle, TextureOptions, vec2,
+};
+use gameboy::emulator::*;
+use sdl2::{
+    VideoSubsystem,
+    event::Event,
+    keyboard::{Keycode, Mod},
+    mouse::MouseButton,
+    video::{GLContext, Window},
+};
+
+use crate::{FRAME_DUR, audio::AudioStats, timing::FrameTimes};
+
+// lines of disassembly shown after pc
+const DISASM_LINES: usize = 24;
+// frames in the frame time graph
+const GRAPH_FRAMES: usize = 240;
+
+pub struct DebugWindow {
+    window: Window,
+    gl_context: GLContext,
+    painter: egui_glow::Painter,
+    ctx: Context,
+    // input gathered since the last draw
+    events: Vec<EguiEvent>,
+    modifiers: Modifiers,
+    start: Instant,
+    tiles: Option<TextureHandle>,
+    // the byte being edited in the memory view, and what's been typed
+    selected: Option<u16>,
+    edit: String,
+    goto: String,
+    new_breakpoint: String,
+}
+
+fn parse_hex(s: &str) -> Option<u16> {
+    u16::from_str_radix(s.trim().trim_start_matches('$'), 16).ok()
+}
+
+fn modifiers(keymod: Mod) -> Modifiers {
+    let ctrl = keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
+    Modifiers {
+        alt: keymod.intersects(Mod::LALTMOD | Mod::RALTMOD),
+        ctrl,
+        shift: keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD),
+        mac_cmd: false,
+        command: ctrl,
+    }
+}
+
+fn pointer_button(button: MouseButton) -> Option<PointerButton> {
+    Some(match button {
+        MouseButton::Left => PointerButton::Primary,
+        MouseButton::Right => PointerButton::Secondary,
+        MouseButton::Middle => PointerButton::Middle,
+        _ => return None,
+    })
+}
+
+impl DebugWindow {
+    pub fn new(video: &VideoSubsystem) -> Self {
+        let window = video
+            .window("Gameboy Debugger", 1000, 700)
+            .opengl()
+            .resizable()
+            .build()
+            .unwrap();
+        let gl_context = window.gl_create_context().unwrap();
+        window.gl_make_current(&gl_context).unwrap();
+        let gl = unsafe {
+            glow::Context::from_loader_function(|name| video.gl_get_proc_address(name) as *const _)
+        };
+        let painter = egui_glow::Painter::new(Arc::new(gl), "", None, false).unwrap();
+        DebugWindow {
+            window,
+            gl_context,
+            painter,
+            ctx: Context::default(),
+            events: Vec::new(),
+            modifiers: Modifiers::default(),
+            start: Instant::now(),
+            tiles: None,
+            selected: None,
+            edit: String::new(),
+            goto: String::new(),
+            new_breakpoint: String::new(),
+        }
+    }
+    pub fn id(&self) -> u32 {
+        self.window.id()
+    }
+    // turns sdl events for this window into egui ones
+    pub fn handle_event(&mut self, event: &Event) {
+        let clipboard = self.window.subsystem().clipboard();
+        match event {
+            Event::MouseMotion { x, y, .. } => {
+                self.events
+                    .push(EguiEvent::PointerMoved(Pos2::new(*x as f32, *y as f32)));
+            }
+            Event::MouseButtonDown {
+                mouse_btn, x, y, ..
+            }
+            | Event::MouseButtonUp {
+                mouse_btn, x, y, ..
+            } => {
+                let Some(button) = pointer_button(*mouse_btn) else {
+                    return;
+                };
+                self.events.push(EguiEvent::PointerButton {
+                    pos: Pos2::new(*x as f32, *y as f32),
+                    button,
+                    pressed: matches!(event, Event::MouseButtonDown { .. }),
+                    modifiers: self.modifiers,
+                });
+            }
+            Event::MouseWheel {
+                precise_x,
+                precise_y,
+                ..
+            } => {
+                self.events.push(EguiEvent::MouseWheel {
+                    unit: egui::MouseWheelUnit::Line,
+                    delta: vec2(*precise_x, *precise_y),
+                    modifiers: self.modifiers,
+                });
+            }
+            Event::TextInput { text, .. } => self.events.push(EguiEvent::Text(text.clone())),
+            Event::KeyDown {
+                keycode: Some(keycode),
+                keymod,
+                repeat,
+                ..
+            }
+            | Event::KeyUp {
+                keycode: Some(keycode),
+                keymod,
+                repeat,
+                ..
+            } => {
+                self.modifiers = modifiers(*keymod);
+                let pressed = matches!(event, Event::KeyDown { .. });
+                if pressed && self.modifiers.ctrl {
+                    match *keycode {
+                        Keycode::C => self.events.push(EguiEvent::Copy),
+                        Keycode::X => self.events.push(EguiEvent::Cut),
+                        Keycode::V => {
+                            if let Ok(text) = clipboard.clipboard_text() {
+                                self.events.push(EguiEvent::Paste(text));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                if let Some(key) = Key::from_name(&keycode.name()) {
+                    self.events.push(EguiEvent::Key {
+                        key,
+                        physical_key: None,
+                        pressed,
+                        repeat: *repeat,
+                        modifiers: self.modifiers,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    pub fn draw(&mut self, emu: &mut Emulator, times: &FrameTimes, audio: Option<AudioStats>) {
+        let (width, height) = self.window.drawable_size();
+        let input = RawInput {
+            screen_rect: Some(Rect::from_min_size(
+                Pos2::ZERO,
+                vec2(width as f32, height as f32),
+            )),
+            time: Some(self.start.elapsed().as_secs_f64()),
+            modifiers: self.modifiers,
+            events: std::mem::take(&mut self.events),
+            ..Default::default()
+        };
+        let ctx = self.ctx.clone();
+        let output = ctx.run(input, |ctx| {
+            self.ui(ctx, emu);
+            timing_ui(ctx, times, audio);
+        });
+        for command in output.platform_output.commands {
+            if let OutputCommand::CopyText(text) = command {
+                let _ = self
+                    .window
+                    .subsystem()
+                    .clipboard()
+                    .set_clipboard_text(&text);
+            }
+        }
+        let primitives = ctx.tessellate(output.shapes, output.pixels_per_point);
+        self.window.gl_make_current(&self.gl_context).unwrap();
+        self.painter.clear([width, height], [0.1, 0.1, 0.1, 1.0]);
+        self.painter.paint_and_update_textures(
+            [width, height],
+            output.pixels_per_point,
+            &primitives,
+            &output.textures_delta,
+        );
+        self.window.gl_swap_window();
+    }
+    fn ui(&mut self, ctx: &Context, emu: &mut Emulator) {
+        let regs = emu.registers();
+        egui::TopBottomPanel::top("controls").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if emu.paused() {
+                    if ui.button("Continue").clicked() {
+                        emu.resume();
+                    }
+                    if ui.button("Step").clicked() {
+                        emu.step();
+                    }
+                    ui.label(format!("Paused at ${:04x}", regs.pc));
+                } else {
+                    if ui.button("Pause").clicked() {
+                        emu.pause();
+                    }
+                    ui.label("Running");
+                }
+            });
+        });
+        egui::Window::new("Registers").show(ctx, |ui| {
+            egui::Grid::new("registers").show(ui, |ui| {
+                let pairs = [
+                    ("af", regs.a, regs.f),
+                    ("bc", regs.b, regs.c),
+                    ("de", regs.d, regs.e),
+                    ("hl", regs.h, regs.l),
+                ];
+                for (name, high, low) in pairs {
+                    ui.monospace(name);
+                    ui.monospace(format!("${high:02x}{low:02x}"));
+                    ui.end_row();
+                }
+                ui.monospace("sp");
+                ui.monospace(format!("${:04x}", regs.sp));
+                ui.end_row();
+                ui.monospace("pc");
+                ui.monospace(format!("${:04x}", regs.pc));
+                ui.end_row();
+            });
+            let f = regs.f;
+            let flag = |bit: u8, name| if f & (1 << bit) > 0 { name } else { "-" };
+            ui.monospace(format!(
+                "flags {}{}{}{}",
+                flag(7, "z"),
+                flag(6, "n"),
+                flag(5, "h"),
+                flag(4, "c")
+            ));
+        });
+        let stats = emu.stats();
+        egui::Window::new("Stats").show(ctx, |ui| {
+            egui::Grid::new("stats").show(ui, |ui| {
+                let rows = [
+                    ("cycles", stats.cycles),
+                    ("instructions", stats.instructions),
+                    ("frames", stats.frames),
+                    ("rom bank", stats.rom_bank as u64),
+                    ("ram bank", stats.ram_bank as u64),
+                ];
+                let names = ["vblank", "stat", "timer", "serial", "joypad"];
+                let names = names.map(|name| format!("{name} interrupts"));
+                let interrupts = zip(names, stats.interrupts);
+                let rows = rows.map(|(name, n)| (name.to_string(), n));
+                for (name, n) in rows.into_iter().chain(interrupts) {
+                    ui.label(name);
+                    ui.monospace(n.to_string());
+                    ui.end_row();
+                }
+            });
+        });
+        egui::Window::new("Disassembly").show(ctx, |ui| {
+            ui.label("Click a line to toggle its breakpoint");
+            let mut addr = regs.pc;
+            for _ in 0..DISASM_LINES {
+                let (text, len) = emu.disassemble(addr);
+                let bp = emu.breakpoint_at(addr);
+                let marker = match (bp.is_some(), addr == regs.pc) {
+                    (true, true) => "●>",
+                    (true, false) => "● ",
+                    (false, true) => " >",
+                    (false, false) => "  ",
+                };
+                let line = format!("{marker} {addr:04x}  {text}");
+                if ui
+                    .selectable_label(addr == regs.pc, egui::RichText::new(line).monospace())
+                    .clicked()
+                {
+                    // only in the bank that's showing, so the same address
+                    // in another bank doesn't stop too
+                    match bp {
+                        Some(bp) => emu.remove_breakpoint(bp),
+                        None => emu.add_breakpoint(Breakpoint {
+                            addr,
+                            bank: emu.code_bank(addr),
+                        }),
+                    }
+                }
+                addr = addr.wrapping_add(len as u16);
+            }
+        });
+        egui::Window::new("Breakpoints").show(ctx, |ui| {
+            for bp in emu.breakpoints().to_vec() {
+                ui.horizontal(|ui| {
+                    ui.monospace(bp.to_string());
+                    if ui.small_button("Remove").clicked() {
+                        emu.remove_breakpoint(bp);
+                    }
+                });
+            }
+            ui.horizontal(|ui| {
+                let edit = ui.text_edit_singleline(&mut self.new_breakpoint);
+                let enter = edit.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter));
+                if (ui.button("Add").clicked() || enter)
+                    && let Some(bp) = Breakpoint::parse_banked(&self.new_breakpoint)
+                        .or_else(|| parse_hex(&self.new_breakpoint).map(Breakpoint::any_bank))
+                {
+                    emu.add_breakpoint(bp);
+                    self.new_breakpoint.clear();
+                }
+            });
+        });
+        self.memory_ui(ctx, emu);
+        self.tiles_ui(ctx, emu);
+    }
+    fn memory_ui(&mut self, ctx: &Context, emu: &mut Emulator) {
+        egui::Window::new("Memory").show(ctx, |ui| {
+            let mut scroll_to = None;
+            ui.horizontal(|ui| {
+                ui.label("Go to");
+                let edit = ui.text_edit_singleline(&mut self.goto);
+                if edit.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+                    scroll_to = parse_hex(&self.goto);
+                }
+            });
+            if let Some(addr) = self.selected {
+                ui.horizontal(|ui| {
+                    ui.monospace(format!("${addr:04x} ="));
+                    let edit = ui.text_edit_singleline(&mut self.edit);
+                    if edit.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+                        if let Ok(val) = u8::from_str_radix(self.edit.trim(), 16) {
+                            emu.ram.write(addr, val);
+                        }
+                        self.selected = None;
+                    }
+                });
+            }
+            let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+            let mut area = egui::ScrollArea::vertical().max_height(400.0);
+            if let Some(addr) = scroll_to {
+                let spacing = ui.spacing().item_spacing.y;
+                area = area.vertical_scroll_offset((addr / 16) as f32 * (row_height + spacing));
+            }
+            area.show_rows(ui, row_height, 0x1000, |ui, rows| {
+                for row in rows {
+                    let base = row as u16 * 16;
+                    ui.horizontal(|ui| {
+                        ui.monospace(format!("{base:04x}:"));
+                        for i in 0..16 {
+                            let addr = base + i;
+                            let byte = emu.ram.read(addr);
+                            let text = egui::RichText::new(format!("{byte:02x}")).monospace();
+                            if ui
+                                .selectable_label(self.selected == Some(addr), text)
+                                .clicked()
+                            {
+                                self.selected = Some(addr);
+                                self.edit = format!("{byte:02x}");
+                            }
+                        }
+                    });
+                }
+            });
+        });
+    }
+    fn tiles_ui(&mut self, ctx: &Context, emu: &Emulator) {
+        let (width, height, shades) = emu.vram_image("tiles").unwrap();
+        let palette = emu
+            .palette()
+            .map(|[r, g, b, a]| Color32::from_rgba_unmultiplied(r, g, b, a));
+        let pixels = shades
+            .iter()
+            .map(|&shade| palette[shade as usize])
+            .collect();
+        let image = ColorImage::new([width, height], pixels);
+        let tiles = match &mut self.tiles {
+            Some(tiles) => {
+                tiles.set(image, TextureOptions::NEAREST);
+                tiles
+            }
+            None => self
+                .tiles
+                .insert(ctx.load_texture("tiles", image, TextureOptions::NEAREST)),
+        };
+        egui::Window::new("Tiles").show(ctx, |ui| {
+            ui.image((tiles.id(), vec2(width as f32 * 2.0, height as f32 * 2.0)));
+        });
+    }
+}
+
+// a bar per frame, with a line where a frame should take
+fn timing_ui(ctx: &Context, times: &FrameTimes, audio: Option<AudioStats>) {
+    egui::Window::new("Frame times").show(ctx, |ui| {
+        let size = vec2(GRAPH_FRAMES as f32 * 2.0, 100.0);
+        let (rect, _) = ui.allocate_exact_size(size, Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0.0, Color32::from_gray(20));
+        // three frames' worth fits
+        let height = |secs: f64| (secs / FRAME_DUR.as_secs_f64() / 3.0).min(1.0) as f32;
+        let x = |i: usize| rect.left() + i as f32 * 2.0;
+        for (i, time) in times.recent(GRAPH_FRAMES).iter().enumerate() {
+            let top = rect.bottom() - height(time.as_secs_f64()) * rect.height();
+            let late = time.as_secs_f64() > FRAME_DUR.as_secs_f64() * 1.5;
+            let color = if late { Color32::RED } else { Color32::GREEN };
+            painter.line_segment(
+                [Pos2::new(x(i), rect.bottom()), Pos2::new(x(i), top)],
+                Stroke::new(1.0, color),
+            );
+        }
+        let y = rect.bottom() - height(FRAME_DUR.as_secs_f64()) * rect.height();
+        painter.hline(rect.x_range(), y, Stroke::new(1.0, Color32::GRAY));
+        ui.label(format!("{:+.3}s against the wall clock", times.drift()));
+        if let Some(audio) = audio {
+            ui.label(audio.report());
+        }
+    });
+}
+
+impl Drop for DebugWindow {
+    fn drop(&mut self) {
+        let _ = self.window.gl_make_current(&self.gl_context);
+        self.painter.destroy();
+    }
+}
@@ -0,0 +1,47 @@
+// --record-input, which writes each button going down or up as it's
+// played, in input_script.rs's format. it's for bug reports, to replay with
+// --replay-input. presses partway through a frame, with --poll-lines, go
+// down as the start of the next one
+use std::{fs::File, io::Write};
+
+use gameboy::emulator::*;
+
+pub struct InputRecorder {
+    // None after a write fails, so there's only the one error
+    file: Option<File>,
+    path: String,
+    held: u8,
+}
+
+impl InputRecorder {
+    pub fn create(path: &str, rom: &str) -> Result<Self, String> {
+        let mut file = File::create(path).map_err(|e| format!("Unable to create {path}: {e}"))?;
+        writeln!(file, "# input for {rom}").map_err(|e| format!("Unable to write {path}: {e}"))?;
+        Ok(InputRecorder {
+            file: Some(file),
+            path: path.into(),
+            held: 0,
+        })
+    }
+    // logs what's changed since the last frame, as of the one about to run
+    pub fn record(&mut self, emu: &Emulator) {
+        let held = emu.buttons();
+        let changed = held ^ self.held;
+        self.held = held;
+        let Some(file) = &mut self.file else {
+            return;
+        };
+        let frame = emu.frame_count();
+        for (i, name) in BUTTON_NAMES.iter().enumerate() {
+            if changed & (1 << i) == 0 {
+                continue;
+            }
+            let state = if held & (1 << i) > 0 { "down" } else { "up" };
+            if let Err(e) = writeln!(file, "{frame} {name} {state}") {
+                eprintln!("Unable to write {}: {e}", self.path);
+                self.file = None;
+                return;
+            }
+        }
+    }
+}
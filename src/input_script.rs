@@ -0,0 +1,60 @@
+// button presses read from a file, one per line like "120 a down", applied
+// at the start of the given frame. blank lines and # comments are skipped
+use std::fs;
+
+use gameboy::emulator::*;
+
+#[derive(Clone)]
+pub struct InputScript {
+    // sorted by frame, keeping the file's order within a frame
+    events: Vec<(u64, Button, bool)>,
+    next: usize,
+}
+
+fn parse_line(line: &str) -> Option<(u64, Button, bool)> {
+    let mut words = line.split_whitespace();
+    let frame = words.next()?.parse().ok()?;
+    let button = Button::from_name(words.next()?)?;
+    let down = match words.next()? {
+        "down" => true,
+        "up" => false,
+        _ => return None,
+    };
+    words.next().is_none().then_some((frame, button, down))
+}
+
+impl InputScript {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Unable to read {path}: {e}"))?;
+        let mut events = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let Some(event) = parse_line(line) else {
+                return Err(format!(
+                    "{path}:{}: expected <frame> <button> <down|up>, got '{line}'",
+                    i + 1
+                ));
+            };
+            events.push(event);
+        }
+        events.sort_by_key(|&(frame, ..)| frame);
+        Ok(InputScript { events, next: 0 })
+    }
+    // starts over, for a fresh emulator
+    pub fn restart(&mut self) {
+        self.next = 0;
+    }
+    // sets the buttons for the frame the emulator is about to run
+    pub fn apply(&mut self, emu: &mut Emulator) {
+        let frame = emu.frame_count();
+        while let Some(&(at, button, down)) = self.events.get(self.next)
+            && at <= frame
+        {
+            emu.set_button(button, down);
+            self.next += 1;
+        }
+    }
+}
@@ -0,0 +1,59 @@
+// a slow lcd for --lcd-response, where pixels take a while to reach their
+// new shade instead of switching at once, which is where the dmg's motion
+// blur comes from. each pixel eases toward its shade exponentially, with one
+// response time for darkening and another for clearing, since the real
+// screen is quicker one way than the other
+use gameboy::emulator::constants::*;
+
+use crate::FRAME_DUR;
+
+pub struct LcdResponse {
+    // how much of the way to its shade a pixel gets in a frame, when it's
+    // darkening and when it's clearing
+    rise: f32,
+    fall: f32,
+    // each pixel's shade so far, from 0.0 for the lightest to 3.0
+    levels: Vec<f32>,
+    rgba: [u8; SCRN_X * SCRN_Y * 4],
+}
+
+// the time constant in milliseconds, as a fraction of the way a frame gets
+fn per_frame(ms: f32) -> f32 {
+    if ms <= 0.0 {
+        return 1.0;
+    }
+    let frame_ms = FRAME_DUR.as_secs_f32() * 1000.0;
+    1.0 - (-frame_ms / ms).exp()
+}
+
+impl LcdResponse {
+    pub fn new(rise_ms: f32, fall_ms: f32) -> Self {
+        LcdResponse {
+            rise: per_frame(rise_ms),
+            fall: per_frame(fall_ms),
+            levels: vec![0.0; SCRN_X * SCRN_Y],
+            rgba: [0; SCRN_X * SCRN_Y * 4],
+        }
+    }
+    // a frame's worth of each pixel heading for its shade
+    pub fn frame(&mut self, shades: &[u8; SCRN_X * SCRN_Y]) {
+        for (level, &shade) in self.levels.iter_mut().zip(shades) {
+            let to = shade as f32;
+            let rate = if to > *level { self.rise } else { self.fall };
+            *level += (to - *level) * rate;
+        }
+    }
+    // the pixels in palette colors, mixed between the two shades each one's
+    // between
+    pub fn rgba(&mut self, palette: [[u8; 4]; 4]) -> &[u8; SCRN_X * SCRN_Y * 4] {
+        for (rgba, &level) in self.rgba.chunks_exact_mut(4).zip(&self.levels) {
+            let low = (level as usize).min(2);
+            let t = level - low as f32;
+            let (from, to) = (palette[low], palette[low + 1]);
+            for (out, (&a, &b)) in rgba.iter_mut().zip(from.iter().zip(&to)) {
+                *out = (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+            }
+        }
+        &self.rgba
+    }
+}
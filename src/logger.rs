@@ -0,0 +1,88 @@
+// where the core's log messages go, filtered by subsystem with specs like
+//   warn,ppu=debug,mbc=trace
+// where a bare level is the default for targets without one of their own.
+// later parts win, so flags can add to the defaults. the core logs to cpu,
+// ppu, mbc, dma, ram, io and serial, and the frontend to rom
+use std::{
+    fs::File,
+    io::{Write, stderr},
+    sync::Mutex,
+};
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+// serial is info so test roms' results show up
+pub const DEFAULT_SPEC: &str = "warn,serial=info";
+
+struct Logger {
+    default: LevelFilter,
+    targets: Vec<(String, LevelFilter)>,
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl Logger {
+    fn filter(&self, target: &str) -> LevelFilter {
+        let filter = self.targets.iter().find(|(t, _)| t == target);
+        filter.map_or(self.default, |&(_, level)| level)
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter(metadata.target())
+    }
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let (level, target) = (record.level(), record.target());
+        let line = format!("{level} {target}: {}\n", record.args());
+        // there's nowhere left to report a failed write
+        let _ = self.out.lock().unwrap().write_all(line.as_bytes());
+    }
+    fn flush(&self) {
+        let _ = self.out.lock().unwrap().flush();
+    }
+}
+
+// sets up logging to stderr, or to path if there is one. only the first
+// call does anything
+pub fn init(spec: &str, path: Option<&str>) -> Result<(), String> {
+    let mut default = LevelFilter::Warn;
+    let mut targets: Vec<(String, LevelFilter)> = Vec::new();
+    for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (target, level) = match part.split_once('=') {
+            Some((target, level)) => (Some(target.trim()), level.trim()),
+            None => (None, part),
+        };
+        let Ok(level) = level.parse() else {
+            return Err(format!(
+                "Unknown log level '{level}', expected off, error, warn, info, debug or trace"
+            ));
+        };
+        match target {
+            Some(target) => {
+                targets.retain(|(t, _)| t != target);
+                targets.push((target.into(), level));
+            }
+            None => default = level,
+        }
+    }
+    let out: Box<dyn Write + Send> = match path {
+        Some(path) => match File::create(path) {
+            Ok(file) => Box::new(file),
+            Err(e) => return Err(format!("Unable to create {path}: {e}")),
+        },
+        None => Box::new(stderr()),
+    };
+    let max = targets.iter().map(|&(_, l)| l).fold(default, Ord::max);
+    let logger = Logger {
+        default,
+        targets,
+        out: Mutex::new(out),
+    };
+    if log::set_logger(Box::leak(Box::new(logger))).is_ok() {
+        log::set_max_level(max);
+    }
+    Ok(())
+}
@@ -0,0 +1,172 @@
+// input macros, for combos that are a pain to hit by hand. f9 starts
+// recording what's pressed and f9 again stops, then the next key pressed
+// gets the macro, which plays it back frame for frame. they're kept for
+// each rom in the config, like
+//   [macros.tetris]
+//   F = "a*2 -*10 a+b*3"
+// where each step is the buttons held, or - for none, and for how many
+// frames
+use std::iter::repeat_n;
+
+use gameboy::emulator::*;
+use sdl2::keyboard::Keycode;
+
+use crate::{bindings::Bindings, config::Config};
+
+// ten seconds, when recording stops on its own
+const MAX_FRAMES: usize = 600;
+// keys that already do something
+const HOTKEYS: [Keycode; 12] = [
+    Keycode::Escape,
+    Keycode::Tab,
+    Keycode::F1,
+    Keycode::F2,
+    Keycode::F3,
+    Keycode::F4,
+    Keycode::F5,
+    Keycode::F6,
+    Keycode::F8,
+    Keycode::F9,
+    Keycode::F10,
+    Keycode::F12,
+];
+
+// the buttons held each frame, a bit each like Emulator::buttons
+fn parse(text: &str) -> Option<Vec<u8>> {
+    let mut frames = Vec::new();
+    for step in text.split_whitespace() {
+        let (buttons, n) = step.split_once('*').unwrap_or((step, "1"));
+        let mut held = 0;
+        if buttons != "-" {
+            for name in buttons.split('+') {
+                held |= 1 << Button::from_name(name)? as u8;
+            }
+        }
+        frames.extend(repeat_n(held, n.parse().ok()?));
+    }
+    (!frames.is_empty() && frames.len() <= MAX_FRAMES).then_some(frames)
+}
+
+fn unparse(frames: &[u8]) -> String {
+    let steps = frames.chunk_by(|a, b| a == b).map(|run| {
+        let held = BUTTON_NAMES
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| run[0] & (1 << i) > 0)
+            .map(|(_, name)| *name)
+            .collect::<Vec<_>>();
+        let held = if held.is_empty() {
+            "-".into()
+        } else {
+            held.join("+")
+        };
+        format!("{held}*{}", run.len())
+    });
+    steps.collect::<Vec<_>>().join(" ")
+}
+
+pub struct Macros {
+    section: String,
+    bound: Vec<(Keycode, Vec<u8>)>,
+    recording: Option<Vec<u8>>,
+    // recorded and waiting for a key
+    unbound: Option<Vec<u8>>,
+    // which one's playing, and how far into it
+    playing: Option<(usize, usize)>,
+}
+
+impl Macros {
+    pub fn load(config: &Config, rom: &str) -> Result<Self, String> {
+        let section = format!("macros.{rom}");
+        let path = config.path().display();
+        let mut bound = Vec::new();
+        for (_, key, text) in config.entries().filter(|&(s, ..)| s == section) {
+            let Some(keycode) = Keycode::from_name(key) else {
+                return Err(format!("{path}: {section}: there's no key called {key}"));
+            };
+            let Some(frames) = parse(text) else {
+                return Err(format!(
+                    "{path}: {section}.{key}: expected steps like a+b*3"
+                ));
+            };
+            bound.push((keycode, frames));
+        }
+        Ok(Macros {
+            section,
+            bound,
+            recording: None,
+            unbound: None,
+            playing: None,
+        })
+    }
+    // starts recording, or stops and waits for a key
+    pub fn toggle_recording(&mut self) {
+        let Some(frames) = self.recording.take() else {
+            println!("Recording a macro, F9 stops");
+            self.recording = Some(Vec::new());
+            return;
+        };
+        // the wait before the first press and after the last don't count
+        let start = frames.iter().position(|&held| held != 0);
+        let end = frames.iter().rposition(|&held| held != 0);
+        match start.zip(end) {
+            Some((start, end)) => self.unbound = Some(frames[start..=end].into()),
+            None => println!("Nothing was pressed, so there's no macro"),
+        }
+    }
+    pub fn binding(&self) -> bool {
+        self.unbound.is_some()
+    }
+    pub fn cancel_binding(&mut self) -> bool {
+        self.unbound.take().is_some()
+    }
+    // gives the recorded macro to key, in place of any it had
+    pub fn bind(&mut self, key: Keycode, bindings: &Bindings, config: &mut Config) {
+        // 1 to 9 are the cheats
+        let digit = key.into_i32() - Keycode::NUM_1.into_i32();
+        if HOTKEYS.contains(&key) || (0..9).contains(&digit) || bindings.button(key).is_some() {
+            println!("{} is already used for something else", key.name());
+            return;
+        }
+        let frames = self.unbound.take().unwrap();
+        config.set(&self.section, &key.name(), &unparse(&frames));
+        self.playing = None;
+        match self.bound.iter_mut().find(|(k, _)| *k == key) {
+            Some(bound) => bound.1 = frames,
+            None => self.bound.push((key, frames)),
+        }
+    }
+    // starts the macro on key, if it has one
+    pub fn play(&mut self, key: Keycode) -> bool {
+        let Some(i) = self.bound.iter().position(|(k, _)| *k == key) else {
+            return false;
+        };
+        self.playing = Some((i, 0));
+        true
+    }
+    // sets the buttons for the frame about to run while one's playing, and
+    // records them. held is whether a button's key is down, for when it ends
+    pub fn apply(&mut self, emu: &mut Emulator, held: impl Fn(Button) -> bool) {
+        if let Some((i, frame)) = &mut self.playing {
+            let buttons = self.bound[*i].1.get(*frame).copied();
+            *frame += 1;
+            for button in Button::ALL {
+                let down = match buttons {
+                    Some(buttons) => buttons & (1 << button as u8) > 0,
+                    None => held(button),
+                };
+                emu.set_button(button, down);
+            }
+            if buttons.is_none() {
+                self.playing = None;
+            }
+        }
+        if let Some(frames) = &mut self.recording {
+            frames.push(emu.buttons());
+            if frames.len() == MAX_FRAMES {
+                println!("Stopped recording after {} seconds", MAX_FRAMES / 60);
+                self.toggle_recording();
+            }
+        }
+    }
+}
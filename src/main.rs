@@ -1,76 +1,1643 @@
+#[cfg(feature = "sdl")]
 extern crate sdl2;
 
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+#[cfg(feature = "gui")]
+use sdl2::event::WindowEvent;
+#[cfg(feature = "sdl")]
+use sdl2::{event::Event, keyboard::Keycode};
+#[cfg(feature = "sdl")]
+use std::time::{Duration, Instant};
 use std::{
     env::args,
-    fs::File,
+    fs::{self, File},
+    io::{ErrorKind, Read, stdin},
+    path::Path,
     process::ExitCode,
-    time::{Duration, Instant},
 };
 
-use crate::{display::*, emulator::*};
+#[cfg(feature = "sdl")]
+use crate::display::*;
+use gameboy::emulator::*;
 
+#[cfg(feature = "sdl")]
+mod audio;
+mod bench;
+#[cfg(feature = "sdl")]
+mod bindings;
+#[cfg(feature = "sdl")]
+mod cheats;
+#[cfg(feature = "sdl")]
+mod compare;
+#[cfg(feature = "sdl")]
+mod config;
+mod control;
+#[cfg(feature = "sdl")]
+mod crash;
+#[cfg(feature = "sdl")]
 mod display;
-mod emulator;
+mod frame_hashes;
+mod golden;
+#[cfg(feature = "gui")]
+mod gui;
+#[cfg(feature = "sdl")]
+mod input_record;
+mod input_script;
+#[cfg(feature = "sdl")]
+mod lcd;
+mod logger;
+#[cfg(feature = "sdl")]
+mod macros;
+#[cfg(feature = "sdl")]
+mod menu;
+#[cfg(feature = "sdl")]
+mod netplay;
+#[cfg(feature = "sdl")]
+mod output;
+#[cfg(feature = "sdl")]
+mod overlay;
+mod profile;
+#[cfg(feature = "remote")]
+mod remote;
+#[cfg(feature = "sdl")]
+mod rewind;
+#[cfg(feature = "sdl")]
+mod saves;
+#[cfg(feature = "sdl")]
+mod serial_console;
+mod serial_out;
+#[cfg(feature = "sdl")]
+mod serial_tcp;
+mod state_info;
+#[cfg(feature = "sdl")]
+mod timing;
+
+// a frame of cycles at the cpu clock, about 59.7275 fps
+#[cfg(feature = "sdl")]
+const FRAME_DUR: Duration =
+    Duration::from_nanos(constants::FRAME_CYCLES as u64 * 1_000_000_000 / constants::CLOCK_HZ);
 
 #[allow(unused_variables)]
+#[cfg_attr(not(feature = "sdl"), allow(unused_assignments))]
 fn main() -> ExitCode {
     let mut debug = false;
+    let mut log_spec = String::from(logger::DEFAULT_SPEC);
+    let mut log_file = None;
+    let mut dump_on_exit = false;
+    let mut export_vram = false;
+    let mut on_crash = None;
+    let mut renderer = Renderer::Fifo;
+    let mut layers = Layers::default();
+    let mut mbc = None;
+    let mut boot_rom = None;
+    let mut model = None;
+    let mut ram_init = None;
+    let mut frame_skip = 0;
+    let mut auto_skip = false;
+    let mut uncapped = false;
+    let mut lcd_response = None;
+    let mut poll_lines: Option<u32> = None;
+    let mut check_dma = false;
+    let mut check_uninit = false;
+    let mut strict_timing = false;
+    let mut timing_report = false;
+    let mut rtc_offset = 0;
+    let mut rtc_speed = 1;
+    let mut gui = false;
+    let mut config_path = None;
+    let mut configure_input = false;
+    let mut keys = None;
+    let mut profile = None;
+    let mut remote_addr = None;
+    let mut control = None;
+    let mut script = None;
+    let mut record_input = None;
+    let mut triggers = Vec::new();
+    let mut hashes = None;
+    let mut serial = None;
+    let mut compare = None;
+    let mut netplay = None;
+    let mut serial_link = None;
+    let mut barcodes = Vec::new();
+    let mut serial_console = false;
+    let mut input_delay = None;
+    let mut spectators = 0;
+    let mut bench = false;
+    let mut bench_frames = 600;
+    let mut disasm_rom = false;
+    let mut sym = None;
+    let mut cycle_profile = None;
+    let mut coverage = None;
+    let mut patches = Vec::new();
     let mut fname = None;
+    let mut golden = None;
+    let mut state_info = false;
+    let mut compression: Option<u8> = None;
     let exec_name = args().next().unwrap();
-    for arg in args().skip(1) {
+    let mut args = args().skip(1);
+    while let Some(arg) = args.next() {
         match arg.as_str() {
             "-d" | "--debug" => debug = true,
+            // writes that do nothing, which usually means a bug in the game
+            "--diagnostics" => log_spec += ",mbc=debug",
+            // warns about dma routines that wouldn't work on real hardware
+            "--check-dma" => check_dma = true,
+            // warns about reads of ram that would be garbage on real hardware
+            "--check-uninit" => check_uninit = true,
+            // skipping ahead with run times lines exactly, so it ends in the
+            // same state as running the frames one by one
+            "--strict-timing" => strict_timing = true,
+            "--log" => {
+                let Some(spec) = args.next() else {
+                    eprintln!("--log needs levels like warn,ppu=debug,mbc=trace");
+                    return ExitCode::FAILURE;
+                };
+                log_spec = format!("{log_spec},{spec}");
+            }
+            "--log-file" => {
+                let Some(path) = args.next() else {
+                    eprintln!("--log-file needs a file");
+                    return ExitCode::FAILURE;
+                };
+                log_file = Some(path);
+            }
+            "--dump-on-exit" => dump_on_exit = true,
+            "--export-vram" => export_vram = true,
+            "--on-crash" => match args.next().as_deref() {
+                Some("exit") => on_crash = Some(false),
+                Some("debug") => on_crash = Some(true),
+                _ => {
+                    eprintln!("--on-crash must be one of: exit, debug");
+                    return ExitCode::FAILURE;
+                }
+            },
+            "--gui" => gui = true,
+            // how far into the game's clock it starts, like 2d or 6h30m
+            "--rtc-offset" => {
+                let Some(secs) = args.next().as_deref().and_then(parse_duration) else {
+                    eprintln!("--rtc-offset needs a duration like 90, 45s, 2h or 1d12h30m");
+                    return ExitCode::FAILURE;
+                };
+                rtc_offset = secs;
+            }
+            "--rtc-speed" => {
+                let Some(speed) = args.next().and_then(|n| n.parse().ok()) else {
+                    eprintln!("--rtc-speed must be a number, with 0 freezing the clock");
+                    return ExitCode::FAILURE;
+                };
+                rtc_speed = speed;
+            }
+            "--configure-input" => configure_input = true,
+            // the controls from [keys.<name>] in the config. with
+            // --configure-input it sets up a new profile
+            "--keys" => {
+                let Some(name) = args.next() else {
+                    eprintln!("--keys needs a profile name");
+                    return ExitCode::FAILURE;
+                };
+                keys = Some(name);
+            }
+            "--config" => {
+                let Some(path) = args.next() else {
+                    eprintln!("--config needs a file");
+                    return ExitCode::FAILURE;
+                };
+                config_path = Some(path);
+            }
+            "--profile" => {
+                let Some(name) = args.next() else {
+                    eprintln!("--profile needs a name");
+                    return ExitCode::FAILURE;
+                };
+                profile = Some(name);
+            }
+            "--remote" => {
+                let Some(addr) = args.next() else {
+                    eprintln!("--remote needs an address to listen on, like 127.0.0.1:9001");
+                    return ExitCode::FAILURE;
+                };
+                remote_addr = Some(addr);
+            }
+            // link cable games over the network, see netplay.rs
+            "--netplay-host" | "--netplay-join" | "--netplay-watch" => {
+                let Some(addr) = args.next() else {
+                    eprintln!("{arg} needs an address, like 0.0.0.0:7845 or 192.168.1.2:7845");
+                    return ExitCode::FAILURE;
+                };
+                netplay = Some((arg, addr));
+            }
+            // the link port to another copy on the network, see serial_tcp.rs
+            "--serial-listen" | "--serial-connect" => {
+                let Some(addr) = args.next() else {
+                    eprintln!("{arg} needs an address, like 0.0.0.0:7846 or 192.168.1.2:7846");
+                    return ExitCode::FAILURE;
+                };
+                serial_link = Some((arg, addr));
+            }
+            // a barcode boy on the link port, swiping each card's 13 digit
+            // code in turn once the game's listening
+            "--barcode" => {
+                let Some(code) = args.next() else {
+                    eprintln!("--barcode needs a card's 13 digit code");
+                    return ExitCode::FAILURE;
+                };
+                barcodes.push(code);
+            }
+            // how many --netplay-watch the host waits for before starting. more
+            // can join once it has
+            "--spectators" => {
+                let Some(n) = args.next().and_then(|s| s.parse().ok()) else {
+                    eprintln!("--spectators must be a number");
+                    return ExitCode::FAILURE;
+                };
+                spectators = n;
+            }
+            // frames the controls take to arrive, which has to cover the
+            // round trip to the other player. the host's is used
+            "--input-delay" => {
+                let Some(frames) = args.next().and_then(|s| s.parse::<u8>().ok()) else {
+                    eprintln!("--input-delay must be a number of frames");
+                    return ExitCode::FAILURE;
+                };
+                input_delay = Some(frames);
+            }
+            "--control-socket" => {
+                let Some(target) = args.next() else {
+                    eprintln!("--control-socket needs a socket path, or - for stdin/stdout");
+                    return ExitCode::FAILURE;
+                };
+                control = Some(target);
+            }
+            // see input_script.rs. --replay-input is for --record-input's files
+            "--input-script" | "--replay-input" => {
+                let Some(path) = args.next() else {
+                    eprintln!("{arg} needs a file");
+                    return ExitCode::FAILURE;
+                };
+                match input_script::InputScript::load(&path) {
+                    Ok(s) => script = Some(s),
+                    Err(e) => {
+                        eprintln!("{e}");
+                        return ExitCode::FAILURE;
+                    }
+                }
+            }
+            "--record-input" => {
+                let Some(path) = args.next() else {
+                    eprintln!("--record-input needs a file");
+                    return ExitCode::FAILURE;
+                };
+                record_input = Some(path);
+            }
+            // screenshots or save states the moment something happens, see
+            // trigger.rs
+            "--trigger" | "--trigger-state" => {
+                let Some(spec) = args.next() else {
+                    eprintln!("{arg} needs a condition, like pc=$0150 or mem=$c0a0");
+                    return ExitCode::FAILURE;
+                };
+                let action = if arg == "--trigger" {
+                    TriggerAction::Screenshot
+                } else {
+                    TriggerAction::SaveState
+                };
+                match Trigger::parse(&spec) {
+                    Ok(trigger) => triggers.push((trigger, action)),
+                    Err(e) => {
+                        eprintln!("{e}");
+                        return ExitCode::FAILURE;
+                    }
+                }
+            }
+            "--hash-frames" => {
+                let Some(path) = args.next() else {
+                    eprintln!("--hash-frames needs a file, or - for stdout");
+                    return ExitCode::FAILURE;
+                };
+                match frame_hashes::FrameHashes::create(&path) {
+                    Ok(h) => hashes = Some(h),
+                    Err(e) => {
+                        eprintln!("Unable to create {path}: {e}");
+                        return ExitCode::FAILURE;
+                    }
+                }
+            }
+            // stdin and stdout on the link port, see serial_console.rs
+            "--serial-console" => {
+                serial_console = true;
+                // it's already going to stdout, so it isn't logged too
+                log_spec += ",serial=off";
+            }
+            "--serial-out" => {
+                let Some(target) = args.next() else {
+                    eprintln!("--serial-out needs a file, stdout or none");
+                    return ExitCode::FAILURE;
+                };
+                match serial_out::SerialOut::create(&target) {
+                    Ok(s) => serial = s,
+                    Err(e) => {
+                        eprintln!("Unable to create {target}: {e}");
+                        return ExitCode::FAILURE;
+                    }
+                }
+                // it's already going somewhere, so it isn't logged too
+                log_spec += ",serial=off";
+            }
+            "--renderer" | "--compare" => {
+                let r = match args.next().as_deref() {
+                    Some("fifo") => Renderer::Fifo,
+                    Some("scanline") => Renderer::Scanline,
+                    _ => {
+                        eprintln!("{arg} must be one of: fifo, scanline");
+                        return ExitCode::FAILURE;
+                    }
+                };
+                if arg == "--renderer" {
+                    renderer = r;
+                } else {
+                    compare = Some(r);
+                }
+            }
+            // prints what a save state file says about itself
+            "state-info" => state_info = true,
+            // how hard states and rewind snapshots get deflated, where 0
+            // leaves them be
+            "--state-compression" => {
+                let level = args.next().and_then(|n| n.parse().ok());
+                let Some(level) = level.filter(|&n| n <= 10) else {
+                    eprintln!("--state-compression must be 0 to 10");
+                    return ExitCode::FAILURE;
+                };
+                compression = Some(level);
+            }
+            // checks the screen against a png once the rom runs ld b,b
+            "--golden" => {
+                let Some(path) = args.next() else {
+                    eprintln!("--golden needs the expected png, or a directory of them");
+                    return ExitCode::FAILURE;
+                };
+                golden = Some(path);
+            }
+            // a comma separated list of bg, window and objects to not draw
+            "--hide" => {
+                let list = args.next().unwrap_or_default();
+                for layer in list.split(',') {
+                    match layer {
+                        "bg" => layers.bg = false,
+                        "window" => layers.window = false,
+                        "objects" => layers.objects = false,
+                        _ => {
+                            eprintln!("--hide takes a list of: bg, window, objects");
+                            return ExitCode::FAILURE;
+                        }
+                    }
+                }
+            }
+            "--mbc" => {
+                let Some(m) = args.next().as_deref().and_then(MbcKind::from_name) else {
+                    eprintln!("--mbc must be one of: {}", MBC_NAMES.join(", "));
+                    return ExitCode::FAILURE;
+                };
+                mbc = Some(m);
+            }
+            "--model" => {
+                let Some(m) = args.next().as_deref().and_then(Model::from_name) else {
+                    eprintln!("--model must be one of: {}", MODEL_NAMES.join(", "));
+                    return ExitCode::FAILURE;
+                };
+                model = Some(m);
+            }
+            "--ram-init" => {
+                let Some(init) = args.next().as_deref().and_then(parse_ram_init) else {
+                    eprintln!(
+                        "--ram-init must be zero, random or pattern, optionally with :<seed>"
+                    );
+                    return ExitCode::FAILURE;
+                };
+                ram_init = Some(init);
+            }
+            "--bootrom" => {
+                let Some(path) = args.next() else {
+                    eprintln!("--bootrom needs a file, or 'builtin'");
+                    return ExitCode::FAILURE;
+                };
+                if path == "builtin" {
+                    boot_rom = Some(BUILTIN_BOOT_ROM);
+                    continue;
+                }
+                match fs::read(&path).map(<[u8; 0x100]>::try_from) {
+                    Ok(Ok(rom)) => boot_rom = Some(rom),
+                    Ok(Err(rom)) => {
+                        eprintln!("{path} is {} bytes, but a boot rom is 256", rom.len());
+                        return ExitCode::FAILURE;
+                    }
+                    Err(e) => {
+                        eprintln!("Unable to read {path}: {e}");
+                        return ExitCode::FAILURE;
+                    }
+                }
+            }
+            "--frame-skip" => {
+                let n = args.next();
+                if n.as_deref() == Some("auto") {
+                    auto_skip = true;
+                } else if let Some(n) = n.and_then(|n| n.parse().ok()) {
+                    frame_skip = n;
+                } else {
+                    eprintln!("--frame-skip must be a number or 'auto'");
+                    return ExitCode::FAILURE;
+                }
+            }
+            "--uncapped" => uncapped = true,
+            // lcd.rs, in milliseconds to darken and to clear
+            "--lcd-response" => {
+                let spec = args.next().unwrap_or_default();
+                let (rise, fall) = spec.split_once(':').unwrap_or((&spec, &spec));
+                let ms = |s: &str| match s.parse() {
+                    Ok(ms) if (0.0..f32::INFINITY).contains(&ms) => Some(ms),
+                    _ => None,
+                };
+                let Some(times) = ms(rise).zip(ms(fall)) else {
+                    eprintln!("--lcd-response takes milliseconds, like 40 or 40:80");
+                    return ExitCode::FAILURE;
+                };
+                lcd_response = Some(times);
+            }
+            // prints how evenly frames were shown on exit
+            "--timing-report" => timing_report = true,
+            // reads the controls every n scanlines instead of once a frame
+            "--poll-lines" => {
+                let Some(n) = args.next().and_then(|n| n.parse().ok()).filter(|&n| n > 0) else {
+                    eprintln!("--poll-lines must be a number of scanlines above 0");
+                    return ExitCode::FAILURE;
+                };
+                poll_lines = Some(n);
+            }
+            // times each renderer on the rom instead of playing it
+            "bench-render" => bench = true,
+            // prints the rom as rgbds assembly instead of playing it
+            "disasm-rom" => disasm_rom = true,
+            "--sym" => {
+                let Some(path) = args.next() else {
+                    eprintln!("--sym needs a file");
+                    return ExitCode::FAILURE;
+                };
+                sym = Some(path);
+            }
+            // written on exit, see profile.rs
+            "--cycle-profile" => {
+                let Some(path) = args.next() else {
+                    eprintln!("--cycle-profile needs a file, like callgrind.out.game");
+                    return ExitCode::FAILURE;
+                };
+                cycle_profile = Some(path);
+            }
+            // a code/data log of the rom, written on exit. with disasm-rom
+            // it's read instead, to find code the listing otherwise can't
+            "--coverage" => {
+                let Some(path) = args.next() else {
+                    eprintln!("--coverage needs a file, like game.cdl");
+                    return ExitCode::FAILURE;
+                };
+                coverage = Some(path);
+            }
+            // applied to the rom in the order they're given
+            "--patch" => {
+                let Some(path) = args.next() else {
+                    eprintln!("--patch needs an .ips or .bps file");
+                    return ExitCode::FAILURE;
+                };
+                match fs::read(&path) {
+                    Ok(patch) => patches.push((path, patch)),
+                    Err(e) => {
+                        eprintln!("Unable to read {path}: {e}");
+                        return ExitCode::FAILURE;
+                    }
+                }
+            }
+            "--frames" => {
+                let Some(n) = args.next().and_then(|n| n.parse().ok()) else {
+                    eprintln!("--frames must be a number");
+                    return ExitCode::FAILURE;
+                };
+                bench_frames = n;
+            }
+            #[cfg(feature = "fuzz")]
+            "--fuzz-cpu" => {
+                let Some(n) = args.next().and_then(|n| n.parse().ok()) else {
+                    eprintln!("--fuzz-cpu must be a number");
+                    return ExitCode::FAILURE;
+                };
+                // a different seed each run, which gets printed on failure
+                let seed = std::time::UNIX_EPOCH.elapsed().unwrap().as_nanos() as u64;
+                return if fuzz_cpu(n, seed) {
+                    ExitCode::SUCCESS
+                } else {
+                    ExitCode::FAILURE
+                };
+            }
             _ if fname.is_none() => fname = Some(arg),
             _ => {
                 // eprintln!("Unknown option: '{arg}'");
-                eprintln!("Usage: {exec_name} [OPTIONS] <file>");
+                eprintln!("Usage: {exec_name} [OPTIONS] <file|->");
                 return ExitCode::FAILURE;
             }
         }
     }
+    if gui && !cfg!(feature = "gui") {
+        eprintln!("--gui needs a build with the gui feature");
+        return ExitCode::FAILURE;
+    }
+    if remote_addr.is_some() && !cfg!(feature = "remote") {
+        eprintln!("--remote needs a build with the remote feature");
+        return ExitCode::FAILURE;
+    }
+    if compare.is_some() && !cfg!(feature = "sdl") {
+        eprintln!("--compare needs a build with the sdl feature");
+        return ExitCode::FAILURE;
+    }
+    if netplay.is_some() && !cfg!(feature = "sdl") {
+        eprintln!("Netplay needs a build with the sdl feature");
+        return ExitCode::FAILURE;
+    }
+    if let Some((arg, _)) = &serial_link
+        && !cfg!(feature = "sdl")
+    {
+        eprintln!("{arg} needs a build with the sdl feature");
+        return ExitCode::FAILURE;
+    }
+    if record_input.is_some() && (control.is_some() || netplay.is_some()) {
+        eprintln!("--record-input only records playing on your own");
+        return ExitCode::FAILURE;
+    }
+    if !triggers.is_empty() && (control.is_some() || !cfg!(feature = "sdl")) {
+        eprintln!("--trigger only saves while playing, not with --control-socket");
+        return ExitCode::FAILURE;
+    }
+    if serial_link.is_some() && netplay.is_some() {
+        eprintln!("Netplay already has the link port");
+        return ExitCode::FAILURE;
+    }
+    if !barcodes.is_empty() && (serial_link.is_some() || netplay.is_some()) {
+        eprintln!("The barcode boy needs the link port to itself");
+        return ExitCode::FAILURE;
+    }
+    if lcd_response.is_some() && (control.is_some() || !cfg!(feature = "sdl")) {
+        eprintln!("--lcd-response only changes what's shown in the window");
+        return ExitCode::FAILURE;
+    }
+    if serial_console && !cfg!(feature = "sdl") {
+        eprintln!("--serial-console needs a build with the sdl feature");
+        return ExitCode::FAILURE;
+    }
+    let link_port_taken = serial_link.is_some() || netplay.is_some() || !barcodes.is_empty();
+    if serial_console && link_port_taken {
+        eprintln!("--serial-console needs the link port to itself");
+        return ExitCode::FAILURE;
+    }
+    let barcode = (!barcodes.is_empty()).then(BarcodeBoy::new);
+    for code in &barcodes {
+        if let Some(Err(e)) = barcode.as_ref().map(|reader| reader.scan(code)) {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    }
+    if let Err(e) = logger::init(&log_spec, log_file.as_deref()) {
+        eprintln!("{e}");
+        return ExitCode::FAILURE;
+    }
+    // with a debugger around it's more use than a crash report alone
+    let debug_on_crash = on_crash.unwrap_or(debug || gui);
+    #[cfg(feature = "sdl")]
+    let mut config = {
+        let path = config_path.map_or_else(config::Config::default_path, Into::into);
+        match config::Config::load(&path) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("{e}");
+                return ExitCode::FAILURE;
+            }
+        }
+    };
+    #[cfg(feature = "sdl")]
+    let rom_overrides = match rom_overrides(&config) {
+        Ok(overrides) => overrides,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    // what ram holds at power on, from [power] ram in the config if it's
+    // not on the command line
+    #[cfg(feature = "sdl")]
+    let ram_init = match ram_init {
+        Some(init) => init,
+        None => match config.get("power", "ram").map(parse_ram_init) {
+            Some(Some(init)) => init,
+            Some(None) => {
+                let path = config.path().display();
+                eprintln!(
+                    "{path}: power.ram must be zero, random or pattern, with an optional :<seed>"
+                );
+                return ExitCode::FAILURE;
+            }
+            None => RamInit::Zeroed,
+        },
+    };
+    #[cfg(not(feature = "sdl"))]
+    let ram_init = ram_init.unwrap_or_default();
+    // so a run that turned up something can be had again
+    if let RamInit::Random(seed) | RamInit::Pattern(seed) = ram_init {
+        log::info!("Filling ram from seed {seed}, which :{seed} after the setting repeats");
+    }
+    let capture_serial = serial.is_some();
+    let new_emu = || {
+        let mut emu = Emulator::with_debug_mode(debug);
+        emu.set_renderer(renderer);
+        emu.set_layers(layers);
+        emu.set_mbc(mbc);
+        emu.set_model(model);
+        emu.set_ram_init(ram_init);
+        #[cfg(feature = "sdl")]
+        emu.set_rom_overrides(&rom_overrides);
+        if let Some(boot_rom) = &boot_rom {
+            emu.set_boot_rom(boot_rom);
+        }
+        emu.set_frame_skip(frame_skip);
+        emu.set_determinism(DeterminismConfig {
+            rtc_start: rtc_offset,
+            rtc_speed,
+            strict_timing,
+        });
+        // breakpoints stop in the debugger window instead of the terminal
+        emu.set_pause_on_break(gui);
+        emu.set_trap_unimplemented(debug || gui);
+        emu.set_check_dma(check_dma);
+        emu.set_check_uninit(check_uninit);
+        emu.set_serial_capture(capture_serial);
+        emu.set_profiling(cycle_profile.is_some());
+        emu.set_coverage(coverage.is_some() && !disasm_rom);
+        if let Some(reader) = &barcode {
+            emu.set_serial_device(Some(Box::new(reader.clone())));
+        }
+        emu
+    };
+    // the rom is optional here since it can be loaded with a command
+    if let Some(target) = control {
+        let emu = match fname.map(|f| (load(new_emu(), &f, &patches), f)) {
+            Some((Ok(emu), f)) => Some((emu, f)),
+            Some((Err(code), _)) => return code,
+            None => None,
+        };
+        let reader = barcode.clone();
+        return control::run(&target, emu, new_emu, script, hashes, serial, reader);
+    }
     let Some(fname) = fname else {
-        eprintln!("Usage: {exec_name} [OPTIONS] <file>");
+        eprintln!("Usage: {exec_name} [OPTIONS] <file|->");
         return ExitCode::FAILURE;
     };
-    let Ok(mut program) = File::open(&fname) else {
-        eprintln!("Unable to open file: {fname}");
+    if state_info {
+        return state_info::print(&fname);
+    }
+    if disasm_rom {
+        return match load(new_emu(), &fname, &patches) {
+            Ok(emu) => disasm(&emu, &fname, sym, coverage.as_deref()),
+            Err(code) => code,
+        };
+    }
+    if bench {
+        return match load(new_emu(), &fname, &patches) {
+            Ok(emu) => bench::run(emu, new_emu, bench_frames),
+            Err(code) => code,
+        };
+    }
+    if let Some(expected) = golden {
+        return match load(new_emu(), &fname, &patches) {
+            Ok(emu) => golden::run(emu, &fname, Path::new(&expected)),
+            Err(code) => code,
+        };
+    }
+    if !cfg!(feature = "sdl") {
+        eprintln!("Running without --control-socket needs a build with the sdl feature");
         return ExitCode::FAILURE;
+    }
+    #[cfg_attr(not(feature = "sdl"), allow(unused_mut))]
+    let mut emu = match load(new_emu(), &fname, &patches) {
+        Ok(emu) => emu,
+        Err(code) => return code,
     };
-    let mut emu = Emulator::with_debug_mode(debug);
-    if emu.load(&mut program).is_err() {
-        eprintln!("Unable to read file: {fname}");
+    // cheats from a .cht file next to the rom, like tetris.cht
+    #[cfg(feature = "sdl")]
+    let mut cheats = match cheats::CheatList::load(&Path::new(&fname).with_extension("cht")) {
+        Ok(cheats) => cheats,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    #[cfg(feature = "sdl")]
+    let mut bindings = {
+        let keys = keys.as_deref().or(config.get("input", "profile"));
+        bindings::Bindings::load(&config, keys.unwrap_or(bindings::DEFAULT_PROFILE))
+    };
+    // battery saves and the quick save state, from --profile or the config's
+    // [saves] section
+    #[cfg(feature = "sdl")]
+    let profile = profile.or_else(|| config.get("saves", "profile").map(Into::into));
+    #[cfg(feature = "sdl")]
+    if let Some(name) = profile.as_deref().filter(|&p| !saves::valid_profile(p)) {
+        eprintln!("Profile names can only have letters, numbers, - and _, not '{name}'");
         return ExitCode::FAILURE;
     }
+    // there's nowhere to put a battery save for a rom from stdin
+    #[cfg(feature = "sdl")]
+    let save_file = {
+        let backups = config.get("saves", "backups");
+        let Ok(backups) = backups.map_or(Ok(saves::BACKUPS), str::parse) else {
+            let path = config.path().display();
+            eprintln!("{path}: saves.backups must be a number");
+            return ExitCode::FAILURE;
+        };
+        (fname != "-" && emu.has_battery())
+            .then(|| saves::SaveFile::new(Path::new(&fname), profile.as_deref(), backups))
+    };
+    // from [saves] state_compression in the config if it's not on the
+    // command line
+    #[cfg(feature = "sdl")]
+    let compression = match compression {
+        Some(level) => level,
+        None => match config.get("saves", "state_compression").map(str::parse) {
+            Some(Ok(level)) if level <= 10 => level,
+            Some(_) => {
+                let path = config.path().display();
+                eprintln!("{path}: saves.state_compression must be 0 to 10");
+                return ExitCode::FAILURE;
+            }
+            None => DEFAULT_COMPRESSION,
+        },
+    };
+    // how often the controls are read partway through a frame, which cuts
+    // up to a frame of lag for a bit more cpu
+    #[cfg(feature = "sdl")]
+    let poll_lines = match poll_lines {
+        Some(n) => Some(n),
+        None => match config.get("input", "poll_lines").map(str::parse) {
+            Some(Ok(n)) if n > 0 => Some(n),
+            Some(_) => {
+                let path = config.path().display();
+                eprintln!("{path}: input.poll_lines must be a number above 0");
+                return ExitCode::FAILURE;
+            }
+            None => None,
+        },
+    };
+    #[cfg(feature = "sdl")]
+    let audio_settings = match audio::AudioSettings::load(&config) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    #[cfg(feature = "sdl")]
+    if let Some(save) = &save_file {
+        match save.load() {
+            Ok(Some(data)) => emu.load_cart_ram(&data),
+            Ok(None) => {}
+            Err(e) => {
+                eprintln!("{e}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    // without cheats, which would only be on one end
+    #[cfg(feature = "sdl")]
+    if let Some((arg, addr)) = netplay {
+        let role = match arg.as_str() {
+            "--netplay-host" => netplay::Role::Host(addr, spectators),
+            "--netplay-join" => netplay::Role::Join(addr),
+            _ => netplay::Role::Watch(addr),
+        };
+        let delay = input_delay.unwrap_or(netplay::INPUT_DELAY);
+        return netplay::run(&role, delay, emu, new_emu, &bindings, save_file.as_ref());
+    }
+    #[cfg(feature = "sdl")]
+    if let Some((arg, addr)) = serial_link {
+        let bridge = match arg.as_str() {
+            "--serial-listen" => serial_tcp::TcpBridge::listen(&addr),
+            _ => serial_tcp::TcpBridge::connect(&addr),
+        };
+        match bridge {
+            Ok(bridge) => emu.set_serial_device(Some(Box::new(bridge))),
+            Err(e) => {
+                eprintln!("Unable to link over {addr}: {e}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    #[cfg(feature = "sdl")]
+    for &(trigger, action) in &triggers {
+        emu.add_trigger(trigger, action);
+    }
+    #[cfg(feature = "sdl")]
+    if serial_console {
+        emu.set_serial_device(Some(Box::new(serial_console::SerialConsole::new())));
+    }
+    #[cfg(feature = "sdl")]
+    let enabled = cheats.as_ref().map(cheats::CheatList::enabled);
+    #[cfg(feature = "sdl")]
+    emu.set_cheats(enabled.as_deref().unwrap_or_default());
+    // the same thing again on the right, with the other renderer
+    #[cfg(feature = "sdl")]
+    if let Some(renderer) = compare {
+        let mut other = new_emu();
+        other.set_renderer(renderer);
+        other.set_cheats(enabled.as_deref().unwrap_or_default());
+        return match load(other, &fname, &patches) {
+            Ok(other) => {
+                compare::run(emu, other, script);
+                ExitCode::SUCCESS
+            }
+            Err(code) => code,
+        };
+    }
+    // f5 and f8 save and load here
+    #[cfg(feature = "sdl")]
+    let state_path = match fname.as_str() {
+        "-" => "stdin.state".into(),
+        _ => saves::state_path(Path::new(&fname), profile.as_deref()),
+    };
+    #[cfg(feature = "remote")]
+    let remote = match remote_addr.as_deref().map(remote::Remote::listen) {
+        Some(Err(e)) => {
+            eprintln!("Unable to listen for remote control: {e}");
+            return ExitCode::FAILURE;
+        }
+        remote => remote.map(Result::unwrap),
+    };
+    // name files written after the rom, like tetris.vram.bin
+    let stem = match fname.as_str() {
+        "-" => "stdin".into(),
+        _ => Path::new(&fname)
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy(),
+    };
+    // where f12's screenshots go, see output.rs
+    #[cfg(feature = "sdl")]
+    let outputs = match output::OutputPaths::load(&config, &stem) {
+        Ok(outputs) => outputs,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    // f9's macros, from the config's section for the rom
+    #[cfg(feature = "sdl")]
+    let mut macros = match macros::Macros::load(&config, &stem) {
+        Ok(macros) => macros,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    #[cfg(feature = "sdl")]
+    let recorder = record_input.map(|path| input_record::InputRecorder::create(&path, &fname));
+    #[cfg(feature = "sdl")]
+    let mut recorder = match recorder {
+        Some(Err(e)) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+        recorder => recorder.map(Result::unwrap),
+    };
+    #[cfg_attr(not(feature = "sdl"), allow(unused_mut))]
+    let mut exit = ExitCode::SUCCESS;
+    #[cfg(feature = "sdl")]
     let mut disp = Display::new();
+    #[cfg(feature = "sdl")]
     disp.show();
-    const CYCLE_DUR: Duration = Duration::from_nanos(238);
+    // it runs fine without sound, so that's only a warning
+    #[cfg(feature = "sdl")]
+    let audio = disp.audio();
+    #[cfg(feature = "sdl")]
+    let mut audio = match audio.and_then(|a| audio::AudioOut::open(&a, &audio_settings)) {
+        Ok(audio) if emu.set_sample_rate(audio.freq()) => Some(audio),
+        Ok(audio) => {
+            eprintln!("Unable to play sound at {} Hz", audio.freq());
+            None
+        }
+        Err(e) => {
+            eprintln!("Unable to open audio: {e}");
+            None
+        }
+    };
+    #[cfg(feature = "gui")]
+    let mut debugger = gui.then(|| gui::DebugWindow::new(&disp.video()));
+    #[cfg(feature = "sdl")]
+    let mut rewind = rewind::Rewind::new(compression);
+    #[cfg(feature = "sdl")]
+    let mut rewinding = false;
+    // escape opens the pause menu, which stops the game while it's up
+    #[cfg(feature = "sdl")]
+    let mut pause_menu: Option<menu::Menu> = None;
+    #[cfg(feature = "sdl")]
+    let mut palette = 0;
+    // asks for a key for each button, then saves them to the config
+    #[cfg(feature = "sdl")]
+    let mut capture = configure_input.then(|| bindings::Capture::new(bindings.profile()));
+    // the menu was drawn over the frame, so it has to be uploaded again
+    #[cfg(feature = "sdl")]
+    let mut redraw = false;
+    // when the current frame should be done by
+    #[cfg(feature = "sdl")]
+    let mut deadline = Instant::now();
+    // frames since the speed in the title was last worked out
+    #[cfg(feature = "sdl")]
+    let mut speed = (Instant::now(), 0);
+    // the last speed worked out, and whether f3's overlay shows it
+    #[cfg(feature = "sdl")]
+    let mut percent = 100.0;
+    #[cfg(feature = "sdl")]
+    let mut show_overlay = false;
+    // uncapped runs far faster than there's any point drawing
+    #[cfg(feature = "sdl")]
+    let mut presented = Instant::now();
+    #[cfg(feature = "sdl")]
+    let mut frame_times = timing::FrameTimes::default();
+    #[cfg(feature = "sdl")]
+    let mut lcd = lcd_response.map(|(rise, fall)| lcd::LcdResponse::new(rise, fall));
+    #[cfg(feature = "sdl")]
     'running: loop {
-        let now = Instant::now();
         for event in disp.events() {
+            #[cfg(feature = "gui")]
+            if let Some(debugger) = &mut debugger {
+                // with two windows, closing one doesn't send Quit
+                if let Event::Window {
+                    win_event: WindowEvent::Close,
+                    ..
+                } = event
+                {
+                    break 'running;
+                }
+                if event.get_window_id() == Some(debugger.id()) {
+                    debugger.handle_event(&event);
+                    continue;
+                }
+            }
             match event {
-                Event::Quit { .. }
-                | Event::KeyDown {
+                Event::Quit { .. } => break 'running,
+                // escape backs out of setting the controls, and otherwise
+                // opens or closes the menu
+                Event::KeyDown {
                     keycode: Some(Keycode::Escape),
                     ..
-                } => break 'running,
+                } => {
+                    if capture.take().is_none() && !macros.cancel_binding() {
+                        pause_menu = match pause_menu {
+                            Some(_) => None,
+                            None => Some(menu::Menu::new()),
+                        };
+                    }
+                    // the key ups would go to the menu instead
+                    for button in Button::ALL {
+                        emu.set_button(button, false);
+                    }
+                    redraw = true;
+                }
+                Event::KeyDown {
+                    keycode: Some(key),
+                    repeat: false,
+                    ..
+                } if capture.is_some() => {
+                    if let Some(new) = capture.as_mut().unwrap().key(key) {
+                        bindings = new;
+                        bindings.save(&mut config);
+                        let path = config.path().display();
+                        match config.save() {
+                            Ok(()) => println!("Saved the controls to {path}"),
+                            Err(e) => eprintln!("Unable to write {path}: {e}"),
+                        }
+                        capture = None;
+                        redraw = true;
+                    }
+                }
+                Event::KeyDown {
+                    keycode: Some(key),
+                    repeat: false,
+                    ..
+                } if macros.binding() => {
+                    macros.bind(key, &bindings, &mut config);
+                    if !macros.binding() {
+                        let path = config.path().display();
+                        match config.save() {
+                            Ok(()) => println!("Saved the macro on {} to {path}", key.name()),
+                            Err(e) => eprintln!("Unable to write {path}: {e}"),
+                        }
+                        redraw = true;
+                    }
+                }
+                Event::KeyDown {
+                    keycode: Some(key), ..
+                } if pause_menu.is_some() => {
+                    let items = menu_items(palette, &bindings, cheats.as_ref());
+                    let Some(picked) = pause_menu.as_mut().unwrap().key(key, items.len()) else {
+                        continue;
+                    };
+                    match items[picked].0 {
+                        MenuAction::Resume => pause_menu = None,
+                        // a power cycle, which the battery keeps the cart ram through
+                        MenuAction::Reset => {
+                            emu.hard_reset();
+                            if let Some(script) = &mut script {
+                                script.restart();
+                            }
+                            pause_menu = None;
+                        }
+                        action @ (MenuAction::SaveState | MenuAction::LoadState) => {
+                            let save = matches!(action, MenuAction::SaveState);
+                            quick_state(&mut emu, &state_path, compression, save);
+                            pause_menu = None;
+                        }
+                        MenuAction::Palette => {
+                            palette = (palette + 1) % menu::PALETTES.len();
+                            emu.set_palette(menu::PALETTES[palette].1);
+                        }
+                        MenuAction::Keys => {
+                            bindings = bindings.next(&config);
+                            switch_profile(&mut emu, &bindings, &mut config);
+                        }
+                        MenuAction::Controls => {
+                            capture = Some(bindings::Capture::new(bindings.profile()));
+                            pause_menu = None;
+                        }
+                        MenuAction::Cheat(i) => {
+                            let list = cheats.as_mut().unwrap();
+                            list.toggle(i);
+                            emu.set_cheats(&list.enabled());
+                        }
+                        MenuAction::Quit => break 'running,
+                    }
+                    redraw = true;
+                }
+                // holding f6 rewinds
+                Event::KeyDown {
+                    keycode: Some(Keycode::F6),
+                    ..
+                }
+                | Event::KeyUp {
+                    keycode: Some(Keycode::F6),
+                    ..
+                } => rewinding = matches!(event, Event::KeyDown { .. }),
+                // f5 saves a state and f8 loads it back
+                Event::KeyDown {
+                    keycode: Some(key @ (Keycode::F5 | Keycode::F8)),
+                    repeat: false,
+                    ..
+                } => {
+                    let save = key == Keycode::F5;
+                    quick_state(&mut emu, &state_path, compression, save);
+                    redraw = true;
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F12),
+                    repeat: false,
+                    ..
+                } => {
+                    let path = outputs.path(output::Output::Screenshot, &emu);
+                    let saved = path.and_then(|path| {
+                        emu.screenshot(File::create(&path)?)?;
+                        Ok(path)
+                    });
+                    match saved {
+                        Ok(path) => println!("Saved a screenshot to {}", path.display()),
+                        Err(e) => eprintln!("Unable to save a screenshot: {e}"),
+                    }
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F3),
+                    repeat: false,
+                    ..
+                } => {
+                    show_overlay = !show_overlay;
+                    redraw = true;
+                }
+                // f1, f2 and f4 hide the bg, window and objects
+                Event::KeyDown {
+                    keycode: Some(key @ (Keycode::F1 | Keycode::F2 | Keycode::F4)),
+                    repeat: false,
+                    ..
+                } => {
+                    let mut layers = emu.layers();
+                    let (name, shown) = match key {
+                        Keycode::F1 => ("Background", &mut layers.bg),
+                        Keycode::F2 => ("Window", &mut layers.window),
+                        _ => ("Objects", &mut layers.objects),
+                    };
+                    *shown = !*shown;
+                    let state = if *shown { "shown" } else { "hidden" };
+                    println!("{name} {state}");
+                    emu.set_layers(layers);
+                }
+                // f10 goes to the next controls profile
+                Event::KeyDown {
+                    keycode: Some(Keycode::F10),
+                    repeat: false,
+                    ..
+                } => {
+                    bindings = bindings.next(&config);
+                    switch_profile(&mut emu, &bindings, &mut config);
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F9),
+                    repeat: false,
+                    ..
+                } => macros.toggle_recording(),
+                // tab turns the frame limiter off and on
+                Event::KeyDown {
+                    keycode: Some(Keycode::Tab),
+                    repeat: false,
+                    ..
+                } => {
+                    uncapped = !uncapped;
+                    deadline = Instant::now();
+                }
+                Event::KeyDown {
+                    keycode: Some(key), ..
+                }
+                | Event::KeyUp {
+                    keycode: Some(key), ..
+                } if bindings.button(key).is_some() => {
+                    let down = matches!(event, Event::KeyDown { .. });
+                    emu.set_button(bindings.button(key).unwrap(), down);
+                }
+                Event::KeyDown {
+                    keycode: Some(key),
+                    repeat: false,
+                    ..
+                } if macros.play(key) => {}
+                // 1 to 9 turn the cheats in the .cht file on and off
+                Event::KeyDown {
+                    keycode: Some(key),
+                    repeat: false,
+                    ..
+                } => {
+                    let n = key.into_i32() - Keycode::NUM_1.into_i32();
+                    if let Some(list) = &mut cheats
+                        && (0..9).contains(&n)
+                        && let Some((desc, on)) = list.toggle(n as usize)
+                    {
+                        let state = if on { "on" } else { "off" };
+                        println!("Cheat {} ({desc}) is {state}", n + 1);
+                        emu.set_cheats(&list.enabled());
+                    }
+                }
                 _ => {}
             }
         }
-        let t_cyc = emu.tick();
-        let elapsed = now.elapsed();
-        // println!("{:?}", elapsed);
-        let expected_time = t_cyc as u32 * CYCLE_DUR;
-        if elapsed < expected_time {
-            std::thread::sleep(expected_time - elapsed);
+        // even with the pause menu up, so the remote isn't left waiting
+        #[cfg(feature = "remote")]
+        if let Some(remote) = &remote {
+            remote.poll(&mut emu);
+        }
+        if capture.is_some() || pause_menu.is_some() || macros.binding() {
+            let mut rgba = *emu.framebuffer_rgba();
+            if let Some(capture) = &capture {
+                let lines = [capture.prompt(), "Escape cancels".into()];
+                menu::draw_box(&mut rgba, &lines, None);
+            } else if macros.binding() {
+                let lines = ["Press a key for the macro".into(), "Escape cancels".into()];
+                menu::draw_box(&mut rgba, &lines, None);
+            } else if let Some(pause_menu) = &pause_menu {
+                let items = menu_items(palette, &bindings, cheats.as_ref());
+                let labels: Vec<_> = items.into_iter().map(|(_, label)| label).collect();
+                pause_menu.draw(&mut rgba, &labels);
+            }
+            disp.update(&rgba);
+            disp.present();
+            wait_until(Instant::now() + FRAME_DUR);
+            deadline = Instant::now();
+            frame_times.pause();
+            continue;
+        }
+        deadline += FRAME_DUR;
+        if let Some(script) = &mut script {
+            script.apply(&mut emu);
+        }
+        macros.apply(&mut emu, |button| disp.key_down(bindings.key(button)));
+        if let Some(recorder) = &mut recorder {
+            recorder.record(&emu);
+        }
+        let started = Instant::now();
+        let ready = match poll_lines {
+            _ if rewinding => rewind.step(&mut emu),
+            Some(lines) => loop {
+                if let Some(ready) = emu.run_slice(lines.saturating_mul(constants::LINE_CYCLES)) {
+                    break ready;
+                }
+                if emu.quit_requested() || emu.paused() {
+                    break false;
+                }
+                // the events stay queued for the top of the next frame
+                disp.pump();
+                for button in Button::ALL {
+                    emu.set_button(button, disp.key_down(bindings.key(button)));
+                }
+            },
+            None => emu.run_frame(),
+        };
+        if let Some(serial) = &mut serial {
+            serial.drain(&mut emu);
+        }
+        for (i, fired) in emu.take_fired() {
+            let trigger = triggers[i].0;
+            let (kind, what, at) = match &fired {
+                Fired::Screenshot(capture) => {
+                    let at = format!("{trigger} on line {}", capture.line);
+                    (output::Output::Screenshot, "a screenshot", at)
+                }
+                Fired::State(_) => (output::Output::State, "a state", trigger.to_string()),
+            };
+            let saved = outputs.path(kind, &emu).and_then(|path| {
+                match &fired {
+                    Fired::Screenshot(capture) => emu.capture_png(capture, File::create(&path)?)?,
+                    Fired::State(state) => fs::write(&path, state)?,
+                }
+                Ok(path)
+            });
+            match saved {
+                Ok(path) => println!("{at}, saved {}", path.display()),
+                Err(e) => eprintln!("{at}, unable to save {what}: {e}"),
+            }
+        }
+        if emu.quit_requested() {
+            break;
+        }
+        if let Some(fault) = emu.take_fault() {
+            let path = format!("{stem}.crash.txt");
+            match crash::write_report(&emu, fault, &path) {
+                Ok(()) => eprintln!("{fault}, wrote a crash report to {path}"),
+                Err(e) => eprintln!("{fault}, and unable to write {path}: {e}"),
+            }
+            if !debug_on_crash {
+                exit = ExitCode::FAILURE;
+                break;
+            }
+            // the debugger window shows it paused on its own
+            if !gui {
+                emu.debug();
+                emu.resume();
+            }
+        }
+        // sitting in the terminal debugger isn't a slow frame
+        if (debug || debug_on_crash) && started.elapsed() > 4 * FRAME_DUR {
+            frame_times.pause();
+        }
+        if ready {
+            if !rewinding {
+                rewind.record(&emu);
+            }
+            if let Some(hashes) = &mut hashes {
+                hashes.record(&emu);
+            }
+            if let Some(lcd) = &mut lcd {
+                lcd.frame(emu.framebuffer());
+            }
+            // sound at a faster speed isn't worth hearing
+            let samples = emu.take_audio();
+            if let Some(audio) = &mut audio
+                && !uncapped
+            {
+                audio.push_frame(&samples);
+            }
+            // we're already late, so skip drawing the next one to catch up
+            if auto_skip && !uncapped && Instant::now() > deadline {
+                emu.skip_next_frame();
+            }
+            if uncapped && presented.elapsed() < FRAME_DUR {
+                // still has to be uploaded when one is next drawn
+                redraw |= emu.frame_changed();
+            } else {
+                // the overlay and a slow lcd change every frame, but otherwise
+                // don't bother uploading the texture if nothing changed
+                if show_overlay || lcd.is_some() {
+                    let mut rgba = match &mut lcd {
+                        Some(lcd) => *lcd.rgba(emu.palette()),
+                        None => *emu.framebuffer_rgba(),
+                    };
+                    if show_overlay {
+                        let audio = audio.as_ref().map(audio::AudioOut::stats);
+                        overlay::draw(&mut rgba, percent, &frame_times, audio, &rewind);
+                    }
+                    disp.update(&rgba);
+                } else if emu.frame_changed() || std::mem::take(&mut redraw) {
+                    disp.update_with(0, |pixels, pitch| emu.write_rgba(pixels, pitch));
+                }
+                disp.present();
+                presented = Instant::now();
+                frame_times.present(presented, emu.frame_count());
+            }
+            // as a percentage of a real game boy, once a second
+            speed.1 += 1;
+            let elapsed = speed.0.elapsed();
+            if elapsed >= Duration::from_secs(1) {
+                let ran = speed.1 as f64 * FRAME_DUR.as_secs_f64();
+                percent = ran / elapsed.as_secs_f64() * 100.0;
+                disp.set_title(&format!("Gameboy ({percent:.0}%)"));
+                speed = (Instant::now(), 0);
+            }
+        }
+        if emu.paused() {
+            frame_times.pause();
+        }
+        #[cfg(feature = "gui")]
+        if let Some(debugger) = &mut debugger {
+            let audio = audio.as_ref().map(audio::AudioOut::stats);
+            debugger.draw(&mut emu, &frame_times, audio);
+        }
+        let now = Instant::now();
+        if uncapped {
+            deadline = now;
+        } else if now < deadline {
+            wait_until(deadline);
+        } else if now - deadline > 4 * FRAME_DUR {
+            // way too far behind (or stopped in the debugger), so don't try
+            // to catch up
+            deadline = now;
+        }
+    }
+    #[cfg(feature = "sdl")]
+    if let Some(path) = &cycle_profile {
+        let symbols = read_symbols(&fname, sym.as_deref());
+        if let Err(e) = symbols.and_then(|symbols| profile::write(&emu, path, &symbols, &fname)) {
+            eprintln!("{e}");
+        }
+    }
+    #[cfg(feature = "sdl")]
+    if let Some(path) = &coverage
+        && let Err(e) = write_coverage(&emu, path)
+    {
+        eprintln!("{e}");
+    }
+    #[cfg(feature = "sdl")]
+    if timing_report {
+        println!("{}", frame_times.report());
+        if let Some(stats) = audio.as_ref().map(audio::AudioOut::stats) {
+            println!("{}", stats.report());
+        }
+    }
+    #[cfg(feature = "sdl")]
+    if let Some(save) = &save_file
+        && let Err(e) = save.write(&emu.memory_region("sram").unwrap())
+    {
+        eprintln!("Unable to write {}: {e}", save.path().display());
+    }
+    if dump_on_exit {
+        for region in MEMORY_REGIONS {
+            let path = format!("{stem}.{region}.bin");
+            if let Err(e) = fs::write(&path, emu.memory_region(region).unwrap()) {
+                eprintln!("Unable to write {path}: {e}");
+            }
+        }
+    }
+    if export_vram {
+        for image in VRAM_IMAGES {
+            let path = format!("{stem}.{image}.png");
+            if let Err(e) = File::create(&path).and_then(|f| emu.export_vram(image, f)) {
+                eprintln!("Unable to write {path}: {e}");
+            }
+        }
+    }
+    exit
+}
+
+// what picking an item in the pause menu does
+#[cfg(feature = "sdl")]
+#[derive(Clone, Copy)]
+enum MenuAction {
+    Resume,
+    Reset,
+    SaveState,
+    LoadState,
+    Palette,
+    Keys,
+    Controls,
+    // toggles the cheat with this index
+    Cheat(usize),
+    Quit,
+}
+
+// resume, reset, save and load state, palette, keys profile, controls, a
+// line per cheat, then quit
+#[cfg(feature = "sdl")]
+fn menu_items(
+    palette: usize,
+    bindings: &bindings::Bindings,
+    cheats: Option<&cheats::CheatList>,
+) -> Vec<(MenuAction, String)> {
+    let mut items = vec![
+        (MenuAction::Resume, "Resume".into()),
+        (MenuAction::Reset, "Reset".into()),
+        (MenuAction::SaveState, "Save state".into()),
+        (MenuAction::LoadState, "Load state".into()),
+        (
+            MenuAction::Palette,
+            format!("Palette: {}", menu::PALETTES[palette].0),
+        ),
+        (MenuAction::Keys, format!("Keys: {}", bindings.profile())),
+        (MenuAction::Controls, "Controls".into()),
+    ];
+    for (i, (desc, on)) in cheats.into_iter().flat_map(|c| c.entries()).enumerate() {
+        let state = if on { "on" } else { "off" };
+        items.push((
+            MenuAction::Cheat(i),
+            format!("Cheat {}: {desc} ({state})", i + 1),
+        ));
+    }
+    items.push((MenuAction::Quit, "Quit".into()));
+    items
+}
+
+// the quick save state, for f5, f8 and the pause menu
+#[cfg(feature = "sdl")]
+fn quick_state(emu: &mut Emulator, path: &Path, compression: u8, save: bool) {
+    let path_str = path.display();
+    if save {
+        match File::create(path).and_then(|f| emu.save_state(f, compression)) {
+            Ok(()) => println!("Saved a state to {path_str}"),
+            Err(e) => eprintln!("Unable to write {path_str}: {e}"),
+        }
+    } else {
+        let file = File::open(path).map_err(StateError::Io);
+        match file.and_then(|f| emu.load_state(f)) {
+            Ok(()) => println!("Loaded the state from {path_str}"),
+            Err(e) => eprintln!("Unable to load {path_str}: {e}"),
         }
-        // present frame if ready
-        if emu.frame_ready() {
-            disp.update(emu.framebuffer());
-            // std::thread::sleep(Duration::from_secs(2));
-            // break;
+    }
+}
+
+// makes bindings' profile the one in use from now on
+#[cfg(feature = "sdl")]
+fn switch_profile(emu: &mut Emulator, bindings: &bindings::Bindings, config: &mut config::Config) {
+    // the new keys' key ups wouldn't release what the old ones pressed
+    for button in Button::ALL {
+        emu.set_button(button, false);
+    }
+    config.set("input", "profile", bindings.profile());
+    let path = config.path().display();
+    match config.save() {
+        Ok(()) => println!("Using the {} controls", bindings.profile()),
+        Err(e) => eprintln!("Unable to write {path}: {e}"),
+    }
+}
+
+// zero, random or pattern, where the last two take a seed after a colon to
+// get the same ram again. without one it's different every run
+fn parse_ram_init(s: &str) -> Option<RamInit> {
+    let (kind, seed) = match s.split_once(':') {
+        Some((kind, seed)) => (kind, seed.parse().ok()?),
+        None => (
+            s,
+            std::time::UNIX_EPOCH.elapsed().unwrap().as_nanos() as u64,
+        ),
+    };
+    match kind {
+        "zero" => Some(RamInit::Zeroed),
+        "random" => Some(RamInit::Random(seed)),
+        "pattern" => Some(RamInit::Pattern(seed)),
+        _ => None,
+    }
+}
+
+// [roms.<crc32>] sections of the config, with any of mbc, model and
+// battery, for carts the built in rom database doesn't know. the crc32 is
+// logged to rom at debug level when a rom loads
+#[cfg(feature = "sdl")]
+fn rom_overrides(config: &config::Config) -> Result<Vec<(u32, RomInfo)>, String> {
+    let path = config.path().display();
+    let mut overrides: Vec<(u32, RomInfo)> = Vec::new();
+    for (section, key, value) in config.entries() {
+        let Some(crc) = section.strip_prefix("roms.") else {
+            continue;
+        };
+        let Ok(crc) = u32::from_str_radix(crc, 16) else {
+            return Err(format!("{path}: [{section}] needs a crc32 in hex"));
+        };
+        let i = match overrides.iter().position(|&(c, _)| c == crc) {
+            Some(i) => i,
+            None => {
+                overrides.push((crc, RomInfo::default()));
+                overrides.len() - 1
+            }
+        };
+        let info = &mut overrides[i].1;
+        let bad = || format!("{path}: {section}.{key} can't be \"{value}\"");
+        match key {
+            "mbc" => info.mbc = Some(MbcKind::from_name(value).ok_or_else(bad)?),
+            "model" => info.model = Some(Model::from_name(value).ok_or_else(bad)?),
+            "battery" => info.battery = Some(value.parse().map_err(|_| bad())?),
+            _ => return Err(format!("{path}: unknown setting {section}.{key}")),
         }
     }
+    Ok(overrides)
+}
+
+// labels come from sym, or a .sym next to the rom if there is one
+fn read_symbols(fname: &str, sym: Option<&str>) -> Result<Symbols, String> {
+    let path = sym.map_or_else(
+        || {
+            let path = Path::new(fname).with_extension("sym");
+            path.to_string_lossy().into_owned()
+        },
+        Into::into,
+    );
+    match fs::read_to_string(&path) {
+        Ok(text) => Ok(Symbols::parse(&text)),
+        Err(e) if e.kind() == ErrorKind::NotFound && fname != path => Ok(Symbols::default()),
+        Err(e) => Err(format!("Unable to read {path}: {e}")),
+    }
+}
+
+// --coverage's code/data log
+fn write_coverage(emu: &Emulator, path: &str) -> Result<(), String> {
+    let coverage = emu.coverage().ok_or("coverage isn't on")?;
+    let cdl = coverage.cdl(emu.rom());
+    fs::write(path, cdl).map_err(|e| format!("Unable to write {path}: {e}"))
+}
+
+fn disasm(emu: &Emulator, fname: &str, sym: Option<String>, cdl: Option<&str>) -> ExitCode {
+    let symbols = match read_symbols(fname, sym.as_deref()) {
+        Ok(symbols) => symbols,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let cdl = match cdl.map(fs::read).transpose() {
+        Ok(cdl) => cdl.unwrap_or_default(),
+        Err(e) => {
+            eprintln!("Unable to read {}: {e}", cdl.unwrap());
+            return ExitCode::FAILURE;
+        }
+    };
+    print!("{}", rom_listing(emu.rom(), &symbols, &cdl));
     ExitCode::SUCCESS
 }
+
+// - reads the rom from stdin. patches are paths and their contents
+fn load(
+    mut emu: Emulator,
+    fname: &str,
+    patches: &[(String, Vec<u8>)],
+) -> Result<Emulator, ExitCode> {
+    let mut rom = Vec::new();
+    let read = if fname == "-" {
+        stdin().lock().read_to_end(&mut rom)
+    } else {
+        let Ok(mut program) = File::open(fname) else {
+            eprintln!("Unable to open file: {fname}");
+            return Err(ExitCode::FAILURE);
+        };
+        program.read_to_end(&mut rom)
+    };
+    if let Err(e) = read {
+        eprintln!("Unable to load {fname}: {e}");
+        return Err(ExitCode::FAILURE);
+    }
+    for (path, patch) in patches {
+        match apply_patch(&rom, patch) {
+            Ok(patched) => rom = patched,
+            Err(e) => {
+                eprintln!("Unable to apply {path}: {e}");
+                return Err(ExitCode::FAILURE);
+            }
+        }
+    }
+    match emu.load_bytes(&rom) {
+        Ok(warnings) => {
+            for warning in warnings {
+                log::warn!(target: "rom", "{fname}: {warning}");
+            }
+        }
+        Err(e) => {
+            eprintln!("Unable to load {fname}: {e}");
+            if matches!(e, RomError::UnsupportedMbc(_)) {
+                eprintln!("--mbc can force one of: {}", MBC_NAMES.join(", "));
+            }
+            return Err(ExitCode::FAILURE);
+        }
+    }
+    Ok(emu)
+}
+
+// sleeps most of the way there, then spins since sleep isn't very precise
+#[cfg(feature = "sdl")]
+fn wait_until(deadline: Instant) {
+    const SPIN: Duration = Duration::from_millis(1);
+    let now = Instant::now();
+    if deadline - now > SPIN {
+        std::thread::sleep(deadline - now - SPIN);
+    }
+    while Instant::now() < deadline {
+        std::hint::spin_loop();
+    }
+}
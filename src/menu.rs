@@ -0,0 +1,161 @@
+// the pause menu, drawn over the game with a tiny font of its own. main
+// decides what the items are and what picking one does
+use gameboy::emulator::constants::*;
+use sdl2::keyboard::Keycode;
+
+// palettes the menu cycles through, the first being the default
+pub const PALETTES: [(&str, [[u8; 4]; 4]); 3] = [
+    ("green", DEFAULT_PALETTE),
+    (
+        "grey",
+        [
+            [0xFF, 0xFF, 0xFF, 0xFF],
+            [0xAA, 0xAA, 0xAA, 0xFF],
+            [0x55, 0x55, 0x55, 0xFF],
+            [0x00, 0x00, 0x00, 0xFF],
+        ],
+    ),
+    (
+        "pocket",
+        [
+            [0xC4, 0xCF, 0xA1, 0xFF],
+            [0x8B, 0x95, 0x6D, 0xFF],
+            [0x4D, 0x53, 0x3C, 0xFF],
+            [0x1F, 0x1F, 0x1F, 0xFF],
+        ],
+    ),
+];
+
+// 3x5 glyphs, a row per 3 bits with the top row highest
+const LETTERS: [u16; 26] = [
+    0b010_101_111_101_101,
+    0b110_101_110_101_110,
+    0b011_100_100_100_011,
+    0b110_101_101_101_110,
+    0b111_100_110_100_111,
+    0b111_100_110_100_100,
+    0b011_100_101_101_011,
+    0b101_101_111_101_101,
+    0b111_010_010_010_111,
+    0b001_001_001_101_010,
+    0b101_101_110_101_101,
+    0b100_100_100_100_111,
+    0b101_111_111_101_101,
+    0b110_101_101_101_101,
+    0b010_101_101_101_010,
+    0b110_101_110_100_100,
+    0b010_101_101_110_011,
+    0b110_101_110_101_101,
+    0b011_100_010_001_110,
+    0b111_010_010_010_010,
+    0b101_101_101_101_111,
+    0b101_101_101_101_010,
+    0b101_101_111_111_101,
+    0b101_101_010_101_101,
+    0b101_101_010_010_010,
+    0b111_001_010_100_111,
+];
+const DIGITS: [u16; 10] = [
+    0b111_101_101_101_111,
+    0b010_110_010_010_111,
+    0b110_001_010_100_111,
+    0b110_001_010_001_110,
+    0b101_101_111_001_001,
+    0b111_100_110_001_110,
+    0b011_100_111_101_111,
+    0b111_001_010_010_010,
+    0b111_101_111_101_111,
+    0b111_101_111_001_110,
+];
+
+fn glyph(c: char) -> u16 {
+    match c.to_ascii_uppercase() {
+        c @ 'A'..='Z' => LETTERS[c as usize - 'A' as usize],
+        c @ '0'..='9' => DIGITS[c as usize - '0' as usize],
+        ' ' => 0,
+        ':' => 0b000_010_000_010_000,
+        '.' => 0b000_000_000_000_010,
+        ',' => 0b000_000_000_010_100,
+        '-' => 0b000_000_111_000_000,
+        '+' => 0b000_010_111_010_000,
+        '(' => 0b001_010_010_010_001,
+        ')' => 0b100_010_010_010_100,
+        '>' => 0b100_010_001_010_100,
+        '/' => 0b001_001_010_100_100,
+        '!' => 0b010_010_010_000_010,
+        '\'' => 0b010_010_000_000_000,
+        '%' => 0b101_001_010_100_101,
+        _ => 0b110_001_010_000_010,
+    }
+}
+
+const TEXT: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+pub const BOX: [u8; 4] = [0x10, 0x10, 0x10, 0xFF];
+// a glyph and the space after it
+pub const CHAR_X: usize = 4;
+const LINE_Y: usize = 8;
+// the most that fits in the box, counting the cursor
+const MAX_CHARS: usize = SCRN_X / CHAR_X - 4;
+
+pub struct Menu {
+    selected: usize,
+}
+
+impl Menu {
+    pub fn new() -> Self {
+        Menu { selected: 0 }
+    }
+    // up and down move through the items, and enter picks one, returning
+    // which
+    pub fn key(&mut self, key: Keycode, items: usize) -> Option<usize> {
+        match key {
+            Keycode::Up => self.selected = (self.selected + items - 1) % items,
+            Keycode::Down => self.selected = (self.selected + 1) % items,
+            Keycode::Return | Keycode::KpEnter | Keycode::Space => return Some(self.selected),
+            _ => {}
+        }
+        None
+    }
+    pub fn draw(&self, rgba: &mut [u8; SCRN_X * SCRN_Y * 4], items: &[String]) {
+        draw_box(rgba, items, Some(self.selected));
+    }
+}
+
+// darkens the frame and draws lines in a box in the middle of it, with a
+// cursor by the selected one
+pub fn draw_box(rgba: &mut [u8; SCRN_X * SCRN_Y * 4], lines: &[String], selected: Option<usize>) {
+    for px in rgba.chunks_exact_mut(4) {
+        px[..3].iter_mut().for_each(|c| *c /= 3);
+    }
+    let longest = lines.iter().map(|s| s.chars().count()).max().unwrap_or(0);
+    let width = (longest.min(MAX_CHARS) + 2) * CHAR_X + 4;
+    let height = lines.len() * LINE_Y + 4;
+    let (left, top) = ((SCRN_X - width) / 2, SCRN_Y.saturating_sub(height) / 2);
+    for y in top..(top + height).min(SCRN_Y) {
+        for x in left..left + width {
+            rgba[(y * SCRN_X + x) * 4..][..4].copy_from_slice(&BOX);
+        }
+    }
+    for (i, line) in lines.iter().enumerate() {
+        let y = top + 3 + i * LINE_Y;
+        let cursor = if selected == Some(i) { '>' } else { ' ' };
+        let chars = [cursor, ' ']
+            .into_iter()
+            .chain(line.chars().take(MAX_CHARS));
+        for (j, c) in chars.enumerate() {
+            draw_char(rgba, left + 3 + j * CHAR_X, y, c);
+        }
+    }
+}
+
+pub fn draw_char(rgba: &mut [u8; SCRN_X * SCRN_Y * 4], x: usize, y: usize, c: char) {
+    let glyph = glyph(c);
+    for row in 0..5 {
+        for col in 0..3 {
+            let (px, py) = (x + col, y + row);
+            if glyph >> (14 - row * 3 - col) & 1 > 0 && px < SCRN_X && py < SCRN_Y {
+                rgba[(py * SCRN_X + px) * 4..][..4].copy_from_slice(&TEXT);
+            }
+        }
+    }
+}
@@ -0,0 +1,525 @@
+// link cable games between two players. both ends run both game boys, with
+// a cable between them locally, so only the controls go over the network
+// and lag never reaches the cable. each end sends its controls for a frame
+// input_delay frames ahead of when they're needed, so the other end has
+// them in time, and every HASH_FRAMES frames the two ends swap hashes of
+// both game boys to catch them drifting apart.
+//
+// spectators connect to the host whenever they like. they get a deflated
+// state of both game boys and the cable, then every input the host applies
+// along with its hashes. that's enough to run both game boys in step and
+// show them side by side. every STATE_FRAMES frames they all get a fresh
+// state, so one that drifts from the host says on which frame, then gets
+// put right
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Write},
+    iter::zip,
+    net::{SocketAddr, TcpListener, TcpStream},
+    process::ExitCode,
+    sync::mpsc::{Sender, channel},
+    thread,
+    time::{Duration, Instant},
+};
+
+use gameboy::emulator::*;
+use sdl2::{event::Event, keyboard::Keycode};
+
+use crate::{FRAME_DUR, bindings::Bindings, display::Display, saves::SaveFile, wait_until};
+
+// what player and spectator streams start with, then the version
+const PLAYER: &[u8; 4] = b"SBNP";
+const SPECTATOR: &[u8; 4] = b"SBSP";
+const VERSION: u8 = 3;
+// the input delay when the host doesn't give one, about 33ms
+pub const INPUT_DELAY: u8 = 2;
+const HASH_FRAMES: u64 = 60;
+const STATE_FRAMES: u64 = 600;
+// far more than a deflated state comes to
+const MAX_STATE: u32 = 1 << 20;
+// how long the other end can go quiet before it's taken to have gone
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+// the byte each message starts with. input is followed by which game boy
+// and its buttons, hash by the frame and hash, and state by the two game
+// boys' states and the cable's, each with its length in front
+const INPUT: u8 = b'I';
+const HASH: u8 = b'H';
+const STATE: u8 = b'S';
+const QUIT: u8 = b'Q';
+
+pub enum Role {
+    // listens on the address for the other player and waits for this many
+    // spectators before starting, and plays the left game boy
+    Host(String, usize),
+    Join(String),
+    Watch(String),
+}
+
+struct Peer {
+    // the other player, or the host for a spectator
+    stream: TcpStream,
+    // spectators only listen, so don't send their hashes back
+    player: bool,
+    // the frame and combined hash from each end, oldest first
+    ours: VecDeque<(u64, u64)>,
+    theirs: VecDeque<(u64, u64)>,
+    // a writer thread each, so a slow one can't hold the game up
+    spectators: Vec<Sender<Vec<u8>>>,
+    // the host keeps listening for spectators, who wait here for a state
+    listener: Option<TcpListener>,
+    joining: Vec<TcpStream>,
+    // the last state a spectator got from the host, yet to be loaded
+    state: Option<[Vec<u8>; 3]>,
+}
+
+fn read_bytes<const N: usize>(r: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    r.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+// the state hash and cart ram of a game boy, so the other end can check
+// it's running the same thing and start from the same save
+fn write_console(out: &mut Vec<u8>, emu: &Emulator) {
+    let sram = emu.memory_region("sram").unwrap();
+    out.extend(emu.state_hash().to_le_bytes());
+    out.extend((sram.len() as u32).to_le_bytes());
+    out.extend(&*sram);
+}
+
+// makes the game boy write_console described, from emu's rom
+fn read_console(
+    stream: &mut TcpStream,
+    emu: &Emulator,
+    new_emu: &impl Fn() -> Emulator,
+) -> Result<Emulator, String> {
+    let err = |e: io::Error| format!("Unable to start netplay: {e}");
+    let hash = u64::from_le_bytes(read_bytes(stream).map_err(err)?);
+    let len = u32::from_le_bytes(read_bytes(stream).map_err(err)?) as usize;
+    // the same rom has the same amount of cart ram
+    if len > emu.memory_region("sram").unwrap().len() {
+        return Err(format!("Got {len} bytes of cart ram from the other end"));
+    }
+    let mut sram = vec![0; len];
+    stream.read_exact(&mut sram).map_err(err)?;
+    let mut other = new_emu();
+    let _ = other.load_bytes(emu.rom());
+    other.load_cart_ram(&sram);
+    if other.state_hash() != hash {
+        return Err("The other end has a different rom or settings".into());
+    }
+    Ok(other)
+}
+
+// waits for the other player and the spectators, in any order. the
+// listener's left nonblocking for spectators who come later
+fn host(addr: &str, watchers: usize) -> Result<(TcpStream, Vec<TcpStream>, TcpListener), String> {
+    let err = |e: io::Error| format!("Unable to start netplay: {e}");
+    let listener = TcpListener::bind(addr).map_err(err)?;
+    match watchers {
+        0 => println!("Waiting for the other player on {addr}"),
+        _ => println!("Waiting for the other player and {watchers} spectators on {addr}"),
+    }
+    let (mut player, mut spectators) = (None, Vec::new());
+    while player.is_none() || spectators.len() < watchers {
+        let (mut stream, from) = listener.accept().map_err(err)?;
+        match read_bytes::<5>(&mut stream) {
+            Ok([magic @ .., VERSION]) if &magic == PLAYER && player.is_none() => {
+                // the same back, so the other player knows it got in
+                stream.write_all(PLAYER).map_err(err)?;
+                stream.write_all(&[VERSION]).map_err(err)?;
+                player = Some(stream);
+            }
+            Ok([magic @ .., VERSION]) if &magic == SPECTATOR => {
+                println!("{from} is watching");
+                spectators.push(stream);
+            }
+            _ => turn_away(from),
+        }
+    }
+    listener.set_nonblocking(true).map_err(err)?;
+    Ok((player.unwrap(), spectators, listener))
+}
+
+fn turn_away(from: SocketAddr) {
+    eprintln!("Turned away {from}, which isn't a player or spectator for this version")
+}
+
+// sends a spectator what it's given on a thread of its own
+fn writer(mut stream: TcpStream) -> Sender<Vec<u8>> {
+    let (tx, rx) = channel::<Vec<u8>>();
+    thread::spawn(move || {
+        for msg in rx {
+            if stream.write_all(&msg).is_err() {
+                break;
+            }
+        }
+    });
+    tx
+}
+
+fn connect(addr: &str) -> Result<TcpStream, String> {
+    let err = |e: io::Error| format!("Unable to start netplay: {e}");
+    let mut stream = TcpStream::connect(addr).map_err(err)?;
+    stream.write_all(PLAYER).map_err(err)?;
+    stream.write_all(&[VERSION]).map_err(err)?;
+    match read_bytes::<5>(&mut stream) {
+        Ok([magic @ .., VERSION]) if &magic == PLAYER => Ok(stream),
+        _ => Err("The host turned this end away, it may be running another version".into()),
+    }
+}
+
+impl Peer {
+    fn send(&mut self, msg: &[u8]) -> Result<(), String> {
+        self.stream
+            .write_all(msg)
+            .map_err(|e| format!("Lost the connection: {e}"))
+    }
+    // spectators that have gone get dropped
+    fn broadcast(&mut self, msg: &[u8]) {
+        self.spectators.retain(|s| s.send(msg.to_vec()).is_ok());
+    }
+    // spectators who've connected since the last frame. only the session's
+    // already got its other player
+    fn accept(&mut self) {
+        let Some(listener) = &self.listener else {
+            return;
+        };
+        while let Ok((mut stream, from)) = listener.accept() {
+            // a quick hello, or they're turned away
+            let hello = stream
+                .set_nonblocking(false)
+                .and_then(|()| stream.set_read_timeout(Some(Duration::from_secs(1))))
+                .and_then(|()| read_bytes::<5>(&mut stream));
+            match hello {
+                Ok([magic @ .., VERSION]) if &magic == SPECTATOR => {
+                    println!("{from} is watching");
+                    self.joining.push(stream);
+                }
+                _ => turn_away(from),
+            }
+        }
+    }
+    // both game boys and the cable for the spectators, which new ones get
+    // straight away and everyone gets every STATE_FRAMES frames. it goes
+    // before the left game boy's input for the frame, so they load it at
+    // the same point
+    fn send_state(&mut self, emus: &[Emulator; 2], cable: &Cable) {
+        self.accept();
+        let due = emus[0].frame_count().is_multiple_of(STATE_FRAMES);
+        if self.joining.is_empty() && (!due || self.spectators.is_empty()) {
+            return;
+        }
+        let mut msg = vec![STATE];
+        let [left, right] = emus
+            .each_ref()
+            .map(|emu| emu.compact_state(None, DEFAULT_COMPRESSION));
+        for state in [left, right, cable.state()] {
+            msg.extend((state.len() as u32).to_le_bytes());
+            msg.extend(state);
+        }
+        let joining = self.joining.drain(..).map(writer);
+        self.spectators.extend(joining);
+        self.broadcast(&msg);
+    }
+    // waits for the next message. returns false if the other end quit
+    fn receive(&mut self, inputs: &mut [VecDeque<u8>; 2]) -> Result<bool, String> {
+        let err = |e: io::Error| format!("Lost the connection: {e}");
+        match read_bytes::<1>(&mut self.stream).map_err(err)? {
+            [INPUT] => {
+                let [console, held] = read_bytes(&mut self.stream).map_err(err)?;
+                let inputs = inputs.get_mut(console as usize);
+                inputs
+                    .ok_or("Got an input for a third game boy")?
+                    .push_back(held);
+            }
+            [HASH] => {
+                let frame = u64::from_le_bytes(read_bytes(&mut self.stream).map_err(err)?);
+                let hash = u64::from_le_bytes(read_bytes(&mut self.stream).map_err(err)?);
+                self.theirs.push_back((frame, hash));
+                self.compare()?;
+            }
+            [STATE] => {
+                let mut state = || {
+                    let len = u32::from_le_bytes(read_bytes(&mut self.stream).map_err(err)?);
+                    if len > MAX_STATE {
+                        return Err(format!("Got a state of {len} bytes from the host"));
+                    }
+                    let mut state = vec![0; len as usize];
+                    self.stream.read_exact(&mut state).map_err(err)?;
+                    Ok(state)
+                };
+                self.state = Some([state()?, state()?, state()?]);
+            }
+            [QUIT] => return Ok(false),
+            [other] => return Err(format!("Got a message netplay doesn't know: ${other:02x}")),
+        }
+        Ok(true)
+    }
+    fn add_hash(&mut self, frame: u64, hash: u64) -> Result<(), String> {
+        let mut msg = vec![HASH];
+        msg.extend(frame.to_le_bytes());
+        msg.extend(hash.to_le_bytes());
+        if self.player {
+            self.send(&msg)?;
+        }
+        self.broadcast(&msg);
+        self.ours.push_back((frame, hash));
+        self.compare()
+    }
+    // both ends hash the same frames in the same order, so they pair up. a
+    // spectator carries on, since the next state puts it right
+    fn compare(&mut self) -> Result<(), String> {
+        while !self.ours.is_empty() && !self.theirs.is_empty() {
+            let ((frame, ours), (_, theirs)) = (self.ours[0], self.theirs[0]);
+            if ours != theirs && self.player {
+                return Err(format!("The game boys desynced by frame {frame}"));
+            } else if ours != theirs {
+                eprintln!("Drifted from the host by frame {frame}, until its next state");
+            }
+            self.ours.pop_front();
+            self.theirs.pop_front();
+        }
+        Ok(())
+    }
+}
+
+struct Session {
+    peer: Peer,
+    emus: [Emulator; 2],
+    // which game boy is played here, or None for a spectator
+    local: Option<usize>,
+    // frames of inputs that are sent ahead
+    delay: u8,
+}
+
+// emu is this end's game boy, with its save loaded, or the host's for a
+// spectator. the rest are made with new_emu and get their saves over the
+// network. a player's save is written when the session ends
+pub fn run(
+    role: &Role,
+    delay: u8,
+    emu: Emulator,
+    new_emu: impl Fn() -> Emulator,
+    bindings: &Bindings,
+    save: Option<&SaveFile>,
+) -> ExitCode {
+    let session = match role {
+        Role::Host(addr, watchers) => {
+            host(addr, *watchers).and_then(|(stream, spectators, listener)| {
+                let spectators = (spectators, Some(listener));
+                start(stream, spectators, 0, delay, emu, &new_emu)
+            })
+        }
+        Role::Join(addr) => connect(addr)
+            .and_then(|stream| start(stream, (Vec::new(), None), 1, delay, emu, &new_emu)),
+        Role::Watch(addr) => watch(addr, emu, &new_emu),
+    };
+    let mut session = match session {
+        Ok(session) => session,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let exit = match play(&mut session, bindings) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    };
+    session.peer.broadcast(&[QUIT]);
+    if let Some(local) = session.local
+        && let Some(save) = save
+        && let Err(e) = save.write(&session.emus[local].memory_region("sram").unwrap())
+    {
+        eprintln!("Unable to write {}: {e}", save.path().display());
+    }
+    exit
+}
+
+// swaps game boys with the other player, the host's going on the left. the
+// host has the spectators so far and the listener for the rest
+fn start(
+    mut stream: TcpStream,
+    (spectators, listener): (Vec<TcpStream>, Option<TcpListener>),
+    local: usize,
+    delay: u8,
+    emu: Emulator,
+    new_emu: &impl Fn() -> Emulator,
+) -> Result<Session, String> {
+    let err = |e: io::Error| format!("Unable to start netplay: {e}");
+    // the inputs are tiny and can't wait around to be batched up
+    stream.set_nodelay(true).map_err(err)?;
+    stream.set_read_timeout(Some(TIMEOUT)).map_err(err)?;
+    let mut hello = vec![delay];
+    write_console(&mut hello, &emu);
+    stream.write_all(&hello).map_err(err)?;
+    // only the host's delay gets used
+    let [their_delay] = read_bytes(&mut stream).map_err(err)?;
+    let delay = if local == 0 { delay } else { their_delay };
+    let other = read_console(&mut stream, &emu, new_emu)?;
+    let emus = if local == 0 {
+        [emu, other]
+    } else {
+        [other, emu]
+    };
+    let peer = Peer {
+        stream,
+        player: true,
+        ours: VecDeque::new(),
+        theirs: VecDeque::new(),
+        spectators: Vec::new(),
+        listener,
+        joining: spectators,
+        state: None,
+    };
+    Ok(Session {
+        peer,
+        emus,
+        local: Some(local),
+        delay,
+    })
+}
+
+fn watch(addr: &str, emu: Emulator, new_emu: &impl Fn() -> Emulator) -> Result<Session, String> {
+    let err = |e: io::Error| format!("Unable to watch: {e}");
+    let mut stream = TcpStream::connect(addr).map_err(err)?;
+    let mut hello = SPECTATOR.to_vec();
+    hello.push(VERSION);
+    stream.write_all(&hello).map_err(err)?;
+    println!("Waiting for the session to start");
+    // the host's first state, which may be a while if it's still waiting
+    // for the other player
+    stream.peek(&mut [0]).map_err(err)?;
+    stream.set_read_timeout(Some(TIMEOUT)).map_err(err)?;
+    let mut other = new_emu();
+    let _ = other.load_bytes(emu.rom());
+    let peer = Peer {
+        stream,
+        player: false,
+        ours: VecDeque::new(),
+        theirs: VecDeque::new(),
+        spectators: Vec::new(),
+        listener: None,
+        joining: Vec::new(),
+        state: None,
+    };
+    // both start from the state that comes before the first input, then
+    // the host sends the inputs it applies
+    let emus = [emu, other];
+    Ok(Session {
+        peer,
+        emus,
+        local: None,
+        delay: 0,
+    })
+}
+
+fn play(session: &mut Session, bindings: &Bindings) -> Result<(), String> {
+    let Session {
+        peer,
+        emus,
+        local,
+        delay,
+    } = session;
+    let local = *local;
+    let ends = link_cable();
+    let cable = ends[0].cable();
+    for (emu, end) in zip(emus.iter_mut(), ends) {
+        emu.set_serial_device(Some(Box::new(end)));
+    }
+    // the buttons for each game boy's coming frames, with nothing held for
+    // the ones before the first inputs arrive
+    let mut inputs = [(); 2].map(|()| VecDeque::from(vec![0; *delay as usize]));
+    let mut disp = match local {
+        Some(local) => {
+            let mut disp = Display::new();
+            disp.set_title(&format!("Gameboy (netplay, player {})", local + 1));
+            disp
+        }
+        None => {
+            let mut disp = Display::with_screens(2);
+            disp.set_title("Gameboy (netplay, watching)");
+            disp
+        }
+    };
+    disp.show();
+    // the screen's paced by the local game boy, or the left one
+    let paced = local.unwrap_or(0);
+    let mut deadline = Instant::now();
+    loop {
+        let done = run_linked_slice(emus);
+        for (i, drawn) in done.into_iter().enumerate() {
+            let Some(drawn) = drawn else {
+                continue;
+            };
+            if i == paced {
+                for event in disp.events() {
+                    if let Event::Quit { .. }
+                    | Event::KeyDown {
+                        keycode: Some(Keycode::Escape),
+                        ..
+                    } = event
+                    {
+                        return match local {
+                            Some(_) => peer.send(&[QUIT]),
+                            None => Ok(()),
+                        };
+                    }
+                }
+            }
+            if local == Some(i) {
+                let held = Button::ALL.iter().enumerate();
+                let held = held
+                    .filter(|&(_, &button)| disp.key_down(bindings.key(button)))
+                    .fold(0, |held, (n, _)| held | 1 << n);
+                peer.send(&[INPUT, i as u8, held])?;
+                inputs[i].push_back(held);
+            }
+            if i == 0 && local == Some(0) {
+                peer.send_state(emus, &cable);
+            }
+            while inputs[i].is_empty() {
+                if !peer.receive(&mut inputs)? {
+                    println!("The other end left");
+                    return Ok(());
+                }
+            }
+            if let Some([left, right, wire]) = peer.state.take() {
+                let err = |e: StateError| format!("Unable to load the host's state: {e}");
+                emus[0].restore_state(&left).map_err(err)?;
+                emus[1].restore_state(&right).map_err(err)?;
+                cable
+                    .restore(&wire)
+                    .ok_or("Unable to load the host's state: the cable's is corrupt")?;
+            }
+            let held = inputs[i].pop_front().unwrap();
+            peer.broadcast(&[INPUT, i as u8, held]);
+            for (n, button) in Button::ALL.into_iter().enumerate() {
+                emus[i].set_button(button, held & (1 << n) > 0);
+            }
+            let frame = emus[0].frame_count();
+            if i == 0 && frame.is_multiple_of(HASH_FRAMES) {
+                let hash = emus[0].state_hash() ^ emus[1].state_hash().rotate_left(1);
+                peer.add_hash(frame, hash)?;
+            }
+            if drawn && local.is_none_or(|local| local == i) {
+                let screen = if local.is_some() { 0 } else { i as u32 };
+                disp.update_with(screen, |pixels, pitch| emus[i].write_rgba(pixels, pitch));
+            }
+            if i == paced {
+                disp.present();
+                deadline += FRAME_DUR;
+                let now = Instant::now();
+                if now < deadline {
+                    wait_until(deadline);
+                } else if now - deadline > 4 * FRAME_DUR {
+                    deadline = now;
+                }
+            }
+        }
+    }
+}
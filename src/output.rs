@@ -0,0 +1,152 @@
+// where screenshots, gifs, videos, audio recordings and triggered save
+// states go. each kind has a
+// name template under the [output] section's dir, like
+//   [output]
+//   dir = "/home/me/Pictures/sethboy"
+//   screenshot = "{title}/{time}-{frame}"
+// where {title} is the header's, {rom} is the rom's file name without the
+// extension, {time} is when it was taken (in utc) and {frame} is the frame
+// count. the extension goes on the end, and directories are made as needed
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use gameboy::emulator::Emulator;
+
+use crate::{config::Config, state_info::utc};
+
+#[derive(Clone, Copy)]
+pub enum Output {
+    Screenshot,
+    Gif,
+    Video,
+    Audio,
+    State,
+}
+
+impl Output {
+    const ALL: [Output; 5] = [
+        Output::Screenshot,
+        Output::Gif,
+        Output::Video,
+        Output::Audio,
+        Output::State,
+    ];
+    // the config key, the template without one, and the extension
+    fn info(self) -> (&'static str, &'static str, &'static str) {
+        match self {
+            Output::Screenshot => ("screenshot", "screenshots/{rom}-{time}", "png"),
+            Output::Gif => ("gif", "gifs/{rom}-{time}", "gif"),
+            Output::Video => ("video", "videos/{rom}-{time}", "avi"),
+            Output::Audio => ("audio", "audio/{rom}-{time}", "wav"),
+            Output::State => ("state", "states/{rom}-{time}", "state"),
+        }
+    }
+}
+
+pub struct OutputPaths {
+    dir: PathBuf,
+    // by Output
+    templates: Vec<String>,
+    rom: String,
+}
+
+// $XDG_DATA_HOME/sethboy, or under ~/.local/share without it. windows has
+// neither, so there it's wherever it runs from
+fn default_dir() -> PathBuf {
+    let dir = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")));
+    dir.map(|dir| dir.join("sethboy")).unwrap_or_default()
+}
+
+// for titles and rom names, which can have anything in them. an empty one
+// would make {title}/... start at the root
+fn safe(name: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+    let name: String = name
+        .trim()
+        .chars()
+        .map(|c| if safe(c) { c } else { '_' })
+        .collect();
+    if name.is_empty() {
+        "untitled".into()
+    } else {
+        name
+    }
+}
+
+// like 20261016-093000
+fn timestamp() -> String {
+    let secs = UNIX_EPOCH.elapsed().unwrap_or_default().as_secs();
+    let [year, month, day, h, m, s] = utc(secs);
+    format!("{year}{month:02}{day:02}-{h:02}{m:02}{s:02}")
+}
+
+// fills in the template, or says which {name} it doesn't know
+fn expand(template: &str, fill: impl Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out += &rest[..start];
+        let end = rest[start..].find('}').ok_or("a { without a }")? + start;
+        let name = &rest[start + 1..end];
+        out += &fill(name).ok_or(format!("there's no {{{name}}}"))?;
+        rest = &rest[end + 1..];
+    }
+    Ok(out + rest)
+}
+
+impl OutputPaths {
+    pub fn load(config: &Config, rom: &str) -> Result<Self, String> {
+        let path = config.path().display();
+        let dir = config
+            .get("output", "dir")
+            .map_or_else(default_dir, Into::into);
+        let mut templates = Vec::new();
+        for kind in Output::ALL {
+            let (key, default, _) = kind.info();
+            let template = config.get("output", key).unwrap_or(default);
+            // caught now rather than when something's first saved
+            let known = ["title", "rom", "time", "frame"];
+            expand(template, |name| known.contains(&name).then(String::new))
+                .map_err(|e| format!("{path}: output.{key}: {e}"))?;
+            templates.push(template.into());
+        }
+        Ok(OutputPaths {
+            dir,
+            templates,
+            rom: safe(rom),
+        })
+    }
+    // somewhere new to save kind, with its directory already made
+    pub fn path(&self, kind: Output, emu: &Emulator) -> io::Result<PathBuf> {
+        let (_, _, ext) = kind.info();
+        let name = expand(&self.templates[kind as usize], |name| match name {
+            "title" => Some(safe(&emu.rom_title())),
+            "rom" => Some(self.rom.clone()),
+            "time" => Some(timestamp()),
+            "frame" => Some(emu.frame_count().to_string()),
+            _ => None,
+        });
+        let base = self.dir.join(name.map_err(io::Error::other)?);
+        if let Some(dir) = base.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let with_ext = |ext: &str| {
+            let mut path = base.clone().into_os_string();
+            path.push(ext);
+            PathBuf::from(path)
+        };
+        // two in the same second get numbered
+        let mut path = with_ext(&format!(".{ext}"));
+        let mut n = 2;
+        while path.exists() {
+            path = with_ext(&format!("-{n}.{ext}"));
+            n += 1;
+        }
+        Ok(path)
+    }
+}
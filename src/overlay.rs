@@ -0,0 +1,73 @@
+// the performance overlay f3 turns on, drawn in the corner of the game with
+// the menu's font. it's the speed over the last second, a bar per frame for
+// how long it took to reach the screen, how full the audio queue is against
+// the latency it's meant to keep and how full the rewind buffer is
+use std::{ops::Range, time::Duration};
+
+use gameboy::emulator::constants::*;
+
+use crate::{FRAME_DUR, audio::AudioStats, menu, rewind::Rewind, timing::FrameTimes};
+
+// a pixel wide bar for each
+const GRAPH_FRAMES: usize = 60;
+const GRAPH_Y: usize = 16;
+const LINE_Y: usize = 8;
+const ON_TIME: [u8; 4] = [0x40, 0xE0, 0x40, 0xFF];
+const LATE: [u8; 4] = [0xE0, 0x40, 0x40, 0xFF];
+const TARGET: [u8; 4] = [0x80, 0x80, 0x80, 0xFF];
+
+// speed is a percentage of a real game boy
+pub fn draw(
+    rgba: &mut [u8; SCRN_X * SCRN_Y * 4],
+    speed: f64,
+    times: &FrameTimes,
+    audio: Option<AudioStats>,
+    rewind: &Rewind,
+) {
+    let recent = times.recent(GRAPH_FRAMES);
+    let ms = |time: Duration| time.as_secs_f64() * 1000.0;
+    let average = recent.iter().sum::<Duration>() / recent.len().max(1) as u32;
+    let audio = match audio {
+        Some(audio) if !audio.latency.is_zero() => {
+            let fill = audio.queued.as_secs_f64() / audio.latency.as_secs_f64();
+            format!("audio {:.0}%", fill * 100.0)
+        }
+        Some(_) => "audio 0%".into(),
+        None => "no audio".into(),
+    };
+    let (held, max) = rewind.usage();
+    let lines = [
+        format!("speed {speed:.0}%"),
+        format!("frame {:.1}ms", ms(average)),
+        audio,
+        format!("rewind {held}/{max}"),
+    ];
+    let height = lines.len() * LINE_Y + GRAPH_Y + 4;
+    fill(rgba, 0..GRAPH_FRAMES + 4, 0..height, menu::BOX);
+    for (i, line) in lines.iter().enumerate() {
+        for (j, c) in line.chars().enumerate() {
+            menu::draw_char(rgba, 2 + j * menu::CHAR_X, 2 + i * LINE_Y, c);
+        }
+    }
+    // three frames' worth fits, like the debugger's graph
+    let bottom = height - 2;
+    let bar = |time: Duration| {
+        let frames = time.as_secs_f64() / FRAME_DUR.as_secs_f64();
+        ((frames / 3.0).min(1.0) * GRAPH_Y as f64) as usize
+    };
+    for (i, &time) in recent.iter().enumerate() {
+        let late = time.as_secs_f64() > FRAME_DUR.as_secs_f64() * 1.5;
+        let color = if late { LATE } else { ON_TIME };
+        fill(rgba, 2 + i..3 + i, bottom - bar(time)..bottom, color);
+    }
+    let target = bottom - bar(FRAME_DUR);
+    fill(rgba, 2..2 + GRAPH_FRAMES, target..target + 1, TARGET);
+}
+
+fn fill(rgba: &mut [u8; SCRN_X * SCRN_Y * 4], xs: Range<usize>, ys: Range<usize>, color: [u8; 4]) {
+    for y in ys {
+        for x in xs.clone() {
+            rgba[(y * SCRN_X + x) * 4..][..4].copy_from_slice(&color);
+        }
+    }
+}
@@ -0,0 +1,17 @@
+// writes out --cycle-profile. a file named like callgrind.out.tetris, which is
+// what kcachegrind looks for, gets callgrind's format and anything else the
+// flat profile
+use std::{fs, path::Path};
+
+use gameboy::emulator::*;
+
+pub fn write(emu: &Emulator, path: &str, symbols: &Symbols, rom: &str) -> Result<(), String> {
+    let profile = emu.profile().ok_or("profiling isn't on")?;
+    let name = Path::new(path).file_name().unwrap_or_default();
+    let text = if name.to_string_lossy().starts_with("callgrind.out") {
+        profile.callgrind(symbols, rom)
+    } else {
+        profile.flat(symbols)
+    };
+    fs::write(path, text).map_err(|e| format!("Unable to write {path}: {e}"))
+}
@@ -0,0 +1,167 @@
+// a websocket server for driving the emulator from other programs. each
+// text message is a json command like {"cmd": "peek", "addr": 49152} and
+// gets a json reply, except screenshot which replies with a png and
+// save_state with the state. a binary message is a state to load
+use std::{
+    io,
+    net::TcpListener,
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+use gameboy::emulator::*;
+use serde_json::{Value, json};
+use tungstenite::Message;
+
+// what a connection sent, with where to send the reply
+type Command = (Request, Sender<Message>);
+
+enum Request {
+    Json(Value),
+    LoadState(Vec<u8>),
+}
+
+pub struct Remote {
+    // commands from every connection
+    commands: Receiver<Command>,
+}
+
+fn error(msg: impl Into<String>) -> Message {
+    Message::text(json!({ "error": msg.into() }).to_string())
+}
+
+fn ok(mut reply: Value) -> Message {
+    reply["ok"] = true.into();
+    Message::text(reply.to_string())
+}
+
+// the address field of a command, which can be a number or "$c000" style hex
+fn addr(cmd: &Value) -> Option<u16> {
+    match &cmd["addr"] {
+        Value::Number(n) => n.as_u64()?.try_into().ok(),
+        Value::String(s) => u16::from_str_radix(s.strip_prefix('$')?, 16).ok(),
+        _ => None,
+    }
+}
+
+fn run(emu: &mut Emulator, cmd: &Value) -> Message {
+    match cmd["cmd"].as_str().unwrap_or_default() {
+        "pause" => {
+            emu.pause();
+            ok(json!({}))
+        }
+        "resume" => {
+            emu.resume();
+            ok(json!({}))
+        }
+        "peek" => {
+            let Some(addr) = addr(cmd) else {
+                return error("peek needs an addr");
+            };
+            // the whole address space at most, wrapping past $ffff
+            let len = cmd["len"].as_u64().unwrap_or(1).min(0x10000) as usize;
+            let bytes: Vec<u8> = (0..len)
+                .map(|i| emu.ram.read(addr.wrapping_add(i as u16)))
+                .collect();
+            ok(json!({ "bytes": bytes }))
+        }
+        "poke" => {
+            let (Some(addr), Some(bytes)) = (addr(cmd), cmd["bytes"].as_array()) else {
+                return error("poke needs an addr and bytes");
+            };
+            for (i, byte) in bytes.iter().enumerate() {
+                let Some(byte) = byte.as_u64().and_then(|b| u8::try_from(b).ok()) else {
+                    return error("bytes have to be numbers from 0 to 255");
+                };
+                emu.ram.write(addr.wrapping_add(i as u16), byte);
+            }
+            ok(json!({}))
+        }
+        "press" | "release" => {
+            let Some(button) = cmd["button"].as_str().and_then(Button::from_name) else {
+                return error(format!(
+                    "button must be one of: {}",
+                    BUTTON_NAMES.join(", ")
+                ));
+            };
+            emu.set_button(button, cmd["cmd"] == "press");
+            ok(json!({}))
+        }
+        "screenshot" => {
+            let mut png = Vec::new();
+            match emu.screenshot(&mut png) {
+                Ok(()) => Message::binary(png),
+                Err(e) => error(e.to_string()),
+            }
+        }
+        "save_state" => {
+            let mut state = Vec::new();
+            match emu.save_state(&mut state, DEFAULT_COMPRESSION) {
+                Ok(()) => Message::binary(state),
+                Err(e) => error(e.to_string()),
+            }
+        }
+        "load_state" => error("load_state takes the state as a binary message"),
+        "" => error("commands need a cmd field"),
+        other => error(format!("unknown command: {other}")),
+    }
+}
+
+impl Remote {
+    // starts accepting connections on addr in the background
+    pub fn listen(addr: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let (tx, commands) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let tx = tx.clone();
+                thread::spawn(move || serve(stream, tx));
+            }
+        });
+        Ok(Remote { commands })
+    }
+    // runs whatever commands came in since the last call
+    pub fn poll(&self, emu: &mut Emulator) {
+        for (request, reply) in self.commands.try_iter() {
+            let msg = match request {
+                Request::Json(cmd) => run(emu, &cmd),
+                Request::LoadState(state) => match emu.restore_state(&state) {
+                    Ok(()) => ok(json!({})),
+                    Err(e) => error(e.to_string()),
+                },
+            };
+            let _ = reply.send(msg);
+        }
+    }
+}
+
+fn serve(stream: std::net::TcpStream, commands: Sender<Command>) {
+    let Ok(mut ws) = tungstenite::accept(stream) else {
+        return;
+    };
+    let (tx, replies) = mpsc::channel();
+    while let Ok(msg) = ws.read() {
+        let request = match msg {
+            Message::Text(text) => serde_json::from_str(&text).map(Request::Json),
+            Message::Binary(state) => Ok(Request::LoadState(state.to_vec())),
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        let reply = match request {
+            Ok(request) => {
+                // the emulator's gone, so there's nobody left to answer
+                if commands.send((request, tx.clone())).is_err() {
+                    break;
+                }
+                let Ok(reply) = replies.recv() else {
+                    break;
+                };
+                reply
+            }
+            Err(e) => error(format!("invalid json: {e}")),
+        };
+        if ws.send(reply).is_err() {
+            break;
+        }
+    }
+}
@@ -0,0 +1,85 @@
+// holding f6 rewinds. a snapshot is kept every few frames, each stored as
+// the changes from the one before it, with a whole one every so often so
+// going back doesn't have to replay everything since the oldest
+use std::collections::VecDeque;
+
+use gameboy::emulator::*;
+
+// frames between snapshots
+const INTERVAL: u64 = 4;
+// snapshots from one whole one to the next
+const GROUP_LEN: usize = 30;
+// about 20 seconds' worth
+const MAX_SNAPSHOTS: usize = 300;
+
+pub struct Rewind {
+    // oldest first. each group starts with a whole snapshot and the rest
+    // are deltas against the one before
+    groups: VecDeque<Vec<Vec<u8>>>,
+    // the newest snapshot as Emulator::state has it, for the next delta.
+    // after rewinding there isn't one, so the next snapshot is whole
+    last: Option<Vec<u8>>,
+    level: u8,
+}
+
+impl Rewind {
+    // level is how hard snapshots get deflated
+    pub fn new(level: u8) -> Self {
+        Rewind {
+            groups: VecDeque::new(),
+            last: None,
+            level,
+        }
+    }
+    // takes a snapshot if one's due, after each frame
+    pub fn record(&mut self, emu: &Emulator) {
+        if !emu.frame_count().is_multiple_of(INTERVAL) {
+            return;
+        }
+        let full = self.groups.back().is_none_or(|g| g.len() == GROUP_LEN);
+        let base = self.last.as_deref().filter(|_| !full);
+        if base.is_none() {
+            self.groups.push_back(Vec::new());
+        }
+        let snapshot = emu.compact_state(base, self.level);
+        self.groups.back_mut().unwrap().push(snapshot);
+        self.last = Some(emu.state());
+        // the oldest go a group at a time, since the rest need its first
+        while self.groups.iter().map(Vec::len).sum::<usize>() > MAX_SNAPSHOTS {
+            self.groups.pop_front();
+        }
+    }
+    // snapshots held, and how many fit
+    pub fn usage(&self) -> (usize, usize) {
+        (self.groups.iter().map(Vec::len).sum(), MAX_SNAPSHOTS)
+    }
+    // goes back to the newest snapshot and drops it. returns false once
+    // there's nothing left to go back to
+    pub fn step(&mut self, emu: &mut Emulator) -> bool {
+        let Some(group) = self.groups.back_mut() else {
+            return false;
+        };
+        let result = Self::restore(emu, group);
+        group.pop();
+        if group.is_empty() {
+            self.groups.pop_back();
+        }
+        self.last = None;
+        if let Err(e) = result {
+            eprintln!("Unable to rewind: {e}");
+            self.groups.clear();
+            return false;
+        }
+        true
+    }
+    // loads the group's whole snapshot, then each delta up to its last
+    fn restore(emu: &mut Emulator, group: &[Vec<u8>]) -> Result<(), StateError> {
+        let (whole, deltas) = group.split_first().unwrap();
+        emu.restore_state(whole)?;
+        for delta in deltas {
+            let base = emu.state();
+            emu.restore_delta(delta, &base)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+// battery saves next to the rom, like tetris.sav, or tetris.kid.sav for a
+// profile called kid. writing one moves the old save to tetris.sav.1, that
+// one to tetris.sav.2 and so on, up to however many backups are kept. the
+// quick save state goes next to it as tetris.state
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+// how many old saves are kept without a backups setting
+pub const BACKUPS: usize = 5;
+
+pub struct SaveFile {
+    path: PathBuf,
+    backups: usize,
+}
+
+// profiles end up in file names, so they're kept to something safe there
+pub fn valid_profile(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn with_profile(rom: &Path, profile: Option<&str>, ext: &str) -> PathBuf {
+    match profile {
+        Some(profile) => rom.with_extension(format!("{profile}.{ext}")),
+        None => rom.with_extension(ext),
+    }
+}
+
+// where F5 saves a state and F8 loads it from
+pub fn state_path(rom: &Path, profile: Option<&str>) -> PathBuf {
+    with_profile(rom, profile, "state")
+}
+
+impl SaveFile {
+    pub fn new(rom: &Path, profile: Option<&str>, backups: usize) -> Self {
+        SaveFile {
+            path: with_profile(rom, profile, "sav"),
+            backups,
+        }
+    }
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    // returns None if there's no save yet
+    pub fn load(&self) -> Result<Option<Vec<u8>>, String> {
+        match fs::read(&self.path) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("Unable to read {}: {e}", self.path.display())),
+        }
+    }
+    // does nothing if the save hasn't changed, so playing without saving
+    // doesn't push the older backups out
+    pub fn write(&self, data: &[u8]) -> io::Result<()> {
+        match fs::read(&self.path) {
+            Ok(old) if old == data => return Ok(()),
+            Ok(_) => self.rotate()?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        fs::write(&self.path, data)
+    }
+    fn backup(&self, n: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{n}"));
+        path.into()
+    }
+    // the oldest falls off the end
+    fn rotate(&self) -> io::Result<()> {
+        if self.backups == 0 {
+            return Ok(());
+        }
+        for n in (1..self.backups).rev() {
+            let from = self.backup(n);
+            if from.exists() {
+                fs::rename(from, self.backup(n + 1))?;
+            }
+        }
+        fs::copy(&self.path, self.backup(1)).map(|_| ())
+    }
+}
@@ -0,0 +1,45 @@
+// a terminal on the link port, for homebrew that talks over serial while
+// it's being debugged. what the game sends goes to stdout as it is, and
+// what's typed goes to the game a byte at a time, which with the terminal
+// buffering lines means after enter. the game gets a typed byte back from
+// its own sends, or by waiting on the external clock
+use std::{
+    io::{Read, Write, stdin, stdout},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use gameboy::emulator::*;
+
+pub struct SerialConsole {
+    typed: Receiver<u8>,
+}
+
+impl SerialConsole {
+    pub fn new() -> Self {
+        let (tx, typed) = mpsc::channel();
+        // stdin blocks, so it gets a thread of its own
+        thread::spawn(move || {
+            for byte in stdin().lock().bytes() {
+                if byte.ok().is_none_or(|byte| tx.send(byte).is_err()) {
+                    break;
+                }
+            }
+        });
+        SerialConsole { typed }
+    }
+}
+
+impl SerialDevice for SerialConsole {
+    fn send(&mut self, byte: u8) -> Option<u8> {
+        let mut out = stdout().lock();
+        if let Err(e) = out.write_all(&[byte]).and_then(|()| out.flush()) {
+            eprintln!("Unable to write serial output: {e}");
+        }
+        // with nothing typed the line floats high
+        Some(self.typed.try_recv().unwrap_or(0xFF))
+    }
+    fn receive(&mut self, _sb: u8) -> Option<u8> {
+        self.typed.try_recv().ok()
+    }
+}
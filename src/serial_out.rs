@@ -0,0 +1,34 @@
+// where --serial-out sends the bytes games write to the link port, raw and
+// as they come rather than a logged line at a time
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write, stdout},
+};
+
+use gameboy::emulator::*;
+
+pub struct SerialOut {
+    out: Box<dyn Write>,
+}
+
+impl SerialOut {
+    // stdout, none, or a file
+    pub fn create(target: &str) -> io::Result<Option<Self>> {
+        let out: Box<dyn Write> = match target {
+            "none" => return Ok(None),
+            "stdout" => Box::new(stdout()),
+            path => Box::new(BufWriter::new(File::create(path)?)),
+        };
+        Ok(Some(SerialOut { out }))
+    }
+    // writes out whatever emu has sent since last time
+    pub fn drain(&mut self, emu: &mut Emulator) {
+        let bytes = emu.take_serial();
+        if bytes.is_empty() {
+            return;
+        }
+        if let Err(e) = self.out.write_all(&bytes).and_then(|()| self.out.flush()) {
+            eprintln!("Unable to write serial output: {e}");
+        }
+    }
+}
@@ -0,0 +1,126 @@
+// the link port over tcp, to another copy of the emulator with
+// --serial-connect on the other end of --serial-listen. unlike netplay
+// nothing's kept in step, so a byte takes as long as the network does and
+// only games that put up with a slow partner work. each message is a kind
+// and a byte
+use std::{
+    collections::VecDeque,
+    io::{self, ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream},
+};
+
+use gameboy::emulator::*;
+
+const SEND: u8 = b'S';
+const REPLY: u8 = b'R';
+// cycles between looking for anything new from the other end
+const READ_CYCLES: u32 = 1024;
+
+pub struct TcpBridge {
+    stream: TcpStream,
+    // a message cut off partway
+    partial: Option<u8>,
+    // bytes the other end sent on its clock, until there's a transfer here
+    // waiting for them
+    sends: VecDeque<u8>,
+    reply: Option<u8>,
+    sending: bool,
+    since_read: u32,
+    // after which it's as if the cable was pulled out
+    closed: bool,
+}
+
+impl TcpBridge {
+    // waits for the other end to connect
+    pub fn listen(addr: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        eprintln!("Waiting on {addr} for the other end of the link cable");
+        let (stream, _) = listener.accept()?;
+        Self::new(stream)
+    }
+    pub fn connect(addr: &str) -> io::Result<Self> {
+        Self::new(TcpStream::connect(addr)?)
+    }
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        stream.set_nonblocking(true)?;
+        Ok(TcpBridge {
+            stream,
+            partial: None,
+            sends: VecDeque::new(),
+            reply: None,
+            sending: false,
+            since_read: 0,
+            closed: false,
+        })
+    }
+    fn write(&mut self, kind: u8, byte: u8) {
+        if let Err(e) = self.stream.write_all(&[kind, byte]) {
+            self.close(e);
+        }
+    }
+    fn close(&mut self, e: io::Error) {
+        if !self.closed {
+            eprintln!("Lost the link cable connection: {e}");
+            self.closed = true;
+        }
+    }
+    fn read(&mut self) {
+        let mut buf = [0; 256];
+        loop {
+            let n = match self.stream.read(&mut buf) {
+                Ok(0) => return self.close(ErrorKind::UnexpectedEof.into()),
+                Ok(n) => n,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return,
+                Err(e) => return self.close(e),
+            };
+            for &b in &buf[..n] {
+                let Some(kind) = self.partial.take() else {
+                    self.partial = Some(b);
+                    continue;
+                };
+                match kind {
+                    // both ends clocking at once, so neither was listening
+                    SEND if self.sending => self.write(REPLY, 0xFF),
+                    SEND => self.sends.push_back(b),
+                    REPLY => self.reply = Some(b),
+                    _ => {
+                        return self.close(io::Error::other("not a link cable message"));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl SerialDevice for TcpBridge {
+    fn send(&mut self, byte: u8) -> Option<u8> {
+        if self.closed {
+            return Some(0xFF);
+        }
+        self.write(SEND, byte);
+        self.sending = true;
+        None
+    }
+    fn poll(&mut self) -> Option<u8> {
+        if self.closed {
+            self.sending = false;
+            return Some(0xFF);
+        }
+        let reply = self.reply.take()?;
+        self.sending = false;
+        Some(reply)
+    }
+    fn receive(&mut self, sb: u8) -> Option<u8> {
+        let byte = self.sends.pop_front()?;
+        self.write(REPLY, sb);
+        Some(byte)
+    }
+    fn tick(&mut self, t_cyc: u8) {
+        self.since_read += t_cyc as u32;
+        if self.since_read >= READ_CYCLES && !self.closed {
+            self.since_read = 0;
+            self.read();
+        }
+    }
+}
@@ -0,0 +1,46 @@
+// state-info prints what a save state says about itself, so slots can be
+// told apart without loading them
+use std::{fs, iter::zip, process::ExitCode};
+
+use gameboy::emulator::{StateError, StateInfo, THUMB_X};
+
+// the thumbnail is drawn in text at two rows of pixels a line
+pub fn print(path: &str) -> ExitCode {
+    let data = fs::read(path).map_err(StateError::Io);
+    let info = match data.and_then(|data| StateInfo::read(&data)) {
+        Ok(info) => info,
+        Err(e) => {
+            eprintln!("Unable to read {path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    println!("Title: {}", info.title);
+    println!("ROM crc32: {:08x}", info.rom_crc);
+    if info.saved_at > 0 {
+        let [year, month, day, h, m, s] = utc(info.saved_at);
+        println!("Saved: {year}-{month:02}-{day:02} {h:02}:{m:02}:{s:02} UTC");
+    }
+    println!("Frame: {}", info.frames);
+    const SHADES: [char; 4] = [' ', '░', '▒', '█'];
+    for rows in info.thumbnail.chunks_exact(THUMB_X * 2) {
+        let (top, bottom) = rows.split_at(THUMB_X);
+        let line = zip(top, bottom).map(|(&a, &b)| SHADES[(a.max(b) & 3) as usize]);
+        println!("{}", line.collect::<String>().trim_end());
+    }
+    ExitCode::SUCCESS
+}
+
+// seconds since 1970 as the year, month, day, hour, minute and second
+pub fn utc(secs: u64) -> [u64; 6] {
+    let (days, secs) = (secs / 86400, secs % 86400);
+    // days since 1970 to a date, from howard hinnant's civil_from_days
+    let z = days + 719468;
+    let (era, doe) = (z / 146097, z % 146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as u64;
+    [year, month, day, secs / 3600, secs / 60 % 60, secs % 60]
+}
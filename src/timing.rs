@@ -0,0 +1,78 @@
+// how evenly frames reach the screen. the gap between each present is kept,
+// along with how far the emulated time has drifted from the wall clock, for
+// --timing-report and the graph in the debugger window
+use std::time::{Duration, Instant};
+
+use crate::FRAME_DUR;
+
+#[derive(Default)]
+pub struct FrameTimes {
+    // the last present and the frame count then, or None after a pause
+    last: Option<(Instant, u64)>,
+    // the gap before each present
+    times: Vec<Duration>,
+    // frames that never made it to the screen
+    dropped: u64,
+    // only counting time spent running
+    wall: Duration,
+    emulated: Duration,
+}
+
+impl FrameTimes {
+    // frames is the emulator's frame count, which goes up by more than one
+    // when frames weren't presented
+    pub fn present(&mut self, now: Instant, frames: u64) {
+        if let Some((last, last_frames)) = self.last {
+            let ran = frames.saturating_sub(last_frames);
+            let time = now - last;
+            self.times.push(time);
+            self.wall += time;
+            self.emulated += FRAME_DUR * ran as u32;
+            // a present half a frame late missed its turn
+            let late = (time.as_secs_f64() / FRAME_DUR.as_secs_f64() - 0.5) as u64;
+            self.dropped += late.max(ran.saturating_sub(1));
+        }
+        self.last = Some((now, frames));
+    }
+    // the next present starts over, so menus and the debugger don't count
+    pub fn pause(&mut self) {
+        self.last = None;
+    }
+    // the last n frame times, oldest first
+    pub fn recent(&self, n: usize) -> &[Duration] {
+        &self.times[self.times.len().saturating_sub(n)..]
+    }
+    // how far ahead of the wall clock the emulation is, in seconds
+    pub fn drift(&self) -> f64 {
+        self.emulated.as_secs_f64() - self.wall.as_secs_f64()
+    }
+    pub fn report(&self) -> String {
+        if self.times.is_empty() {
+            return "No frames were presented".into();
+        }
+        let mut sorted = self.times.clone();
+        sorted.sort_unstable();
+        let ms = |time: Duration| format!("{:.2}ms", time.as_secs_f64() * 1000.0);
+        // the nearest rank
+        let percentile = |p: f64| {
+            let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+            ms(sorted[rank.clamp(1, sorted.len()) - 1])
+        };
+        let drift = self.drift();
+        let (drift, side) = (drift.abs(), if drift < 0.0 { "behind" } else { "ahead of" });
+        format!(
+            "{} frames over {:.1}s\n\
+             median {}, 90% {}, 99% {}, 99.9% {}, worst {}\n\
+             {} dropped frames\n\
+             the emulation ended {drift:.3}s {side} the wall clock",
+            self.times.len(),
+            self.wall.as_secs_f64(),
+            percentile(50.0),
+            percentile(90.0),
+            percentile(99.0),
+            percentile(99.9),
+            ms(sorted[sorted.len() - 1]),
+            self.dropped,
+        )
+    }
+}
@@ -0,0 +1,96 @@
+use gameboy::emulator::{Emulator, Renderer};
+
+#[test]
+fn reads_rom_bank_with_no_rom() {
+    let emu = Emulator::new();
+    assert_eq!(emu.ram.read(0x4000), 0xFF);
+}
+
+#[test]
+fn refuses_sample_rates_too_low_for_the_ring() {
+    let mut emu = Emulator::new();
+    assert!(!emu.set_sample_rate(3));
+    assert!(emu.set_sample_rate(0));
+    assert!(emu.set_sample_rate(8000));
+}
+
+// a rom that puts an all colour 3 object at x 37 on the top lines with scx at
+// 3, then spins
+fn object_rom() -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    // nop, jp 0x150
+    rom[0x100..0x104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+    #[rustfmt::skip]
+    let code = [
+        0xF3,                   // di
+        0xF0, 0x44,             // ldh a, (LY)
+        0xFE, 0x90,             // cp 144
+        0x38, 0xFA,             // jr c, -6
+        0xAF,                   // xor a
+        0xE0, 0x40,             // ldh (LCDC), a
+        0x21, 0x00, 0x82,       // ld hl, 0x8200
+        0x3E, 0xFF,             // ld a, 0xFF
+        0x06, 0x10,             // ld b, 16
+        0x22,                   // ld (hl+), a
+        0x05,                   // dec b
+        0x20, 0xFC,             // jr nz, -4
+        0x21, 0x04, 0xFE,       // ld hl, 0xFE04
+        0xAF,                   // xor a
+        0x06, 0x9C,             // ld b, 156
+        0x22,                   // ld (hl+), a
+        0x05,                   // dec b
+        0x20, 0xFC,             // jr nz, -4
+        0x21, 0x00, 0xFE,       // ld hl, 0xFE00
+        0x36, 0x10,             // ld (hl), 16
+        0x23,                   // inc hl
+        0x36, 0x25,             // ld (hl), 37
+        0x23,                   // inc hl
+        0x36, 0x20,             // ld (hl), 0x20
+        0x23,                   // inc hl
+        0x36, 0x00,             // ld (hl), 0
+        0x3E, 0x03,             // ld a, 3
+        0xE0, 0x43,             // ldh (SCX), a
+        0x3E, 0xE4,             // ld a, 0xE4
+        0xE0, 0x47,             // ldh (BGP), a
+        0xE0, 0x48,             // ldh (OBP0), a
+        0x3E, 0x93,             // ld a, 0x93
+        0xE0, 0x40,             // ldh (LCDC), a
+        0x18, 0xFE,             // jr -2
+    ];
+    rom[0x150..0x150 + code.len()].copy_from_slice(&code);
+    rom
+}
+
+#[test]
+fn fetches_objects_on_the_dot_the_fifo_refills() {
+    let frames = [Renderer::Fifo, Renderer::Scanline].map(|renderer| {
+        let mut emu = Emulator::new();
+        emu.set_renderer(renderer);
+        emu.load_bytes(&object_rom()).unwrap();
+        for _ in 0..3 {
+            while !emu.run_frame() {}
+        }
+        *emu.framebuffer()
+    });
+    // the second line, since the first after turning the lcd on has no objects
+    assert_eq!(frames[0][160 + 29], 3);
+    assert_eq!(frames[0], frames[1]);
+}
+
+#[test]
+fn writes_the_same_rgba_as_framebuffer_rgba() {
+    let mut emu = Emulator::new();
+    emu.load_bytes(&object_rom()).unwrap();
+    while !emu.run_frame() {}
+    let _ = emu.framebuffer_rgba();
+    for _ in 0..2 {
+        while !emu.run_frame() {}
+    }
+    // a pitch wider than a row, like a texture's can be
+    let mut pixels = vec![0; 200 * 4 * 144];
+    emu.write_rgba(&mut pixels, 200 * 4);
+    let rgba = *emu.framebuffer_rgba();
+    for (row, line) in rgba.chunks_exact(160 * 4).enumerate() {
+        assert_eq!(line, &pixels[row * 200 * 4..][..160 * 4]);
+    }
+}
@@ -0,0 +1,19 @@
+#!/bin/sh
+# runs the mealybug tearoom ppu tests through --golden, failing if any of
+# them do. the roms go in tests/mealybug and their expected dmg pngs in
+# tests/mealybug/expected, both from
+# https://github.com/mattcurrie/mealybug-tearoom-tests
+# without the roms it skips instead of failing
+cd "$(dirname "$0")/.." || exit 1
+dir=tests/mealybug
+if ! ls "$dir"/*.gb > /dev/null 2>&1; then
+    echo "Skipping mealybug tests: no roms in $dir" >&2
+    exit 0
+fi
+# --golden doesn't need a window
+cargo build --release --no-default-features --features std || exit 1
+failed=0
+for rom in "$dir"/*.gb; do
+    target/release/gameboy --golden "$dir/expected" "$rom" || failed=1
+done
+exit $failed